@@ -21,6 +21,7 @@ where
             break;
         }
         draw(renderer, &window_wrapper);
+        renderer.resolve();
         let pixel_row_width = renderer.width() as usize;
         window_wrapper.update(pixel_row_width, renderer.rgba_bytes())?;
         // std::thread::sleep(Duration::new(0, 70_000));
@@ -15,6 +15,7 @@ pub struct WindowWrapper {
     last_frame: Instant,
     frame_count: u32,
     fps: u32,
+    wheel: i32,
 }
 
 #[profiling::all_functions]
@@ -52,6 +53,7 @@ impl WindowWrapper {
             last_frame: Instant::now(),
             frame_count: 0,
             fps: 0,
+            wheel: 0,
         }
     }
 
@@ -77,6 +79,7 @@ impl WindowWrapper {
     }
 
     pub fn should_quit(&mut self) -> bool {
+        self.wheel = 0;
         for event in self.event_pump.poll_iter() {
             match event {
                 Event::Quit { .. }
@@ -84,6 +87,7 @@ impl WindowWrapper {
                     keycode: Some(Keycode::Escape),
                     ..
                 } => return true,
+                Event::MouseWheel { y, .. } => self.wheel += y,
                 _ => {}
             }
         }
@@ -95,4 +99,18 @@ impl WindowWrapper {
         let keyboard_state = sdl2::keyboard::KeyboardState::new(&self.event_pump);
         keyboard_state.is_scancode_pressed(code)
     }
+
+    /// Mouse movement in pixels since the previous call, and whether the left and right buttons
+    /// are held.
+    #[allow(dead_code)]
+    pub fn mouse_motion(&self) -> (i32, i32, bool, bool) {
+        let state = self.event_pump.relative_mouse_state();
+        (state.x(), state.y(), state.left(), state.right())
+    }
+
+    /// Mouse wheel steps scrolled during the current frame, positive away from the user.
+    #[allow(dead_code)]
+    pub fn mouse_wheel(&self) -> i32 {
+        self.wheel
+    }
 }
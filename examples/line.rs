@@ -2,7 +2,8 @@ mod common;
 
 use crate::common::window_wrapper::WindowWrapper;
 use tiny_soft_renderer::color::Color;
-use tiny_soft_renderer::math::Vec2u;
+use tiny_soft_renderer::line::{LineCap, LineJoin, LineStyle};
+use tiny_soft_renderer::math::Vec2;
 use tiny_soft_renderer::renderer::Renderer;
 
 fn main() {
@@ -17,18 +18,59 @@ fn main() {
 fn draw(renderer: &mut Renderer, _window: &WindowWrapper) {
     renderer.clear(Color::BLACK);
     renderer.draw_line(
-        &Vec2u { x: 20, y: 34 },
-        &Vec2u { x: 744, y: 400 },
+        &Vec2 { x: 20.0, y: 34.0 },
+        &Vec2 { x: 744.0, y: 400.0 },
         Color::RED,
     );
     renderer.draw_line(
-        &Vec2u { x: 120, y: 434 },
-        &Vec2u { x: 444, y: 400 },
+        &Vec2 { x: 120.0, y: 434.0 },
+        &Vec2 { x: 444.0, y: 400.0 },
         Color::GREEN,
     );
     renderer.draw_line(
-        &Vec2u { x: 330, y: 463 },
-        &Vec2u { x: 594, y: 200 },
+        &Vec2 { x: 330.0, y: 463.0 },
+        &Vec2 { x: 594.0, y: 200.0 },
         Color::BLUE,
     );
+
+    // anti-aliased, the second one leaves the window
+    renderer.draw_line_aa(
+        &Vec2 { x: 20.0, y: 534.0 },
+        &Vec2 { x: 744.0, y: 600.0 },
+        Color::WHITE,
+    );
+    renderer.draw_line_aa(
+        &Vec2 { x: 400.0, y: 700.0 },
+        &Vec2 {
+            x: 1000.0,
+            y: 900.0,
+        },
+        Color::WHITE,
+    );
+
+    let style = LineStyle {
+        width: 12.0,
+        cap: LineCap::Round,
+        join: LineJoin::Miter,
+        ..Default::default()
+    };
+    let points = [
+        Vec2 { x: 60.0, y: 100.0 },
+        Vec2 { x: 200.0, y: 250.0 },
+        Vec2 { x: 340.0, y: 120.0 },
+        Vec2 { x: 480.0, y: 260.0 },
+    ];
+    renderer.draw_polyline(&points, &style, Color::rgb(255, 200, 0));
+
+    let dashed = LineStyle {
+        width: 4.0,
+        join: LineJoin::Round,
+        dash: vec![24.0, 12.0],
+        ..Default::default()
+    };
+    let points = points.map(|p| Vec2 {
+        x: p.x + 200.0,
+        y: p.y + 420.0,
+    });
+    renderer.draw_polyline(&points, &dashed, Color::rgb(0, 200, 255));
 }
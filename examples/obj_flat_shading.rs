@@ -1,20 +1,22 @@
 mod common;
 
 use sdl2::keyboard::Scancode;
-use tiny_soft_renderer::color::Color;
-use tiny_soft_renderer::math::{vec3, Mat4, Mat4x1, Vec2u, Vec3};
-use tiny_soft_renderer::model::Model;
+use tiny_soft_renderer::camera::{Camera, CameraController, CameraInput, OrbitController};
+use tiny_soft_renderer::color::{Color, LinearColor};
+use tiny_soft_renderer::light::{Light, Lighting};
+use tiny_soft_renderer::math::{vec2, vec3, Mat4, Mat4x1, Vec2, Vec3, Vec4};
+use tiny_soft_renderer::model::{Model, Vertex};
+use tiny_soft_renderer::postprocess::{AmbientOcclusion, Ssao};
+use tiny_soft_renderer::rasterizer::{CullMode, PrimitiveTopology, RasterizerState};
 use tiny_soft_renderer::renderer::Renderer;
+use tiny_soft_renderer::shader::{Fragment, Instance, Shader};
 use tiny_soft_renderer::texture::Texture;
+use tiny_soft_renderer::viewport::Viewport;
 
 const WIDTH: u32 = 800;
 const HEIGHT: u32 = 800;
 const WINDOW_SCALE: u32 = 1;
 
-const DEPTH: f32 = 255.0;
-
-const CAMERA_POSITION: Vec3 = vec3(0.0, 0.0, 3.0);
-
 enum DrawMode {
     DiffusePerspective,
     Diffuse,
@@ -24,10 +26,19 @@ enum DrawMode {
 }
 
 fn main() {
-    let title = "Playground, press A/W/S/D to change shading mode";
+    let title = "Playground, press A/W/S/D to change shading mode, Q to turn off ambient \
+        occlusion, drag to orbit, scroll to zoom";
     let mut renderer = Renderer::new(WIDTH, HEIGHT, true);
+    renderer.set_rasterizer_state(RasterizerState {
+        cull_mode: CullMode::Back,
+        ..Default::default()
+    });
     let diffuse = Texture::load_tga_texture("assets/textures/african_head_diffuse.tga").unwrap();
-    let model = Model::load_obj_model("assets/models/african_head.obj", diffuse).unwrap();
+    let mut model = Model::load_obj_model("assets/models/african_head.obj", diffuse).unwrap();
+    model.optimize();
+    // the same view as a pinhole 3 units in front of the model, with the model filling the image
+    let mut camera = Camera::new(2.0 * (1.0f32 / 3.0).atan(), 1.0, 0.1, 100.0);
+    let mut orbit = OrbitController::new(Vec3::ZERO, 3.0);
 
     common::run(
         title,
@@ -46,149 +57,216 @@ fn main() {
             } else if window.is_key_pressed(Scancode::W) {
                 draw_mode = DrawMode::Diffuse;
             }
-            draw(&model, renderer, draw_mode);
+            let (dx, dy, left, right) = window.mouse_motion();
+            let drag = vec2(dx as f32, dy as f32);
+            let input = CameraInput {
+                look: if left { drag } else { Vec2::ZERO },
+                pan: if right { drag } else { Vec2::ZERO },
+                zoom: window.mouse_wheel() as f32,
+                ..Default::default()
+            };
+            camera.aspect = renderer.width() as f32 / renderer.height() as f32;
+            orbit.update(&mut camera, &input);
+            let ambient_occlusion = !window.is_key_pressed(Scancode::Q);
+            draw(&model, renderer, draw_mode, &camera, ambient_occlusion);
         },
     )
     .unwrap();
 }
 
-fn viewport(x: i32, y: i32, width: i32, height: i32) -> Mat4 {
-    let mut m = Mat4::identity();
-    m[(0, 3)] = x as f32 + width as f32 / 2.0;
-    m[(1, 3)] = y as f32 + height as f32 / 2.0;
-    m[(2, 3)] = DEPTH / 2.0;
-    m[(0, 0)] = width as f32 / 2.0;
-    m[(1, 1)] = height as f32 / 2.0;
-    m[(2, 2)] = DEPTH / 2.0;
-    m
-}
-
-fn draw(model: &Model, renderer: &mut Renderer, draw_mode: DrawMode) {
+fn draw(
+    model: &Model,
+    renderer: &mut Renderer,
+    draw_mode: DrawMode,
+    camera: &Camera,
+    ambient_occlusion: bool,
+) {
     renderer.clear(Color::BLACK);
     let half_width = renderer.width() as f32 / 2.0;
     let half_height = renderer.height() as f32 / 2.0;
     let light_dir = vec3(0.0, 0.0, -1.0);
-    let viewport = viewport(
-        (renderer.width() as f32 / 8.0) as i32,
-        (renderer.height() as f32 / 8.0) as i32,
-        (renderer.width() as f32 * 0.75) as i32,
-        (renderer.height() as f32 * 0.75) as i32,
-    );
-    let mut projection = Mat4::identity();
-    projection[(3, 2)] = -1.0 / CAMERA_POSITION.z;
+    let (width, height) = (renderer.width() as f32, renderer.height() as f32);
+    // only the perspective mode maps through the viewport, the others fill the window
+    let viewport = match draw_mode {
+        DrawMode::DiffusePerspective => {
+            Viewport::new(width / 8.0, height / 8.0, width * 0.75, height * 0.75)
+        }
+        _ => Viewport::new(0.0, 0.0, width, height),
+    };
+    renderer.set_viewport(viewport);
+    match draw_mode {
+        DrawMode::DiffusePerspective | DrawMode::Diffuse => {
+            // the perspective mode lights the model relative to the camera
+            let (transform, lighting) = match draw_mode {
+                DrawMode::DiffusePerspective => (
+                    camera.view_projection_matrix(),
+                    Lighting::key_fill_rim(camera),
+                ),
+                _ => (
+                    Mat4::identity(),
+                    Lighting {
+                        lights: vec![Light::directional(light_dir, Vec3::ONE, 1.0)],
+                        ..Default::default()
+                    },
+                ),
+            };
+            let mut shader = DiffuseShader {
+                transform,
+                lighting: &lighting,
+                diffuse: &model.diffuse,
+                occlusion: None,
+            };
+            renderer.draw_model(&shader, model, &transform);
+            if ambient_occlusion && matches!(draw_mode, DrawMode::DiffusePerspective) {
+                // the first pass provides the depth, the second one the occluded ambient light
+                let occlusion =
+                    Ssao::new(camera.projection_matrix()).compute(&renderer.frame_buffers());
+                renderer.clear(Color::BLACK);
+                shader.occlusion = Some(&occlusion);
+                renderer.draw_model(&shader, model, &transform);
+            }
+            return;
+        }
+        DrawMode::Wireframe => {
+            draw_wireframe(model, renderer);
+            return;
+        }
+        DrawMode::Flat | DrawMode::RandomColor => {}
+    }
 
+    // flat shading needs a color per face, so triangles are drawn one by one
     for index in model.indices.chunks(3) {
-        let [v0, v1, v2] = [
+        let world_coords = [
             model.vertices[index[0] as usize].position,
             model.vertices[index[1] as usize].position,
             model.vertices[index[2] as usize].position,
         ];
-
-        // points of the triangle
-        let world_coords = [v0, v1, v2];
-
         let screen_coords = world_coords.map(|v| Vec3 {
             x: (v.x + 1.0) * half_width,
             y: (v.y + 1.0) * half_height,
             z: v.z,
         });
-
-        match draw_mode {
-            DrawMode::DiffusePerspective => {
-                let screen_coords = world_coords.map(|v| {
-                    viewport
-                        .mul(&projection)
-                        .mul_mat41(&Mat4x1::from(v))
-                        .to_vec3()
-                });
-                diffuse_shading(
-                    model,
-                    renderer,
-                    &light_dir,
-                    index,
-                    &world_coords,
-                    &screen_coords,
-                );
-            }
-            DrawMode::Diffuse => {
-                diffuse_shading(
-                    model,
-                    renderer,
-                    &light_dir,
-                    index,
-                    &world_coords,
-                    &screen_coords,
-                );
-            }
+        let color = match draw_mode {
             DrawMode::Flat => {
-                let normal = (world_coords[2] - world_coords[0])
-                    .cross(&(world_coords[1] - world_coords[0]))
-                    .normalize();
-                let intensity = normal.dot(&light_dir);
-                // Back-face culling
-                if intensity > 0.0 {
-                    let color = Color::rgb(
-                        (intensity * 255.0) as u8,
-                        (intensity * 255.0) as u8,
-                        (intensity * 255.0) as u8,
-                    );
-                    renderer.draw_triangle(
-                        &screen_coords[0],
-                        &screen_coords[1],
-                        &screen_coords[2],
-                        color,
-                    );
-                }
+                let intensity = face_normal(&world_coords).dot(&light_dir).max(0.0);
+                Color::rgb(
+                    (intensity * 255.0) as u8,
+                    (intensity * 255.0) as u8,
+                    (intensity * 255.0) as u8,
+                )
             }
-            DrawMode::RandomColor => {
-                renderer.draw_triangle(
-                    &screen_coords[0],
-                    &screen_coords[1],
-                    &screen_coords[2],
-                    Color::random(),
-                );
-            }
-            DrawMode::Wireframe => {
-                let screen_coords_2d = screen_coords.map(|v| Vec2u {
-                    x: v.x as u32,
-                    y: v.y as u32,
-                });
-                renderer.draw_line(&screen_coords_2d[0], &screen_coords_2d[1], Color::WHITE);
-                renderer.draw_line(&screen_coords_2d[1], &screen_coords_2d[2], Color::WHITE);
-                renderer.draw_line(&screen_coords_2d[2], &screen_coords_2d[0], Color::WHITE);
-            }
-        }
+            _ => Color::random(),
+        };
+        renderer.draw_triangle(
+            &screen_coords[0],
+            &screen_coords[1],
+            &screen_coords[2],
+            color,
+        );
     }
 }
 
-fn diffuse_shading(
-    model: &Model,
-    renderer: &mut Renderer,
-    light_dir: &Vec3,
-    index: &[u32],
-    world_coords: &[Vec3; 3],
-    screen_coords: &[Vec3; 3],
-) {
-    let normal = (world_coords[2] - world_coords[0])
+fn face_normal(world_coords: &[Vec3; 3]) -> Vec3 {
+    (world_coords[2] - world_coords[0])
         .cross(&(world_coords[1] - world_coords[0]))
-        .normalize();
-    let intensity = normal.dot(&light_dir);
-    if intensity > 0.0 {
-        let uvs = [
-            model.vertices[index[0] as usize].uv,
-            model.vertices[index[1] as usize].uv,
-            model.vertices[index[2] as usize].uv,
-        ];
+        .normalize()
+}
 
-        renderer.draw_triangle_uv(
-            &screen_coords[0],
-            &screen_coords[1],
-            &screen_coords[2],
-            &uvs[0],
-            &uvs[1],
-            &uvs[2],
-            &model.diffuse,
-            intensity,
-        );
+/// Textured and lit per vertex, with the ambient light occluded per pixel.
+struct DiffuseShader<'a> {
+    transform: Mat4,
+    lighting: &'a Lighting,
+    diffuse: &'a Texture,
+    occlusion: Option<&'a AmbientOcclusion>,
+}
+
+impl DiffuseShader<'_> {
+    fn light(&self, (_, direct, ambient): &(Vec2, Vec3, Vec3), fragment: &Fragment) -> Vec3 {
+        let visibility = self.occlusion.map_or(1.0, |o| o.at(&fragment.position));
+        *direct + *ambient * visibility
+    }
+}
+
+impl Shader for DiffuseShader<'_> {
+    type Vertex = Vertex;
+    // uv, the direct light and the ambient light reaching the vertex
+    type Varying = (Vec2, Vec3, Vec3);
+
+    fn vertex(
+        &self,
+        vertex: &Vertex,
+        _instance_index: u32,
+        _instance: &Instance,
+    ) -> (Vec4, (Vec2, Vec3, Vec3)) {
+        let position = self
+            .transform
+            .mul_mat41(&Mat4x1::from(vertex.position))
+            .to_vec4();
+        let direct = self
+            .lighting
+            .diffuse_occluded(&vertex.position, &vertex.normal, 0.0);
+        let ambient = self.lighting.ambient.irradiance(&vertex.normal);
+        (position, (vertex.uv, direct, ambient))
+    }
+
+    fn fragment(&self, varying: &(Vec2, Vec3, Vec3), fragment: &Fragment) -> Option<Color> {
+        self.fragment_linear(varying, fragment).map(Color::from)
+    }
+
+    fn fragment_linear(
+        &self,
+        varying: &(Vec2, Vec3, Vec3),
+        fragment: &Fragment,
+    ) -> Option<LinearColor> {
+        Some(self.diffuse.sample(&varying.0) * self.light(varying, fragment))
+    }
+}
+
+/// Fills the depth buffer with black triangles first, so that hidden edges fail the depth test.
+fn draw_wireframe(model: &Model, renderer: &mut Renderer) {
+    let positions: Vec<Vec3> = model.vertices.iter().map(|v| v.position).collect();
+    let shader = SolidShader {
+        color: Color::BLACK,
+    };
+    renderer.draw_indexed(
+        &shader,
+        &positions,
+        &model.indices,
+        PrimitiveTopology::TriangleList,
+        0,
+    );
+
+    let edges: Vec<u32> = model
+        .indices
+        .chunks(3)
+        .flat_map(|t| [t[0], t[1], t[1], t[2], t[2], t[0]])
+        .collect();
+    let state = *renderer.rasterizer_state();
+    renderer.set_rasterizer_state(RasterizerState {
+        depth_bias: 1e-3,
+        ..state
+    });
+    let shader = SolidShader {
+        color: Color::WHITE,
+    };
+    renderer.draw_indexed(&shader, &positions, &edges, PrimitiveTopology::LineList, 0);
+    renderer.set_rasterizer_state(state);
+}
+
+/// Untransformed positions in a single color.
+struct SolidShader {
+    color: Color,
+}
+
+impl Shader for SolidShader {
+    type Vertex = Vec3;
+    type Varying = ();
+
+    fn vertex(&self, position: &Vec3, _instance_index: u32, _instance: &Instance) -> (Vec4, ()) {
+        (Vec4::new(position.x, position.y, position.z, 1.0), ())
+    }
+
+    fn fragment(&self, _varying: &(), _fragment: &Fragment) -> Option<Color> {
+        Some(self.color)
     }
 }
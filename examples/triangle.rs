@@ -3,7 +3,7 @@ mod common;
 use crate::common::window_wrapper::WindowWrapper;
 use tiny_soft_renderer::color::Color;
 use tiny_soft_renderer::math::{vec3, Vec3i};
-use tiny_soft_renderer::renderer::Renderer;
+use tiny_soft_renderer::renderer::{Msaa, Renderer};
 
 fn main() {
     let title = "Triangle";
@@ -11,6 +11,7 @@ fn main() {
     let height = 200;
     let window_scale = 4;
     let mut renderer = Renderer::new(width, height, true);
+    renderer.set_msaa(Msaa::X4);
     common::run(title, width, height, window_scale, &mut renderer, draw).unwrap();
 }
 
@@ -0,0 +1,316 @@
+use crate::math::{Frustum, Mat4, Quat, Vec2, Vec3};
+use std::f32::consts::FRAC_PI_2;
+
+/// Perspective camera looking down its local -z axis with +y up.
+///
+/// The projection maps the near plane to a depth of 1 and the far plane to -1, so closer points
+/// keep the larger depth the renderer's depth test expects.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Camera {
+    pub position: Vec3,
+    pub orientation: Quat,
+    /// Vertical field of view in radians.
+    pub fov_y: f32,
+    /// Width divided by height of the image.
+    pub aspect: f32,
+    pub near: f32,
+    pub far: f32,
+}
+
+impl Camera {
+    pub fn new(fov_y: f32, aspect: f32, near: f32, far: f32) -> Self {
+        Camera {
+            position: Vec3::ZERO,
+            orientation: Quat::IDENTITY,
+            fov_y,
+            aspect,
+            near,
+            far,
+        }
+    }
+
+    /// Turns the camera towards `target`. `up` only has to be roughly perpendicular to the view
+    /// direction.
+    pub fn look_at(&mut self, target: &Vec3, up: &Vec3) {
+        let forward = (*target - self.position).normalize();
+        let right = forward.cross(up).normalize();
+        let up = right.cross(&forward);
+        // rotate -z onto forward, then around forward until +y matches the wanted up vector
+        let q = Quat::from_rotation_arc(&Vec3::new(0.0, 0.0, -1.0), &forward);
+        let rotated_up = q.rotate(&Vec3::new(0.0, 1.0, 0.0));
+        let angle = rotated_up
+            .cross(&up)
+            .dot(&forward)
+            .atan2(rotated_up.dot(&up));
+        let twist = Quat::from_axis_angle(&forward, angle);
+        self.orientation = (twist * q).normalize();
+    }
+
+    pub fn forward(&self) -> Vec3 {
+        self.orientation.rotate(&Vec3::new(0.0, 0.0, -1.0))
+    }
+
+    pub fn right(&self) -> Vec3 {
+        self.orientation.rotate(&Vec3::new(1.0, 0.0, 0.0))
+    }
+
+    pub fn up(&self) -> Vec3 {
+        self.orientation.rotate(&Vec3::new(0.0, 1.0, 0.0))
+    }
+
+    /// Transform from world space to the camera's space.
+    pub fn view_matrix(&self) -> Mat4 {
+        let rotation = self.orientation.conjugate();
+        let translation = rotation.rotate(&-self.position);
+        Mat4::from_scale_rotation_translation(&Vec3::ONE, &rotation, &translation)
+    }
+
+    pub fn projection_matrix(&self) -> Mat4 {
+        let f = 1.0 / (self.fov_y * 0.5).tan();
+        let (near, far) = (self.near, self.far);
+        Mat4::from([
+            [f / self.aspect, 0.0, 0.0, 0.0],
+            [0.0, f, 0.0, 0.0],
+            [
+                0.0,
+                0.0,
+                (far + near) / (far - near),
+                2.0 * far * near / (far - near),
+            ],
+            [0.0, 0.0, -1.0, 0.0],
+        ])
+    }
+
+    pub fn view_projection_matrix(&self) -> Mat4 {
+        self.projection_matrix().mul(&self.view_matrix())
+    }
+
+    /// World space frustum of the camera.
+    pub fn frustum(&self) -> Frustum {
+        Frustum::from_matrix(&self.view_projection_matrix())
+    }
+}
+
+/// Input of a frame for the camera controllers, independent of the windowing library.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CameraInput {
+    /// Pointer movement, usually in pixels, positive x to the right and positive y downwards.
+    pub look: Vec2,
+    /// Wanted movement along right, up and forward, each in -1..1.
+    pub movement: Vec3,
+    /// Pointer movement for panning, in the same units as `look`.
+    pub pan: Vec2,
+    /// Positive to move closer, usually in mouse wheel steps.
+    pub zoom: f32,
+    /// Seconds since the previous update.
+    pub delta_time: f32,
+}
+
+pub trait CameraController {
+    fn update(&mut self, camera: &mut Camera, input: &CameraInput);
+}
+
+/// Turns around `target` at `distance`, like a model viewer.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OrbitController {
+    pub target: Vec3,
+    pub distance: f32,
+    /// Rotation around the world y axis in radians, 0 looks down -z.
+    pub yaw: f32,
+    /// Elevation in radians, positive looks down on the target.
+    pub pitch: f32,
+    /// Radians per unit of look and pan input.
+    pub sensitivity: f32,
+    /// Fraction of the distance covered by a unit of zoom.
+    pub zoom_speed: f32,
+    pub min_distance: f32,
+    pub max_distance: f32,
+}
+
+impl OrbitController {
+    pub fn new(target: Vec3, distance: f32) -> Self {
+        OrbitController {
+            target,
+            distance,
+            yaw: 0.0,
+            pitch: 0.0,
+            sensitivity: 0.005,
+            zoom_speed: 0.1,
+            min_distance: 0.01,
+            max_distance: f32::MAX,
+        }
+    }
+}
+
+impl CameraController for OrbitController {
+    fn update(&mut self, camera: &mut Camera, input: &CameraInput) {
+        let limit = FRAC_PI_2 - 1e-3;
+        self.yaw -= input.look.x * self.sensitivity;
+        self.pitch = (self.pitch + input.look.y * self.sensitivity).clamp(-limit, limit);
+        self.distance = (self.distance * (1.0 - self.zoom_speed).powf(input.zoom))
+            .clamp(self.min_distance, self.max_distance);
+        // panning moves the target in the view plane, by the same angle the pointer covers
+        let pan = self.distance * self.sensitivity;
+        self.target += camera.right() * (-input.pan.x * pan) + camera.up() * (input.pan.y * pan);
+
+        camera.orientation = Quat::from_axis_angle(&Vec3::new(0.0, 1.0, 0.0), self.yaw)
+            * Quat::from_axis_angle(&Vec3::new(1.0, 0.0, 0.0), -self.pitch);
+        camera.position = self.target - camera.forward() * self.distance;
+    }
+}
+
+/// First person controls, movement stays in the horizontal plane and the camera can't roll.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FpsController {
+    pub yaw: f32,
+    pub pitch: f32,
+    /// Radians per unit of look input.
+    pub sensitivity: f32,
+    /// Units per second.
+    pub speed: f32,
+}
+
+impl Default for FpsController {
+    fn default() -> Self {
+        FpsController {
+            yaw: 0.0,
+            pitch: 0.0,
+            sensitivity: 0.003,
+            speed: 2.0,
+        }
+    }
+}
+
+impl CameraController for FpsController {
+    fn update(&mut self, camera: &mut Camera, input: &CameraInput) {
+        let limit = FRAC_PI_2 - 1e-3;
+        self.yaw -= input.look.x * self.sensitivity;
+        self.pitch = (self.pitch - input.look.y * self.sensitivity).clamp(-limit, limit);
+        let heading = Quat::from_axis_angle(&Vec3::new(0.0, 1.0, 0.0), self.yaw);
+        camera.orientation = heading * Quat::from_axis_angle(&Vec3::new(1.0, 0.0, 0.0), self.pitch);
+
+        let forward = heading.rotate(&Vec3::new(0.0, 0.0, -1.0));
+        let right = heading.rotate(&Vec3::new(1.0, 0.0, 0.0));
+        let step = self.speed * input.delta_time;
+        camera.position += right * (input.movement.x * step)
+            + Vec3::new(0.0, input.movement.y * step, 0.0)
+            + forward * (input.movement.z * step);
+    }
+}
+
+/// Free flight, movement follows the view direction and looking around turns the camera around
+/// its own axes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FlyController {
+    /// Radians per unit of look input.
+    pub sensitivity: f32,
+    /// Units per second.
+    pub speed: f32,
+}
+
+impl Default for FlyController {
+    fn default() -> Self {
+        FlyController {
+            sensitivity: 0.003,
+            speed: 2.0,
+        }
+    }
+}
+
+impl CameraController for FlyController {
+    fn update(&mut self, camera: &mut Camera, input: &CameraInput) {
+        let yaw =
+            Quat::from_axis_angle(&Vec3::new(0.0, 1.0, 0.0), -input.look.x * self.sensitivity);
+        let pitch =
+            Quat::from_axis_angle(&Vec3::new(1.0, 0.0, 0.0), -input.look.y * self.sensitivity);
+        camera.orientation = (camera.orientation * yaw * pitch).normalize();
+
+        let step = self.speed * input.delta_time;
+        camera.position += camera.right() * (input.movement.x * step)
+            + camera.up() * (input.movement.y * step)
+            + camera.forward() * (input.movement.z * step);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::{vec2, vec3, Mat4x1};
+
+    fn assert_near(a: Vec3, b: Vec3) {
+        assert!((a - b).sqrt() < 1e-4, "{:?} != {:?}", a, b);
+    }
+
+    fn project(m: &Mat4, p: Vec3) -> Vec3 {
+        m.mul_mat41(&Mat4x1::from(p)).to_vec3()
+    }
+
+    #[test]
+    fn test_camera_matrices() {
+        let mut camera = Camera::new(FRAC_PI_2, 2.0, 1.0, 10.0);
+        camera.position = vec3(0.0, 0.0, 5.0);
+        let m = camera.view_projection_matrix();
+        // the near plane at z = 4 and the far plane at z = -5
+        assert_near(project(&m, vec3(0.0, 0.0, 4.0)), vec3(0.0, 0.0, 1.0));
+        assert_near(project(&m, vec3(0.0, 0.0, -5.0)), vec3(0.0, 0.0, -1.0));
+        assert_near(project(&m, vec3(2.0, 1.0, 3.0)), vec3(0.5, 0.5, -1.0 / 9.0));
+
+        camera.look_at(&vec3(5.0, 0.0, 5.0), &vec3(0.0, 1.0, 0.0));
+        assert_near(camera.forward(), vec3(1.0, 0.0, 0.0));
+        assert_near(camera.up(), vec3(0.0, 1.0, 0.0));
+        let view = camera.view_matrix();
+        assert_near(project(&view, vec3(7.0, 1.0, 5.0)), vec3(0.0, 1.0, -7.0));
+        assert_near(
+            project(&view.inverse().unwrap(), Vec3::ZERO),
+            camera.position,
+        );
+
+        let frustum = camera.frustum();
+        assert!(frustum.contains_point(&vec3(3.0, 0.0, 5.0)));
+        assert!(!frustum.contains_point(&vec3(-3.0, 0.0, 5.0)));
+    }
+
+    #[test]
+    fn test_controllers() {
+        let mut camera = Camera::new(1.0, 1.0, 0.1, 100.0);
+        let mut orbit = OrbitController::new(vec3(1.0, 0.0, 0.0), 4.0);
+        let input = CameraInput {
+            look: vec2(-FRAC_PI_2 / orbit.sensitivity, 0.0),
+            ..Default::default()
+        };
+        orbit.update(&mut camera, &input);
+        // a quarter turn around the target from the default view along -z
+        assert_near(camera.position, vec3(5.0, 0.0, 0.0));
+        assert_near(camera.forward(), vec3(-1.0, 0.0, 0.0));
+        orbit.update(
+            &mut camera,
+            &CameraInput {
+                look: vec2(0.0, 1e6),
+                zoom: 1.0,
+                ..Default::default()
+            },
+        );
+        assert!(orbit.pitch < FRAC_PI_2);
+        assert!((orbit.distance - 3.6).abs() < 1e-5);
+        assert!(((camera.position - orbit.target).sqrt() - 3.6).abs() < 1e-4);
+
+        let mut camera = Camera::new(1.0, 1.0, 0.1, 100.0);
+        let mut fps = FpsController::default();
+        let input = CameraInput {
+            look: vec2(0.0, -0.5 / fps.sensitivity),
+            movement: vec3(0.0, 0.0, 1.0),
+            delta_time: 0.5,
+            ..Default::default()
+        };
+        fps.update(&mut camera, &input);
+        // looking up doesn't lift the camera off the ground
+        assert!(camera.forward().y > 0.4);
+        assert_near(camera.position, vec3(0.0, 0.0, -1.0));
+
+        let mut camera = Camera::new(1.0, 1.0, 0.1, 100.0);
+        let mut fly = FlyController::default();
+        fly.update(&mut camera, &input);
+        assert!((camera.position.sqrt() - 1.0).abs() < 1e-5);
+        assert!(camera.position.y > 0.4);
+    }
+}
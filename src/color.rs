@@ -1,4 +1,95 @@
-use std::ops::{Mul, MulAssign};
+use crate::math::{Vec3, Vec4};
+use std::ops::{Add, AddAssign, Div, Mul, MulAssign, Sub, SubAssign};
+use std::sync::OnceLock;
+
+/// Entries of the table encoding linear values to sRGB, enough for every 8-bit value to
+/// survive a decode and encode round trip.
+const SRGB_ENCODE_LUT_SIZE: usize = 4096;
+
+/// How the bytes of a texture or render target relate to light intensity.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ColorSpace {
+    /// Gamma encoded with the sRGB transfer function, as stored by image files and expected by
+    /// displays.
+    #[default]
+    Srgb,
+    /// Proportional to light intensity, for data such as normal maps.
+    Linear,
+}
+
+impl ColorSpace {
+    /// Converts a stored color to linear 0..1 values. Alpha is always linear.
+    pub fn decode(&self, color: Color) -> LinearColor {
+        match self {
+            ColorSpace::Srgb => {
+                let lut = srgb_decode_lut();
+                LinearColor::rgba(
+                    lut[color.r as usize],
+                    lut[color.g as usize],
+                    lut[color.b as usize],
+                    color.a as f32 / 255.0,
+                )
+            }
+            ColorSpace::Linear => LinearColor::from(color.to_vec4()),
+        }
+    }
+
+    /// Converts linear values to a stored color, values outside of 0..1 are clamped.
+    pub fn encode(&self, linear: &LinearColor) -> Color {
+        match self {
+            ColorSpace::Srgb => Color::rgba(
+                linear_to_srgb(linear.r),
+                linear_to_srgb(linear.g),
+                linear_to_srgb(linear.b),
+                (linear.a.clamp(0.0, 1.0) * 255.0).round() as u8,
+            ),
+            ColorSpace::Linear => Color::from_vec4(&Vec4::from(*linear)),
+        }
+    }
+}
+
+/// The sRGB transfer function, from an encoded value in 0..1 to linear.
+pub fn srgb_eotf(encoded: f32) -> f32 {
+    if encoded <= 0.04045 {
+        encoded / 12.92
+    } else {
+        ((encoded + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// The inverse of [`srgb_eotf`].
+pub fn srgb_oetf(linear: f32) -> f32 {
+    if linear <= 0.0031308 {
+        linear * 12.92
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+fn srgb_decode_lut() -> &'static [f32; 256] {
+    static LUT: OnceLock<[f32; 256]> = OnceLock::new();
+    LUT.get_or_init(|| std::array::from_fn(|i| srgb_eotf(i as f32 / 255.0)))
+}
+
+/// Decodes an sRGB byte to a linear value in 0..1 with a lookup table.
+pub fn srgb_to_linear(encoded: u8) -> f32 {
+    srgb_decode_lut()[encoded as usize]
+}
+
+/// Encodes a linear value to an sRGB byte with a lookup table, clamping to 0..1.
+pub fn linear_to_srgb(linear: f32) -> u8 {
+    static LUT: OnceLock<Vec<u8>> = OnceLock::new();
+    let lut = LUT.get_or_init(|| {
+        (0..SRGB_ENCODE_LUT_SIZE)
+            .map(|i| {
+                let linear = i as f32 / (SRGB_ENCODE_LUT_SIZE - 1) as f32;
+                (srgb_oetf(linear) * 255.0).round() as u8
+            })
+            .collect()
+    });
+    let index = (linear.clamp(0.0, 1.0) * (SRGB_ENCODE_LUT_SIZE - 1) as f32).round() as usize;
+    lut[index]
+}
 
 #[repr(C)]
 #[derive(Clone, Copy, Debug, PartialEq, bytemuck::Zeroable, bytemuck::Pod)]
@@ -24,6 +115,22 @@ impl Color {
         Color { r, g, b, a }
     }
 
+    /// Channels mapped to 0..1.
+    pub fn to_vec4(&self) -> Vec4 {
+        Vec4::new(
+            self.r as f32 / 255.0,
+            self.g as f32 / 255.0,
+            self.b as f32 / 255.0,
+            self.a as f32 / 255.0,
+        )
+    }
+
+    /// Inverse of [`Color::to_vec4`], channels outside of 0..1 are clamped.
+    pub fn from_vec4(v: &Vec4) -> Self {
+        let channel = |c: f32| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+        Color::rgba(channel(v.x), channel(v.y), channel(v.z), channel(v.w))
+    }
+
     pub fn random() -> Self {
         let r = (rand::random::<f32>() * 255.0) as u8;
         let g = (rand::random::<f32>() * 255.0) as u8;
@@ -56,3 +163,353 @@ impl MulAssign<f32> for Color {
         *self = *self * rhs;
     }
 }
+
+/// Color with floating point channels proportional to light intensity, for the math of shaders.
+/// Channels may leave 0..1 until the color is stored, such as in a high dynamic range target.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, bytemuck::Zeroable, bytemuck::Pod)]
+pub struct LinearColor {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+/// The name of the format in graphics APIs.
+pub type Rgba32F = LinearColor;
+
+impl LinearColor {
+    pub const BLACK: LinearColor = LinearColor::rgb(0.0, 0.0, 0.0);
+    pub const WHITE: LinearColor = LinearColor::rgb(1.0, 1.0, 1.0);
+    pub const TRANSPARENT: LinearColor = LinearColor::rgba(0.0, 0.0, 0.0, 0.0);
+
+    pub const fn rgb(r: f32, g: f32, b: f32) -> Self {
+        LinearColor { r, g, b, a: 1.0 }
+    }
+
+    pub const fn rgba(r: f32, g: f32, b: f32, a: f32) -> Self {
+        LinearColor { r, g, b, a }
+    }
+
+    /// Opaque color from red, green and blue in x, y and z.
+    pub fn from_vec3(rgb: &Vec3) -> Self {
+        LinearColor::rgb(rgb.x, rgb.y, rgb.z)
+    }
+
+    /// Red, green and blue without alpha.
+    pub fn to_vec3(&self) -> Vec3 {
+        Vec3::new(self.r, self.g, self.b)
+    }
+
+    pub fn with_alpha(&self, a: f32) -> Self {
+        LinearColor { a, ..*self }
+    }
+
+    fn map(&self, f: impl Fn(f32) -> f32) -> Self {
+        LinearColor::rgba(f(self.r), f(self.g), f(self.b), f(self.a))
+    }
+
+    fn map_rgb(&self, f: impl Fn(f32) -> f32) -> Self {
+        LinearColor::rgba(f(self.r), f(self.g), f(self.b), self.a)
+    }
+
+    /// Interpolates every channel, `self` at 0 and `other` at 1.
+    pub fn lerp(&self, other: &Self, t: f32) -> Self {
+        *self + (*other - *self) * t
+    }
+
+    pub fn clamp(&self, min: f32, max: f32) -> Self {
+        self.map(|c| c.clamp(min, max))
+    }
+
+    /// Clamps every channel to 0..1.
+    pub fn saturate(&self) -> Self {
+        self.clamp(0.0, 1.0)
+    }
+
+    /// Red, green and blue multiplied by alpha, the form compositing works in.
+    pub fn premultiply(&self) -> Self {
+        LinearColor::rgba(self.r * self.a, self.g * self.a, self.b * self.a, self.a)
+    }
+
+    /// Inverse of [`LinearColor::premultiply`], fully transparent colors become
+    /// [`LinearColor::TRANSPARENT`].
+    pub fn unpremultiply(&self) -> Self {
+        if self.a <= 0.0 {
+            return LinearColor::TRANSPARENT;
+        }
+        LinearColor::rgba(self.r / self.a, self.g / self.a, self.b / self.a, self.a)
+    }
+
+    /// Perceived brightness with the Rec. 709 weights of sRGB.
+    pub fn luminance(&self) -> f32 {
+        0.2126 * self.r + 0.7152 * self.g + 0.0722 * self.b
+    }
+
+    /// Hue in degrees, saturation and value of the sRGB encoded color, like a color picker shows
+    /// them. Expects channels in 0..1.
+    pub fn to_hsv(&self) -> Vec3 {
+        let c = self.map_rgb(srgb_oetf);
+        let max = c.r.max(c.g).max(c.b);
+        let chroma = max - c.r.min(c.g).min(c.b);
+        let saturation = if max > 0.0 { chroma / max } else { 0.0 };
+        Vec3::new(hue(&c, max, chroma), saturation, max)
+    }
+
+    /// Inverse of [`LinearColor::to_hsv`], the result is opaque.
+    pub fn from_hsv(hsv: &Vec3) -> Self {
+        let (h, s, v) = (hsv.x, hsv.y, hsv.z);
+        let f = |n: f32| {
+            let k = (n + h / 60.0).rem_euclid(6.0);
+            srgb_eotf(v - v * s * k.min(4.0 - k).clamp(0.0, 1.0))
+        };
+        LinearColor::rgb(f(5.0), f(3.0), f(1.0))
+    }
+
+    /// Hue in degrees, saturation and lightness of the sRGB encoded color. Expects channels in
+    /// 0..1.
+    pub fn to_hsl(&self) -> Vec3 {
+        let c = self.map_rgb(srgb_oetf);
+        let max = c.r.max(c.g).max(c.b);
+        let min = c.r.min(c.g).min(c.b);
+        let chroma = max - min;
+        let lightness = (max + min) * 0.5;
+        let saturation = if lightness > 0.0 && lightness < 1.0 {
+            chroma / (1.0 - (2.0 * lightness - 1.0).abs())
+        } else {
+            0.0
+        };
+        Vec3::new(hue(&c, max, chroma), saturation, lightness)
+    }
+
+    /// Inverse of [`LinearColor::to_hsl`], the result is opaque.
+    pub fn from_hsl(hsl: &Vec3) -> Self {
+        let (h, s, l) = (hsl.x, hsl.y, hsl.z);
+        let a = s * l.min(1.0 - l);
+        let f = |n: f32| {
+            let k = (n + h / 30.0).rem_euclid(12.0);
+            srgb_eotf(l - a * (k - 3.0).min(9.0 - k).clamp(-1.0, 1.0))
+        };
+        LinearColor::rgb(f(0.0), f(8.0), f(4.0))
+    }
+
+    /// Björn Ottosson's perceptual Oklab coordinates, lightness in x and the a and b opponent
+    /// axes in y and z. Interpolating there gives even gradients.
+    pub fn to_oklab(&self) -> Vec3 {
+        let l = 0.41222147 * self.r + 0.53633254 * self.g + 0.051445993 * self.b;
+        let m = 0.2119035 * self.r + 0.6806995 * self.g + 0.10739696 * self.b;
+        let s = 0.08830246 * self.r + 0.28171884 * self.g + 0.6299787 * self.b;
+        let (l, m, s) = (l.cbrt(), m.cbrt(), s.cbrt());
+        Vec3::new(
+            0.21045426 * l + 0.7936178 * m - 0.004072047 * s,
+            1.9779985 * l - 2.4285922 * m + 0.4505937 * s,
+            0.025904037 * l + 0.78277177 * m - 0.80867577 * s,
+        )
+    }
+
+    /// Inverse of [`LinearColor::to_oklab`], the result is opaque.
+    pub fn from_oklab(lab: &Vec3) -> Self {
+        let l = lab.x + 0.39633778 * lab.y + 0.21580376 * lab.z;
+        let m = lab.x - 0.105561346 * lab.y - 0.06385417 * lab.z;
+        let s = lab.x - 0.08948418 * lab.y - 1.2914855 * lab.z;
+        let (l, m, s) = (l * l * l, m * m * m, s * s * s);
+        LinearColor::rgb(
+            4.0767417 * l - 3.3077116 * m + 0.23096993 * s,
+            -1.268438 * l + 2.6097574 * m - 0.34131938 * s,
+            -0.0041960863 * l - 0.7034186 * m + 1.7076147 * s,
+        )
+    }
+}
+
+/// Hue in degrees shared by HSV and HSL.
+fn hue(c: &LinearColor, max: f32, chroma: f32) -> f32 {
+    if chroma <= 0.0 {
+        return 0.0;
+    }
+    let sector = if max == c.r {
+        ((c.g - c.b) / chroma).rem_euclid(6.0)
+    } else if max == c.g {
+        (c.b - c.r) / chroma + 2.0
+    } else {
+        (c.r - c.g) / chroma + 4.0
+    };
+    sector * 60.0
+}
+
+/// Decodes sRGB, exact for every 8-bit value when converted back.
+impl From<Color> for LinearColor {
+    fn from(color: Color) -> Self {
+        ColorSpace::Srgb.decode(color)
+    }
+}
+
+/// Encodes to sRGB, clamping to 0..1.
+impl From<LinearColor> for Color {
+    fn from(color: LinearColor) -> Self {
+        ColorSpace::Srgb.encode(&color)
+    }
+}
+
+impl From<Vec4> for LinearColor {
+    fn from(v: Vec4) -> Self {
+        LinearColor::rgba(v.x, v.y, v.z, v.w)
+    }
+}
+
+impl From<LinearColor> for Vec4 {
+    fn from(c: LinearColor) -> Self {
+        Vec4::new(c.r, c.g, c.b, c.a)
+    }
+}
+
+impl Add for LinearColor {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        LinearColor::rgba(
+            self.r + rhs.r,
+            self.g + rhs.g,
+            self.b + rhs.b,
+            self.a + rhs.a,
+        )
+    }
+}
+
+impl AddAssign for LinearColor {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl Sub for LinearColor {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        LinearColor::rgba(
+            self.r - rhs.r,
+            self.g - rhs.g,
+            self.b - rhs.b,
+            self.a - rhs.a,
+        )
+    }
+}
+
+impl SubAssign for LinearColor {
+    fn sub_assign(&mut self, rhs: Self) {
+        *self = *self - rhs;
+    }
+}
+
+/// Scales every channel including alpha, like averaging samples needs.
+impl Mul<f32> for LinearColor {
+    type Output = Self;
+
+    fn mul(self, rhs: f32) -> Self::Output {
+        self.map(|c| c * rhs)
+    }
+}
+
+impl MulAssign<f32> for LinearColor {
+    fn mul_assign(&mut self, rhs: f32) {
+        *self = *self * rhs;
+    }
+}
+
+impl Div<f32> for LinearColor {
+    type Output = Self;
+
+    fn div(self, rhs: f32) -> Self::Output {
+        self.map(|c| c / rhs)
+    }
+}
+
+/// Component-wise product, such as a texture tinted by a material color.
+impl Mul for LinearColor {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        LinearColor::rgba(
+            self.r * rhs.r,
+            self.g * rhs.g,
+            self.b * rhs.b,
+            self.a * rhs.a,
+        )
+    }
+}
+
+impl MulAssign for LinearColor {
+    fn mul_assign(&mut self, rhs: Self) {
+        *self = *self * rhs;
+    }
+}
+
+/// Scales the red, green and blue channels separately and keeps alpha, such as by the light
+/// reaching a surface.
+impl Mul<Vec3> for LinearColor {
+    type Output = Self;
+
+    fn mul(self, rhs: Vec3) -> Self::Output {
+        LinearColor::rgba(self.r * rhs.x, self.g * rhs.y, self.b * rhs.z, self.a)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_srgb_round_trip() {
+        for i in 0..=255u8 {
+            assert_eq!(linear_to_srgb(srgb_to_linear(i)), i);
+            let exact = (srgb_oetf(i as f32 / 255.0) * 255.0).round() as u8;
+            assert!(linear_to_srgb(i as f32 / 255.0).abs_diff(exact) <= 1);
+        }
+        // mid gray in sRGB is about a fifth of the light of white
+        assert!((srgb_to_linear(128) - 0.2158).abs() < 1e-3);
+        assert_eq!(linear_to_srgb(0.5), 188);
+
+        let color = Color::rgba(12, 128, 255, 128);
+        let linear = ColorSpace::Srgb.decode(color);
+        assert_eq!(linear.a, 128.0 / 255.0);
+        assert_eq!(ColorSpace::Srgb.encode(&linear), color);
+        assert_eq!(
+            ColorSpace::Linear.encode(&ColorSpace::Linear.decode(color)),
+            color
+        );
+    }
+
+    fn assert_near(a: Vec3, b: Vec3) {
+        assert!((a - b).sqrt() < 1e-3, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn test_linear_color() {
+        for i in 0..=255u8 {
+            let color = Color::rgba(i, 255 - i, i / 2, i);
+            assert_eq!(Color::from(LinearColor::from(color)), color);
+        }
+
+        let a = LinearColor::rgba(0.25, 0.5, 0.75, 1.0);
+        let b = LinearColor::rgba(1.0, 2.0, 3.0, 0.0);
+        assert_eq!(a.lerp(&b, 0.5), LinearColor::rgba(0.625, 1.25, 1.875, 0.5));
+        assert_eq!((a * b).g, 1.0);
+        assert_eq!(b.saturate(), LinearColor::rgba(1.0, 1.0, 1.0, 0.0));
+        assert_eq!((a * Vec3::splat(2.0)).a, 1.0);
+        assert_eq!((a + b - b).to_vec3(), a.to_vec3());
+
+        // pure red in the encoded space of color pickers
+        let red = LinearColor::from_hsv(&Vec3::new(0.0, 1.0, 1.0));
+        assert_eq!(Color::from(red), Color::RED);
+        let teal = Color::rgb(0, 128, 128);
+        let linear = LinearColor::from(teal);
+        assert_near(linear.to_hsv(), Vec3::new(180.0, 1.0, 128.0 / 255.0));
+        assert_near(linear.to_hsl(), Vec3::new(180.0, 1.0, 64.0 / 255.0));
+        assert_eq!(Color::from(LinearColor::from_hsv(&linear.to_hsv())), teal);
+        assert_eq!(Color::from(LinearColor::from_hsl(&linear.to_hsl())), teal);
+
+        // white has full lightness and no chroma in Oklab
+        assert_near(LinearColor::WHITE.to_oklab(), Vec3::new(1.0, 0.0, 0.0));
+        let lab = linear.to_oklab();
+        assert_eq!(Color::from(LinearColor::from_oklab(&lab)), teal);
+    }
+}
@@ -0,0 +1,351 @@
+use crate::color::{srgb_eotf, srgb_oetf, Color, ColorSpace, LinearColor};
+use crate::renderer::Renderer;
+use crate::texture::Texture;
+
+/// The Porter-Duff operators, combining a source layer with the destination it is drawn onto.
+/// Each keeps the parts of both layers named by the operator where they overlap or not.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CompositeOp {
+    Clear,
+    Src,
+    Dst,
+    /// The source on top of the destination, the usual way to layer images.
+    #[default]
+    SrcOver,
+    DstOver,
+    SrcIn,
+    DstIn,
+    SrcOut,
+    DstOut,
+    SrcAtop,
+    DstAtop,
+    Xor,
+}
+
+impl CompositeOp {
+    /// Combines premultiplied colors.
+    pub fn apply(&self, src: &LinearColor, dst: &LinearColor) -> LinearColor {
+        let (sa, da) = (src.a, dst.a);
+        // fractions of the source and the destination in the result
+        let (fs, fd) = match self {
+            CompositeOp::Clear => (0.0, 0.0),
+            CompositeOp::Src => (1.0, 0.0),
+            CompositeOp::Dst => (0.0, 1.0),
+            CompositeOp::SrcOver => (1.0, 1.0 - sa),
+            CompositeOp::DstOver => (1.0 - da, 1.0),
+            CompositeOp::SrcIn => (da, 0.0),
+            CompositeOp::DstIn => (0.0, sa),
+            CompositeOp::SrcOut => (1.0 - da, 0.0),
+            CompositeOp::DstOut => (0.0, 1.0 - sa),
+            CompositeOp::SrcAtop => (da, 1.0 - sa),
+            CompositeOp::DstAtop => (1.0 - da, sa),
+            CompositeOp::Xor => (1.0 - da, 1.0 - sa),
+        };
+        *src * fs + *dst * fd
+    }
+}
+
+/// Separable blend modes of the W3C compositing specification, mixing the colors where a source
+/// layer covers the destination. Like in the specification, CSS and image editors the channels
+/// are mixed as sRGB encoded values, so Overlay pivots at encoded 0.5. The result is composited
+/// source over destination in linear light.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BlendMode {
+    /// The source color, plain source over compositing.
+    #[default]
+    Normal,
+    /// Darkens, white keeps the other layer.
+    Multiply,
+    /// Lightens, black keeps the other layer.
+    Screen,
+    /// Multiply or screen depending on the destination, increasing its contrast.
+    Overlay,
+    Darken,
+    Lighten,
+    ColorDodge,
+    ColorBurn,
+    /// Multiply or screen depending on the source.
+    HardLight,
+    SoftLight,
+    Difference,
+    Exclusion,
+}
+
+impl BlendMode {
+    /// Mixes a single unpremultiplied channel of the source with the destination, both encoded
+    /// values in 0..1.
+    fn mix(&self, s: f32, d: f32) -> f32 {
+        match self {
+            BlendMode::Normal => s,
+            BlendMode::Multiply => s * d,
+            BlendMode::Screen => s + d - s * d,
+            BlendMode::Overlay => BlendMode::HardLight.mix(d, s),
+            BlendMode::Darken => s.min(d),
+            BlendMode::Lighten => s.max(d),
+            BlendMode::ColorDodge => {
+                if d <= 0.0 {
+                    0.0
+                } else if s >= 1.0 {
+                    1.0
+                } else {
+                    (d / (1.0 - s)).min(1.0)
+                }
+            }
+            BlendMode::ColorBurn => {
+                if d >= 1.0 {
+                    1.0
+                } else if s <= 0.0 {
+                    0.0
+                } else {
+                    1.0 - ((1.0 - d) / s).min(1.0)
+                }
+            }
+            BlendMode::HardLight => {
+                if s <= 0.5 {
+                    BlendMode::Multiply.mix(2.0 * s, d)
+                } else {
+                    BlendMode::Screen.mix(2.0 * s - 1.0, d)
+                }
+            }
+            BlendMode::SoftLight => {
+                if s <= 0.5 {
+                    d - (1.0 - 2.0 * s) * d * (1.0 - d)
+                } else {
+                    let g = if d <= 0.25 {
+                        ((16.0 * d - 12.0) * d + 4.0) * d
+                    } else {
+                        d.sqrt()
+                    };
+                    d + (2.0 * s - 1.0) * (g - d)
+                }
+            }
+            BlendMode::Difference => (s - d).abs(),
+            BlendMode::Exclusion => s + d - 2.0 * s * d,
+        }
+    }
+
+    /// [`BlendMode::mix`] of linear channels through their sRGB encoding.
+    fn mix_linear(&self, s: f32, d: f32) -> f32 {
+        match self {
+            BlendMode::Normal => s,
+            _ => srgb_eotf(self.mix(srgb_oetf(s), srgb_oetf(d))),
+        }
+    }
+
+    /// Blends premultiplied linear colors.
+    pub fn apply(&self, src: &LinearColor, dst: &LinearColor) -> LinearColor {
+        let (s, d) = (src.unpremultiply(), dst.unpremultiply());
+        let both = src.a * dst.a;
+        // the blended color where both layers are opaque, each layer alone elsewhere
+        let channel = |ps: f32, pd: f32, s: f32, d: f32| {
+            ps * (1.0 - dst.a) + pd * (1.0 - src.a) + both * self.mix_linear(s, d)
+        };
+        LinearColor::rgba(
+            channel(src.r, dst.r, s.r, d.r),
+            channel(src.g, dst.g, s.g, d.g),
+            channel(src.b, dst.b, s.b, d.b),
+            src.a + dst.a * (1.0 - src.a),
+        )
+    }
+}
+
+/// Converts straight alpha pixels to premultiplied alpha, in linear light.
+pub fn premultiply(pixels: &mut [Color], color_space: ColorSpace) {
+    for pixel in pixels {
+        *pixel = color_space.encode(&color_space.decode(*pixel).premultiply());
+    }
+}
+
+/// Inverse of [`premultiply`]. Colors of faint pixels lose precision in 8 bits.
+pub fn unpremultiply(pixels: &mut [Color], color_space: ColorSpace) {
+    for pixel in pixels {
+        *pixel = color_space.encode(&color_space.decode(*pixel).unpremultiply());
+    }
+}
+
+/// Combines two images of straight alpha pixels with `f`, which takes premultiplied linear colors
+/// of the source and the destination.
+fn combine<F>(dst: &mut [Color], dst_space: ColorSpace, src: &[Color], src_space: ColorSpace, f: F)
+where
+    F: Fn(&LinearColor, &LinearColor) -> LinearColor,
+{
+    assert_eq!(dst.len(), src.len());
+    for (d, s) in dst.iter_mut().zip(src) {
+        let s = src_space.decode(*s).premultiply();
+        let result = f(&s, &dst_space.decode(*d).premultiply());
+        *d = dst_space.encode(&result.unpremultiply());
+    }
+}
+
+impl Texture {
+    /// Combines `src`, which must have the same size, into this texture.
+    pub fn composite(&mut self, src: &Texture, op: CompositeOp) {
+        assert_eq!((self.width, self.height), (src.width, src.height));
+        combine(
+            &mut self.pixels,
+            self.color_space,
+            &src.pixels,
+            src.color_space,
+            |s, d| op.apply(s, d),
+        );
+    }
+
+    /// Blends `src`, which must have the same size, over this texture.
+    pub fn blend(&mut self, src: &Texture, mode: BlendMode) {
+        assert_eq!((self.width, self.height), (src.width, src.height));
+        combine(
+            &mut self.pixels,
+            self.color_space,
+            &src.pixels,
+            src.color_space,
+            |s, d| mode.apply(s, d),
+        );
+    }
+}
+
+impl Renderer {
+    /// Combines `src` into the output pixels, for example `CompositeOp::DstOver` puts a
+    /// background behind a frame cleared to transparent. Call after
+    /// [`Renderer::resolve`], which overwrites the pixels otherwise.
+    pub fn composite(&mut self, src: &Texture, op: CompositeOp) {
+        assert_eq!((self.width(), self.height()), (src.width, src.height));
+        let color_space = self.color_space();
+        combine(
+            self.pixels_mut(),
+            color_space,
+            &src.pixels,
+            src.color_space,
+            |s, d| op.apply(s, d),
+        );
+    }
+
+    /// Blends `src` over the output pixels, see [`Renderer::composite`].
+    pub fn blend(&mut self, src: &Texture, mode: BlendMode) {
+        assert_eq!((self.width(), self.height()), (src.width, src.height));
+        let color_space = self.color_space();
+        combine(
+            self.pixels_mut(),
+            color_space,
+            &src.pixels,
+            src.color_space,
+            |s, d| mode.apply(s, d),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OPS: [CompositeOp; 12] = [
+        CompositeOp::Clear,
+        CompositeOp::Src,
+        CompositeOp::Dst,
+        CompositeOp::SrcOver,
+        CompositeOp::DstOver,
+        CompositeOp::SrcIn,
+        CompositeOp::DstIn,
+        CompositeOp::SrcOut,
+        CompositeOp::DstOut,
+        CompositeOp::SrcAtop,
+        CompositeOp::DstAtop,
+        CompositeOp::Xor,
+    ];
+
+    #[test]
+    fn test_porter_duff() {
+        let src = LinearColor::rgba(1.0, 0.0, 0.0, 0.5).premultiply();
+        let dst = LinearColor::rgba(0.0, 0.0, 1.0, 0.25).premultiply();
+        let alphas = OPS.map(|op| op.apply(&src, &dst).a);
+        // the area of each region kept: both 0.125, source only 0.375, destination only 0.125
+        assert_eq!(
+            alphas,
+            [0.0, 0.5, 0.25, 0.625, 0.625, 0.125, 0.125, 0.375, 0.125, 0.25, 0.5, 0.5]
+        );
+        let over = CompositeOp::SrcOver.apply(&src, &dst);
+        assert_eq!(over, LinearColor::rgba(0.5, 0.0, 0.125, 0.625));
+        assert_eq!(CompositeOp::SrcIn.apply(&src, &dst).r, 0.125);
+        // an opaque source covers everything
+        let opaque = LinearColor::rgb(0.0, 1.0, 0.0);
+        assert_eq!(CompositeOp::SrcOver.apply(&opaque, &dst), opaque);
+        assert_eq!(CompositeOp::DstOver.apply(&src, &opaque), opaque);
+
+        let color = LinearColor::rgba(0.5, 0.25, 1.0, 0.5);
+        assert_eq!(color.premultiply().unpremultiply(), color);
+        assert_eq!(
+            LinearColor::rgba(1.0, 1.0, 1.0, 0.0)
+                .premultiply()
+                .unpremultiply(),
+            LinearColor::TRANSPARENT
+        );
+    }
+
+    #[test]
+    fn test_blend_modes() {
+        let blend = |mode: BlendMode| mode.mix(0.5, 0.25);
+        assert_eq!(blend(BlendMode::Normal), 0.5);
+        assert_eq!(blend(BlendMode::Multiply), 0.125);
+        assert_eq!(blend(BlendMode::Screen), 0.625);
+        // overlay multiplies on a dark destination, hard light screens with a light source
+        assert_eq!(blend(BlendMode::Overlay), 0.25);
+        assert_eq!(BlendMode::HardLight.mix(0.5, 0.25), 0.25);
+        assert_eq!(blend(BlendMode::Darken), 0.25);
+        assert_eq!(blend(BlendMode::Difference), 0.25);
+        assert_eq!(blend(BlendMode::Exclusion), 0.5);
+
+        // linear colors are mixed as their encoded values
+        let gray = LinearColor::rgb(srgb_eotf(0.5), srgb_eotf(0.5), srgb_eotf(0.5));
+        let dark = LinearColor::rgb(srgb_eotf(0.25), srgb_eotf(0.25), srgb_eotf(0.25));
+        let multiplied = BlendMode::Multiply.apply(&gray, &dark).r;
+        assert!((srgb_oetf(multiplied) - 0.125).abs() < 1e-5);
+        assert_eq!(BlendMode::Normal.apply(&gray, &dark), gray);
+
+        // a transparent source leaves the destination alone in every mode
+        let clear = LinearColor::TRANSPARENT;
+        assert_eq!(BlendMode::Multiply.apply(&clear, &dark), dark);
+        // a half transparent source blends half of the way in linear light
+        let half = LinearColor::rgba(1.0, 1.0, 1.0, 0.5).premultiply();
+        let dark = LinearColor::rgb(0.25, 0.25, 0.25);
+        assert!((BlendMode::Multiply.apply(&half, &dark).r - 0.25).abs() < 1e-5);
+        assert!((BlendMode::Screen.apply(&half, &dark).r - 0.625).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_composite_buffers() {
+        let mut renderer = Renderer::new(2, 1, false);
+        renderer.clear(Color::rgba(0, 0, 0, 0));
+        renderer.draw_pixel(0, 0, Color::RED);
+        let background = Texture {
+            pixels: vec![Color::BLUE; 2],
+            width: 2,
+            height: 1,
+            color_space: ColorSpace::Srgb,
+        };
+        renderer.composite(&background, CompositeOp::DstOver);
+        assert_eq!(renderer.pixels(), [Color::RED, Color::BLUE]);
+
+        let mut texture = Texture::solid(Color::rgb(188, 188, 188));
+        texture.blend(
+            &Texture::solid(Color::rgba(255, 255, 255, 128)),
+            BlendMode::Multiply,
+        );
+        assert_eq!(texture.pixels[0], Color::rgb(188, 188, 188));
+        // the 8-bit results of CSS mix-blend-mode, overlay screens onto a destination above 127
+        let blended = |dst: u8, src: u8, mode: BlendMode| {
+            let mut texture = Texture::solid(Color::rgb(dst, dst, dst));
+            texture.blend(&Texture::solid(Color::rgb(src, src, src)), mode);
+            texture.pixels[0].r
+        };
+        assert_eq!(blended(128, 128, BlendMode::Multiply), 64);
+        assert_eq!(blended(128, 128, BlendMode::Screen), 192);
+        assert_eq!(blended(160, 128, BlendMode::Overlay), 160);
+        texture.composite(&Texture::solid(Color::rgba(255, 0, 0, 0)), CompositeOp::Src);
+        assert_eq!(texture.pixels[0], Color::rgba(0, 0, 0, 0));
+
+        let mut pixels = [Color::rgba(255, 255, 255, 128)];
+        premultiply(&mut pixels, ColorSpace::Linear);
+        assert_eq!(pixels, [Color::rgba(128, 128, 128, 128)]);
+        unpremultiply(&mut pixels, ColorSpace::Linear);
+        assert_eq!(pixels, [Color::rgba(255, 255, 255, 128)]);
+    }
+}
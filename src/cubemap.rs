@@ -0,0 +1,387 @@
+use crate::color::{Color, LinearColor};
+use crate::light::{fresnel_schlick, reflect, refract};
+use crate::math::{Mat4, Mat4x1, Vec2f, Vec3, Vec4};
+use crate::rasterizer::{PrimitiveTopology, RasterizerState};
+use crate::renderer::Renderer;
+use crate::shader::{Fragment, Instance, Shader};
+use crate::texture::Texture;
+use anyhow::Result;
+use std::f32::consts::{PI, TAU};
+use std::path::Path;
+
+/// The six faces of a [`CubeTexture`] in the order of [`CubeTexture::faces`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CubeFace {
+    PositiveX,
+    NegativeX,
+    PositiveY,
+    NegativeY,
+    PositiveZ,
+    NegativeZ,
+}
+
+impl CubeFace {
+    pub const ALL: [CubeFace; 6] = [
+        CubeFace::PositiveX,
+        CubeFace::NegativeX,
+        CubeFace::PositiveY,
+        CubeFace::NegativeY,
+        CubeFace::PositiveZ,
+        CubeFace::NegativeZ,
+    ];
+
+    /// The face a direction points at and the texture coordinates of the hit, with the layout
+    /// of OpenGL cube maps so the usual skybox images load without flipping.
+    pub fn from_direction(direction: &Vec3) -> (CubeFace, Vec2f) {
+        let (x, y, z) = (direction.x, direction.y, direction.z);
+        let (ax, ay, az) = (x.abs(), y.abs(), z.abs());
+        let (face, sc, tc, major) = if ax >= ay && ax >= az {
+            if x > 0.0 {
+                (CubeFace::PositiveX, -z, -y, ax)
+            } else {
+                (CubeFace::NegativeX, z, -y, ax)
+            }
+        } else if ay >= az {
+            if y > 0.0 {
+                (CubeFace::PositiveY, x, z, ay)
+            } else {
+                (CubeFace::NegativeY, x, -z, ay)
+            }
+        } else if z > 0.0 {
+            (CubeFace::PositiveZ, x, -y, az)
+        } else {
+            (CubeFace::NegativeZ, -x, -y, az)
+        };
+        let major = major.max(f32::MIN_POSITIVE);
+        let uv = Vec2f::new(sc / major * 0.5 + 0.5, tc / major * 0.5 + 0.5);
+        (face, uv)
+    }
+
+    /// Direction through `uv` on the face, the inverse of [`CubeFace::from_direction`]. Not
+    /// normalized.
+    pub fn direction(&self, uv: &Vec2f) -> Vec3 {
+        let (sc, tc) = (uv.x * 2.0 - 1.0, uv.y * 2.0 - 1.0);
+        match self {
+            CubeFace::PositiveX => Vec3::new(1.0, -tc, -sc),
+            CubeFace::NegativeX => Vec3::new(-1.0, -tc, sc),
+            CubeFace::PositiveY => Vec3::new(sc, 1.0, tc),
+            CubeFace::NegativeY => Vec3::new(sc, -1.0, -tc),
+            CubeFace::PositiveZ => Vec3::new(sc, -tc, 1.0),
+            CubeFace::NegativeZ => Vec3::new(-sc, -tc, -1.0),
+        }
+    }
+}
+
+/// Six square textures around a point, sampled by direction. Used for skyboxes and
+/// environment reflections.
+pub struct CubeTexture {
+    /// Indexed by [`CubeFace`] as usize, +x, -x, +y, -y, +z, -z.
+    pub faces: [Texture; 6],
+}
+
+impl CubeTexture {
+    pub fn from_faces(faces: [Texture; 6]) -> Self {
+        CubeTexture { faces }
+    }
+
+    /// Loads the faces from six image files in the order of [`CubeTexture::faces`].
+    pub fn load_faces<P: AsRef<Path>>(paths: [P; 6]) -> Result<Self> {
+        let [px, nx, py, ny, pz, nz] = paths;
+        Ok(CubeTexture::from_faces([
+            Texture::load_tga_texture(px)?,
+            Texture::load_tga_texture(nx)?,
+            Texture::load_tga_texture(py)?,
+            Texture::load_tga_texture(ny)?,
+            Texture::load_tga_texture(pz)?,
+            Texture::load_tga_texture(nz)?,
+        ]))
+    }
+
+    /// Projects a panorama in equirectangular layout onto faces of `size`x`size` texels. The
+    /// middle of the image looks down -z and its top row is straight up.
+    #[profiling::function]
+    pub fn from_equirectangular(panorama: &Texture, size: u32) -> Self {
+        let face = |face: CubeFace| {
+            let pixels = (0..size * size)
+                .map(|i| {
+                    let uv = Vec2f::new(
+                        ((i % size) as f32 + 0.5) / size as f32,
+                        ((i / size) as f32 + 0.5) / size as f32,
+                    );
+                    panorama.get_color(&equirectangular_uv(&face.direction(&uv)))
+                })
+                .collect();
+            Texture {
+                pixels,
+                width: size,
+                height: size,
+                color_space: panorama.color_space,
+            }
+        };
+        CubeTexture::from_faces(CubeFace::ALL.map(face))
+    }
+
+    /// Loads a panorama, see [`CubeTexture::from_equirectangular`].
+    pub fn load_equirectangular<P: AsRef<Path>>(path: P, size: u32) -> Result<Self> {
+        let panorama = Texture::load_tga_texture(path)?;
+        Ok(CubeTexture::from_equirectangular(&panorama, size))
+    }
+
+    pub fn face(&self, face: CubeFace) -> &Texture {
+        &self.faces[face as usize]
+    }
+
+    /// Nearest texel seen in `direction` as linear values, the direction needs no normalizing.
+    pub fn sample(&self, direction: &Vec3) -> LinearColor {
+        let (face, uv) = CubeFace::from_direction(direction);
+        self.face(face).sample(&uv)
+    }
+
+    /// The environment mirrored by a surface with a unit `normal` seen from `eye`.
+    pub fn reflection(&self, position: &Vec3, normal: &Vec3, eye: &Vec3) -> LinearColor {
+        let view = (*position - *eye).normalize();
+        self.sample(&reflect(&view, normal))
+    }
+
+    /// The environment seen through a surface with a unit `normal`, bending the view by the
+    /// index ratio `eta` (about 0.66 from air into glass) and mixed with the reflection by
+    /// Fresnel. Total internal reflection only reflects.
+    pub fn refraction(&self, position: &Vec3, normal: &Vec3, eye: &Vec3, eta: f32) -> LinearColor {
+        let view = (*position - *eye).normalize();
+        let reflected = self.sample(&reflect(&view, normal));
+        let Some(refracted) = refract(&view, normal, eta) else {
+            return reflected;
+        };
+        let f0 = ((1.0 - eta) / (1.0 + eta)).powi(2);
+        let fresnel = fresnel_schlick(-view.dot(normal), f0);
+        self.sample(&refracted).lerp(&reflected, fresnel)
+    }
+}
+
+/// Texture coordinates of `direction` on an equirectangular panorama.
+fn equirectangular_uv(direction: &Vec3) -> Vec2f {
+    let d = direction.normalize();
+    let longitude = d.x.atan2(-d.z);
+    let latitude = d.y.clamp(-1.0, 1.0).acos();
+    Vec2f::new(0.5 + longitude / TAU, latitude / PI)
+}
+
+/// Unit cube around the camera, two triangles per face.
+const SKYBOX_VERTICES: [Vec3; 8] = [
+    Vec3::new(-1.0, -1.0, -1.0),
+    Vec3::new(1.0, -1.0, -1.0),
+    Vec3::new(1.0, 1.0, -1.0),
+    Vec3::new(-1.0, 1.0, -1.0),
+    Vec3::new(-1.0, -1.0, 1.0),
+    Vec3::new(1.0, -1.0, 1.0),
+    Vec3::new(1.0, 1.0, 1.0),
+    Vec3::new(-1.0, 1.0, 1.0),
+];
+
+const SKYBOX_INDICES: [u32; 36] = [
+    0, 1, 2, 0, 2, 3, // -z
+    5, 4, 7, 5, 7, 6, // +z
+    4, 0, 3, 4, 3, 7, // -x
+    1, 5, 6, 1, 6, 2, // +x
+    3, 2, 6, 3, 6, 7, // +y
+    4, 5, 1, 4, 1, 0, // -y
+];
+
+/// Draws the directions around the camera at the far plane, used by [`Renderer::draw_skybox`].
+struct SkyboxShader<'a> {
+    sky: &'a CubeTexture,
+    /// Projection times the rotation of the view, the sky never moves with the camera.
+    transform: Mat4,
+}
+
+impl Shader for SkyboxShader<'_> {
+    type Vertex = Vec3;
+    type Varying = Vec3;
+
+    fn vertex(&self, vertex: &Vec3, _instance_index: u32, _instance: &Instance) -> (Vec4, Vec3) {
+        let clip = self.transform.mul_mat41(&Mat4x1::from(*vertex)).to_vec4();
+        // z = -w lands on the far plane after the perspective divide
+        (Vec4::new(clip.x, clip.y, -clip.w, clip.w), *vertex)
+    }
+
+    fn fragment(&self, direction: &Vec3, fragment: &Fragment) -> Option<Color> {
+        self.fragment_linear(direction, fragment).map(Color::from)
+    }
+
+    fn fragment_linear(&self, direction: &Vec3, _fragment: &Fragment) -> Option<LinearColor> {
+        Some(self.sky.sample(direction))
+    }
+}
+
+impl Renderer {
+    /// Fills every pixel left empty by the scene with `sky` in the direction seen through it.
+    /// Draw it after the opaque geometry: the sky sits at the far plane, so the depth test
+    /// rejects it wherever something closer was drawn. It writes no depth, so depth based post
+    /// effects such as fog and SSAO still see nothing there. Only the rotation of `view` is used.
+    #[profiling::function]
+    pub fn draw_skybox(&mut self, sky: &CubeTexture, view: &Mat4, projection: &Mat4) {
+        let mut rotation = *view;
+        for i in 0..3 {
+            rotation[(i, 3)] = 0.0;
+        }
+        let shader = SkyboxShader {
+            sky,
+            transform: projection.mul(&rotation),
+        };
+        // the cube is seen from the inside, so no culling or bias applies to it
+        let state = *self.rasterizer_state();
+        self.set_rasterizer_state(RasterizerState {
+            depth_write: false,
+            ..Default::default()
+        });
+        self.draw_indexed(
+            &shader,
+            &SKYBOX_VERTICES,
+            &SKYBOX_INDICES,
+            PrimitiveTopology::TriangleList,
+            0,
+        );
+        self.set_rasterizer_state(state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::Camera;
+    use crate::color::ColorSpace;
+    use crate::math::{vec2, vec3};
+    use crate::postprocess::{Fog, FogFalloff, PostEffect};
+
+    fn colored_cube() -> CubeTexture {
+        let colors = [
+            Color::RED,
+            Color::GREEN,
+            Color::BLUE,
+            Color::WHITE,
+            Color::BLACK,
+            Color::rgb(255, 255, 0),
+        ];
+        CubeTexture::from_faces(
+            colors.map(|c| Texture::solid(c).with_color_space(ColorSpace::Linear)),
+        )
+    }
+
+    #[test]
+    fn test_cube_texture() {
+        for face in CubeFace::ALL {
+            for uv in [vec2(0.25, 0.75), vec2(0.5, 0.5), vec2(0.9, 0.1)] {
+                let (hit, back) = CubeFace::from_direction(&(face.direction(&uv) * 3.0));
+                assert_eq!(hit, face);
+                assert!((back - uv).x.abs() < 1e-6 && (back - uv).y.abs() < 1e-6);
+            }
+        }
+        let cube = colored_cube();
+        assert_eq!(
+            cube.sample(&vec3(2.0, 0.5, -1.0)),
+            LinearColor::rgb(1.0, 0.0, 0.0)
+        );
+        assert_eq!(
+            cube.sample(&vec3(0.0, 0.1, -1.0)),
+            LinearColor::rgb(1.0, 1.0, 0.0)
+        );
+
+        // a panorama with a red sky, a blue ground and a white stripe facing -z
+        let mut panorama = Texture {
+            pixels: vec![Color::BLUE; 64 * 32],
+            width: 64,
+            height: 32,
+            color_space: ColorSpace::Srgb,
+        };
+        for (i, pixel) in panorama.pixels.iter_mut().enumerate() {
+            let (x, y) = (i % 64, i / 64);
+            if y < 16 {
+                *pixel = Color::RED;
+            }
+            if (30..34).contains(&x) {
+                *pixel = Color::WHITE;
+            }
+        }
+        let sky = CubeTexture::from_equirectangular(&panorama, 8);
+        assert_eq!(
+            sky.face(CubeFace::PositiveY).get_color(&vec2(0.3, 0.3)),
+            Color::RED
+        );
+        assert_eq!(
+            sky.face(CubeFace::NegativeY).get_color(&vec2(0.3, 0.3)),
+            Color::BLUE
+        );
+        assert_eq!(sky.sample(&vec3(0.0, 0.2, -1.0)), LinearColor::WHITE);
+        assert_eq!(
+            sky.sample(&vec3(1.0, 0.2, 0.0)),
+            LinearColor::rgb(1.0, 0.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn test_environment_mapping() {
+        let up = vec3(0.0, 1.0, 0.0);
+        assert_eq!(reflect(&vec3(1.0, -1.0, 0.0), &up), vec3(1.0, 1.0, 0.0));
+        // head on light passes straight through, grazing light bends towards the normal
+        assert_eq!(
+            refract(&vec3(0.0, -1.0, 0.0), &up, 0.66),
+            Some(vec3(0.0, -1.0, 0.0))
+        );
+        let grazing = vec3(1.0, -1.0, 0.0).normalize();
+        let bent = refract(&grazing, &up, 0.66).unwrap();
+        assert!((bent.sqrt() - 1.0).abs() < 1e-5);
+        assert!(bent.x < grazing.x);
+        // leaving glass at a shallow angle reflects everything
+        assert_eq!(refract(&grazing, &up, 1.5), None);
+        assert_eq!(fresnel_schlick(1.0, 0.04), 0.04);
+        assert_eq!(fresnel_schlick(0.0, 0.04), 1.0);
+
+        let cube = colored_cube();
+        let eye = vec3(0.0, 0.0, 5.0);
+        // a mirror facing the eye reflects what is behind the eye
+        let mirror = cube.reflection(&Vec3::ZERO, &vec3(0.0, 0.0, 1.0), &eye);
+        assert_eq!(mirror, LinearColor::BLACK);
+        // mostly the scene behind the glass, with a little of the reflection
+        let glass = cube.refraction(&Vec3::ZERO, &vec3(0.0, 0.0, 1.0), &eye, 0.66);
+        assert!(glass.r > 0.9 && glass.g > 0.9 && glass.b == 0.0);
+
+        let mut camera = Camera::new(1.2, 1.0, 0.1, 10.0);
+        camera.position = vec3(0.0, 0.0, 3.0);
+        let mut renderer = Renderer::new(16, 16, false);
+        renderer.set_color_space(ColorSpace::Linear);
+        renderer.clear(Color::BLACK);
+        let transform = camera.view_projection_matrix();
+        let square = [
+            vec3(-0.2, -0.2, 0.0),
+            vec3(0.2, -0.2, 0.0),
+            vec3(0.2, 0.2, 0.0),
+            vec3(-0.2, -0.2, 0.0),
+            vec3(0.2, 0.2, 0.0),
+            vec3(-0.2, 0.2, 0.0),
+        ];
+        renderer.draw_primitives(
+            &square,
+            PrimitiveTopology::TriangleList,
+            &transform,
+            Color::WHITE,
+        );
+        renderer.draw_skybox(&cube, &camera.view_matrix(), &camera.projection_matrix());
+        // the skybox only fills the background and doesn't move with the camera
+        let yellow = Color::rgb(255, 255, 0);
+        assert_eq!(renderer.pixels()[0], yellow);
+        assert_eq!(renderer.pixels()[8 * 16 + 8], Color::WHITE);
+        // the sky writes no depth, so fog that spares the background leaves it alone
+        let mut frame = renderer.frame_buffers();
+        assert_eq!(frame.ndc(0, 0), None);
+        assert!(frame.ndc(8, 8).is_some());
+        let sky = frame.color[0];
+        let mut fog = Fog::new(
+            camera,
+            FogFalloff::Exponential { density: 1.0 },
+            LinearColor::WHITE,
+        );
+        fog.background = false;
+        fog.apply(&mut frame);
+        assert_eq!(frame.color[0], sky);
+    }
+}
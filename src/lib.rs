@@ -1,5 +1,23 @@
+pub mod camera;
 pub mod color;
+pub mod composite;
+pub mod cubemap;
+pub mod light;
+pub mod line;
 pub mod math;
 pub mod model;
+pub mod optimize;
+pub mod postprocess;
+pub mod procedural;
+pub mod rasterizer;
 pub mod renderer;
+pub mod resample;
+pub mod scene;
+pub mod shader;
+pub mod simplify;
+pub mod stencil;
 pub mod texture;
+#[cfg(test)]
+mod testing;
+pub mod tonemap;
+pub mod viewport;
@@ -0,0 +1,371 @@
+use crate::camera::Camera;
+use crate::math::{Mat4, Mat4x1, Vec3};
+
+/// Where a light is and how its influence falls off.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LightKind {
+    /// Infinitely far away, `direction` is the direction the light travels in.
+    Directional { direction: Vec3 },
+    /// Emits in every direction, fading out to nothing at `range`.
+    Point { position: Vec3, range: f32 },
+    /// A point light restricted to a cone around `direction`. Full intensity inside
+    /// `inner_angle` and none outside of `outer_angle`, both half angles in radians.
+    Spot {
+        position: Vec3,
+        direction: Vec3,
+        range: f32,
+        inner_angle: f32,
+        outer_angle: f32,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Light {
+    pub kind: LightKind,
+    /// Linear RGB, usually in 0..1.
+    pub color: Vec3,
+    pub intensity: f32,
+}
+
+impl Light {
+    pub fn directional(direction: Vec3, color: Vec3, intensity: f32) -> Self {
+        Light {
+            kind: LightKind::Directional {
+                direction: direction.normalize(),
+            },
+            color,
+            intensity,
+        }
+    }
+
+    pub fn point(position: Vec3, range: f32, color: Vec3, intensity: f32) -> Self {
+        Light {
+            kind: LightKind::Point { position, range },
+            color,
+            intensity,
+        }
+    }
+
+    pub fn spot(
+        position: Vec3,
+        direction: Vec3,
+        range: f32,
+        inner_angle: f32,
+        outer_angle: f32,
+        color: Vec3,
+        intensity: f32,
+    ) -> Self {
+        Light {
+            kind: LightKind::Spot {
+                position,
+                direction: direction.normalize(),
+                range,
+                inner_angle,
+                outer_angle,
+            },
+            color,
+            intensity,
+        }
+    }
+
+    /// Direction from `position` towards the light and the light arriving there, `None` when
+    /// the light doesn't reach the point or sits on it without a direction.
+    pub fn incident(&self, position: &Vec3) -> Option<(Vec3, Vec3)> {
+        let radiance = self.color * self.intensity;
+        match self.kind {
+            LightKind::Directional { direction } => Some((-direction, radiance)),
+            LightKind::Point {
+                position: light,
+                range,
+            } => {
+                let offset = light - *position;
+                let distance = offset.sqrt();
+                if distance <= f32::EPSILON {
+                    return None;
+                }
+                let attenuation = distance_attenuation(distance, range);
+                (attenuation > 0.0).then(|| (offset / distance, radiance * attenuation))
+            }
+            LightKind::Spot {
+                position: light,
+                direction,
+                range,
+                inner_angle,
+                outer_angle,
+            } => {
+                let offset = light - *position;
+                let distance = offset.sqrt();
+                if distance <= f32::EPSILON {
+                    return None;
+                }
+                let to_light = offset / distance;
+                let cos = (-to_light).dot(&direction);
+                let cone = smoothstep(outer_angle.cos(), inner_angle.cos(), cos);
+                let attenuation = distance_attenuation(distance, range) * cone;
+                (attenuation > 0.0).then(|| (to_light, radiance * attenuation))
+            }
+        }
+    }
+
+    /// The light moved by an affine transform, such as the world transform of a scene node.
+    /// Ranges and cone angles are kept.
+    pub fn transformed(&self, m: &Mat4) -> Light {
+        let point = |p: &Vec3| m.mul_mat41(&Mat4x1::from(*p)).to_vec3();
+        let vector = |v: &Vec3| {
+            let v = m.mul_mat41(&Mat4x1::new([v.x, v.y, v.z, 0.0])).to_vec4();
+            Vec3::new(v.x, v.y, v.z).normalize()
+        };
+        let kind = match self.kind {
+            LightKind::Directional { direction } => LightKind::Directional {
+                direction: vector(&direction),
+            },
+            LightKind::Point { position, range } => LightKind::Point {
+                position: point(&position),
+                range,
+            },
+            LightKind::Spot {
+                position,
+                direction,
+                range,
+                inner_angle,
+                outer_angle,
+            } => LightKind::Spot {
+                position: point(&position),
+                direction: vector(&direction),
+                range,
+                inner_angle,
+                outer_angle,
+            },
+        };
+        Light { kind, ..*self }
+    }
+}
+
+/// Inverse square falloff, windowed to reach zero at `range` like Frostbite and Unreal do.
+fn distance_attenuation(distance: f32, range: f32) -> f32 {
+    let window = (1.0 - (distance / range).powi(4)).clamp(0.0, 1.0);
+    window * window / distance.powi(2).max(1e-4)
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// Light arriving from every direction, independent of the light list.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Ambient {
+    #[default]
+    None,
+    Constant(Vec3),
+    /// `sky` from above `up`, `ground` from below, blended by the normal.
+    Hemisphere {
+        sky: Vec3,
+        ground: Vec3,
+        up: Vec3,
+    },
+}
+
+impl Ambient {
+    pub fn irradiance(&self, normal: &Vec3) -> Vec3 {
+        match *self {
+            Ambient::None => Vec3::ZERO,
+            Ambient::Constant(color) => color,
+            Ambient::Hemisphere { sky, ground, up } => {
+                let t = normal.dot(&up) * 0.5 + 0.5;
+                ground * (1.0 - t) + sky * t
+            }
+        }
+    }
+}
+
+/// Lights and ambient term shared by the shaders of a frame.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Lighting {
+    pub lights: Vec<Light>,
+    pub ambient: Ambient,
+}
+
+impl Lighting {
+    /// Classic three point lighting relative to `camera`: a warm key light from the upper
+    /// left front, a dimmer cool fill from the right and a rim light from behind the subject.
+    pub fn key_fill_rim(camera: &Camera) -> Self {
+        let (forward, right, up) = (camera.forward(), camera.right(), camera.up());
+        Lighting {
+            lights: vec![
+                Light::directional(
+                    forward + right * 0.7 - up * 0.6,
+                    Vec3::new(1.0, 0.95, 0.85),
+                    1.0,
+                ),
+                Light::directional(
+                    forward - right * 0.8 - up * 0.2,
+                    Vec3::new(0.8, 0.9, 1.0),
+                    0.35,
+                ),
+                Light::directional(-forward - up * 0.5, Vec3::ONE, 0.6),
+            ],
+            ambient: Ambient::Hemisphere {
+                sky: Vec3::splat(0.08),
+                ground: Vec3::splat(0.02),
+                up: Vec3::new(0.0, 1.0, 0.0),
+            },
+        }
+    }
+
+    /// Lambertian diffuse lighting of a point with a unit `normal`, including the ambient term.
+    pub fn diffuse(&self, position: &Vec3, normal: &Vec3) -> Vec3 {
+        self.diffuse_occluded(position, normal, 1.0)
+    }
+
+    /// [`Lighting::diffuse`] with the ambient term scaled by `occlusion`, the visibility of the
+    /// point from an ambient occlusion pass such as [`Ssao`](crate::postprocess::Ssao). 0 leaves
+    /// only the direct light.
+    pub fn diffuse_occluded(&self, position: &Vec3, normal: &Vec3, occlusion: f32) -> Vec3 {
+        self.lights
+            .iter()
+            .filter_map(|light| light.incident(position))
+            .fold(
+                self.ambient.irradiance(normal) * occlusion,
+                |sum, (to_light, radiance)| sum + radiance * normal.dot(&to_light).max(0.0),
+            )
+    }
+
+    /// Blinn-Phong diffuse and specular lighting of a point seen from `eye`. The ambient term
+    /// is part of the diffuse light.
+    pub fn blinn_phong(
+        &self,
+        position: &Vec3,
+        normal: &Vec3,
+        eye: &Vec3,
+        shininess: f32,
+    ) -> (Vec3, Vec3) {
+        self.blinn_phong_occluded(position, normal, eye, shininess, 1.0)
+    }
+
+    /// [`Lighting::blinn_phong`] with the ambient term scaled by `occlusion`, see
+    /// [`Lighting::diffuse_occluded`].
+    pub fn blinn_phong_occluded(
+        &self,
+        position: &Vec3,
+        normal: &Vec3,
+        eye: &Vec3,
+        shininess: f32,
+        occlusion: f32,
+    ) -> (Vec3, Vec3) {
+        let to_eye = (*eye - *position).normalize();
+        let mut diffuse = self.ambient.irradiance(normal) * occlusion;
+        let mut specular = Vec3::ZERO;
+        for (to_light, radiance) in self.lights.iter().filter_map(|l| l.incident(position)) {
+            let n_dot_l = normal.dot(&to_light);
+            if n_dot_l <= 0.0 {
+                continue;
+            }
+            diffuse += radiance * n_dot_l;
+            let half = (to_light + to_eye).normalize();
+            specular += radiance * normal.dot(&half).max(0.0).powf(shininess);
+        }
+        (diffuse, specular)
+    }
+}
+
+/// Mirrors `incident`, a direction travelling towards the surface, about a unit `normal`.
+pub fn reflect(incident: &Vec3, normal: &Vec3) -> Vec3 {
+    *incident - *normal * (2.0 * normal.dot(incident))
+}
+
+/// Bends `incident` through a surface with a unit `normal` facing against it, `eta` is the
+/// ratio of the refractive indices, outside over inside. `None` on total internal reflection.
+pub fn refract(incident: &Vec3, normal: &Vec3, eta: f32) -> Option<Vec3> {
+    let cos_i = -normal.dot(incident);
+    let k = 1.0 - eta * eta * (1.0 - cos_i * cos_i);
+    if k < 0.0 {
+        return None;
+    }
+    Some(*incident * eta + *normal * (eta * cos_i - k.sqrt()))
+}
+
+/// Schlick's approximation of the share of light reflected at a surface, from the cosine
+/// between the view direction and the normal and the reflectance at normal incidence.
+pub fn fresnel_schlick(cos_theta: f32, f0: f32) -> f32 {
+    f0 + (1.0 - f0) * (1.0 - cos_theta.clamp(0.0, 1.0)).powi(5)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::vec3;
+
+    #[test]
+    fn test_light_falloff() {
+        let point = Light::point(vec3(0.0, 2.0, 0.0), 10.0, Vec3::ONE, 4.0);
+        let (to_light, radiance) = point.incident(&Vec3::ZERO).unwrap();
+        assert_eq!(to_light, vec3(0.0, 1.0, 0.0));
+        assert!((radiance.x - 1.0).abs() < 0.01);
+        let (_, far) = point.incident(&vec3(0.0, -4.0, 0.0)).unwrap();
+        assert!(far.x < radiance.x / 9.0);
+        assert_eq!(point.incident(&vec3(0.0, 12.0, 0.0)), None);
+        // no direction to the light from its own position
+        assert_eq!(point.incident(&vec3(0.0, 2.0, 0.0)), None);
+
+        let spot = Light::spot(
+            vec3(0.0, 1.0, 0.0),
+            vec3(0.0, -1.0, 0.0),
+            10.0,
+            0.2,
+            0.4,
+            Vec3::ONE,
+            1.0,
+        );
+        let inside = spot.incident(&Vec3::ZERO).unwrap().1;
+        let edge = spot.incident(&vec3(0.3f32.tan(), 0.0, 0.0)).unwrap().1;
+        assert!(edge.x > 0.0 && edge.x < inside.x * 0.7);
+        assert_eq!(spot.incident(&vec3(1.0, 0.0, 0.0)), None);
+        assert_eq!(spot.incident(&vec3(0.0, 1.0, 0.0)), None);
+
+        // a spot moved with a scene node
+        let moved = spot.transformed(&Mat4::identity().translate(5.0, 0.0, 0.0));
+        assert!(moved.incident(&vec3(5.0, 0.0, 0.0)).is_some());
+        assert_eq!(moved.incident(&Vec3::ZERO), None);
+    }
+
+    #[test]
+    fn test_lighting() {
+        let lighting = Lighting {
+            lights: vec![
+                Light::directional(vec3(0.0, -1.0, 0.0), vec3(1.0, 0.5, 0.0), 1.0),
+                Light::directional(vec3(0.0, 1.0, 0.0), Vec3::ONE, 1.0),
+            ],
+            ambient: Ambient::Hemisphere {
+                sky: vec3(0.0, 0.0, 0.2),
+                ground: vec3(0.1, 0.0, 0.0),
+                up: vec3(0.0, 1.0, 0.0),
+            },
+        };
+        let up = vec3(0.0, 1.0, 0.0);
+        assert_eq!(lighting.diffuse(&Vec3::ZERO, &up), vec3(1.0, 0.5, 0.2));
+        let side = lighting.diffuse(&Vec3::ZERO, &vec3(1.0, 0.0, 0.0));
+        assert_eq!(side, vec3(0.05, 0.0, 0.1));
+        let occluded = lighting.diffuse_occluded(&Vec3::ZERO, &vec3(1.0, 0.0, 0.0), 0.5);
+        assert_eq!(occluded, vec3(0.025, 0.0, 0.05));
+        // a point light on the surface adds nothing instead of NaN
+        let mut touching = lighting.clone();
+        touching
+            .lights
+            .push(Light::point(Vec3::ZERO, 10.0, Vec3::ONE, 1.0));
+        assert_eq!(touching.diffuse(&Vec3::ZERO, &up), vec3(1.0, 0.5, 0.2));
+
+        // the highlight is brightest when the eye sits in the reflected direction
+        let (_, head_on) = lighting.blinn_phong(&Vec3::ZERO, &up, &vec3(0.0, 5.0, 0.0), 32.0);
+        let (_, grazing) = lighting.blinn_phong(&Vec3::ZERO, &up, &vec3(5.0, 1.0, 0.0), 32.0);
+        assert_eq!(head_on, vec3(1.0, 0.5, 0.0));
+        assert!(grazing.x < 0.1);
+
+        let camera = Camera::new(1.0, 1.0, 0.1, 10.0);
+        let three_point = Lighting::key_fill_rim(&camera);
+        assert_eq!(three_point.lights.len(), 3);
+        // a surface facing the camera is lit by the key and fill lights
+        let lit = three_point.diffuse(&Vec3::ZERO, &vec3(0.0, 0.0, 1.0));
+        assert!(lit.x > 0.5);
+    }
+}
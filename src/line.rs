@@ -0,0 +1,517 @@
+use crate::color::{Color, ColorSpace};
+use crate::math::Vec2;
+use crate::renderer::Renderer;
+
+/// Shape drawn at the two ends of an open line.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LineCap {
+    /// Ends exactly at the endpoint.
+    #[default]
+    Butt,
+    /// Extends past the endpoint by half the line width.
+    Square,
+    Round,
+}
+
+/// Shape drawn where two segments of a polyline meet.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LineJoin {
+    /// Extends the outer edges until they meet, falling back to `Bevel` past the miter limit.
+    #[default]
+    Miter,
+    Bevel,
+    Round,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LineStyle {
+    /// Width in pixels.
+    pub width: f32,
+    pub cap: LineCap,
+    pub join: LineJoin,
+    /// Maximum ratio between the miter length and half the line width.
+    pub miter_limit: f32,
+    /// Alternating dash and gap lengths in pixels, empty for a solid line.
+    pub dash: Vec<f32>,
+    /// Distance into the dash pattern at which the line starts.
+    pub dash_offset: f32,
+    pub anti_aliased: bool,
+}
+
+impl Default for LineStyle {
+    fn default() -> Self {
+        LineStyle {
+            width: 1.0,
+            cap: LineCap::Butt,
+            join: LineJoin::Miter,
+            miter_limit: 4.0,
+            dash: vec![],
+            dash_offset: 0.0,
+            anti_aliased: true,
+        }
+    }
+}
+
+/// Clips the segment `p0`-`p1` to the rectangle `min`-`max` with the Liang-Barsky algorithm.
+/// Returns `None` when the segment lies completely outside.
+pub fn clip_line(p0: &Vec2, p1: &Vec2, min: &Vec2, max: &Vec2) -> Option<(Vec2, Vec2)> {
+    let (t0, t1) = clip_line_parameters(p0, p1, min, max)?;
+    let d = *p1 - *p0;
+    Some((*p0 + d * t0, *p0 + d * t1))
+}
+
+/// Like [`clip_line`], but returns the parameters along the segment of the clipped endpoints so
+/// other attributes can be interpolated the same way.
+pub fn clip_line_parameters(p0: &Vec2, p1: &Vec2, min: &Vec2, max: &Vec2) -> Option<(f32, f32)> {
+    let d = *p1 - *p0;
+    let mut t0 = 0.0f32;
+    let mut t1 = 1.0f32;
+    for (p, q) in [
+        (-d.x, p0.x - min.x),
+        (d.x, max.x - p0.x),
+        (-d.y, p0.y - min.y),
+        (d.y, max.y - p0.y),
+    ] {
+        if p == 0.0 {
+            // parallel to this edge
+            if q < 0.0 {
+                return None;
+            }
+        } else {
+            let r = q / p;
+            if p < 0.0 {
+                if r > t1 {
+                    return None;
+                }
+                t0 = t0.max(r);
+            } else {
+                if r < t0 {
+                    return None;
+                }
+                t1 = t1.min(r);
+            }
+        }
+    }
+    Some((t0, t1))
+}
+
+/// Area covered by a thick line, rasterized through its signed distance.
+enum Shape {
+    /// Convex polygon, in any winding order.
+    Polygon(Vec<Vec2>),
+    Disk {
+        center: Vec2,
+        radius: f32,
+    },
+}
+
+impl Shape {
+    fn bounds(&self) -> (Vec2, Vec2) {
+        match self {
+            Shape::Polygon(points) => points.iter().fold(
+                (Vec2::splat(f32::MAX), Vec2::splat(-f32::MAX)),
+                |(min, max), p| {
+                    (
+                        Vec2::new(min.x.min(p.x), min.y.min(p.y)),
+                        Vec2::new(max.x.max(p.x), max.y.max(p.y)),
+                    )
+                },
+            ),
+            Shape::Disk { center, radius } => (
+                *center - Vec2::splat(*radius),
+                *center + Vec2::splat(*radius),
+            ),
+        }
+    }
+
+    /// Approximate signed distance from `p` to the shape's border, negative inside.
+    fn distance(&self, p: &Vec2) -> f32 {
+        match self {
+            Shape::Polygon(points) => {
+                let mut area = 0.0;
+                for (i, a) in points.iter().enumerate() {
+                    let b = points[(i + 1) % points.len()];
+                    area += a.x * b.y - b.x * a.y;
+                }
+                let orientation = if area < 0.0 { -1.0 } else { 1.0 };
+                let mut distance = -f32::MAX;
+                for (i, a) in points.iter().enumerate() {
+                    let edge = points[(i + 1) % points.len()] - *a;
+                    let length = edge.sqrt();
+                    if length < f32::EPSILON {
+                        continue;
+                    }
+                    // outward normal of a counter-clockwise edge
+                    let normal = Vec2::new(edge.y, -edge.x) * (orientation / length);
+                    distance = distance.max((*p - *a).dot(&normal));
+                }
+                distance
+            }
+            Shape::Disk { center, radius } => (*p - *center).sqrt() - radius,
+        }
+    }
+}
+
+fn perpendicular(d: &Vec2) -> Vec2 {
+    Vec2::new(-d.y, d.x)
+}
+
+/// Splits a polyline into the runs drawn by a dash pattern.
+fn dash_runs(points: &[Vec2], dash: &[f32], offset: f32) -> Vec<Vec<Vec2>> {
+    let period: f32 = dash.iter().sum();
+    if dash.is_empty() || period <= 0.0 {
+        return vec![points.to_vec()];
+    }
+    // find where in the pattern the line starts
+    let mut index = 0;
+    let mut remaining = dash[0];
+    let mut skip = offset.rem_euclid(period);
+    while skip > 0.0 {
+        if skip < remaining {
+            remaining -= skip;
+            break;
+        }
+        skip -= remaining;
+        index = (index + 1) % dash.len();
+        remaining = dash[index];
+    }
+
+    let mut runs = vec![];
+    let mut current = vec![points[0]];
+    for pair in points.windows(2) {
+        let (a, b) = (pair[0], pair[1]);
+        let length = (b - a).sqrt();
+        let mut travelled = 0.0;
+        while length - travelled > remaining {
+            travelled += remaining;
+            let p = a + (b - a) * (travelled / length);
+            if index % 2 == 0 {
+                current.push(p);
+                runs.push(std::mem::take(&mut current));
+            } else {
+                current = vec![p];
+            }
+            index = (index + 1) % dash.len();
+            remaining = dash[index];
+        }
+        remaining -= length - travelled;
+        if index % 2 == 0 {
+            current.push(b);
+        }
+    }
+    if index % 2 == 0 && current.len() > 1 {
+        runs.push(current);
+    }
+    runs
+}
+
+/// Builds the shapes covering one run of a thick polyline, including its caps and joins.
+fn stroke_shapes(points: &[Vec2], style: &LineStyle, shapes: &mut Vec<Shape>) {
+    let half_width = style.width / 2.0;
+    let points: Vec<Vec2> = points
+        .iter()
+        .enumerate()
+        .filter(|(i, p)| *i == 0 || (**p - points[i - 1]).sqrt() > 1e-4)
+        .map(|(_, p)| *p)
+        .collect();
+    if points.len() == 1 {
+        // zero length, only the caps are visible
+        match style.cap {
+            LineCap::Butt => {}
+            LineCap::Square => {
+                let r = Vec2::splat(half_width);
+                let p = points[0];
+                shapes.push(Shape::Polygon(vec![
+                    p - r,
+                    Vec2::new(p.x + r.x, p.y - r.y),
+                    p + r,
+                    Vec2::new(p.x - r.x, p.y + r.y),
+                ]));
+            }
+            LineCap::Round => shapes.push(Shape::Disk {
+                center: points[0],
+                radius: half_width,
+            }),
+        }
+        return;
+    }
+
+    let last = points.len() - 2;
+    for (i, pair) in points.windows(2).enumerate() {
+        let d = (pair[1] - pair[0]).normalize();
+        let n = perpendicular(&d) * half_width;
+        let mut a = pair[0];
+        let mut b = pair[1];
+        if style.cap == LineCap::Square {
+            if i == 0 {
+                a -= d * half_width;
+            }
+            if i == last {
+                b += d * half_width;
+            }
+        }
+        shapes.push(Shape::Polygon(vec![a + n, b + n, b - n, a - n]));
+    }
+    if style.cap == LineCap::Round {
+        for p in [points[0], points[points.len() - 1]] {
+            shapes.push(Shape::Disk {
+                center: p,
+                radius: half_width,
+            });
+        }
+    }
+
+    for triple in points.windows(3) {
+        let v = triple[1];
+        let d0 = (triple[1] - triple[0]).normalize();
+        let d1 = (triple[2] - triple[1]).normalize();
+        let turn = d0.x * d1.y - d0.y * d1.x;
+        if turn.abs() < 1e-4 && d0.dot(&d1) > 0.0 {
+            continue;
+        }
+        // the outer side of the bend is opposite to the turning direction
+        let side = if turn > 0.0 { -1.0 } else { 1.0 };
+        let n0 = perpendicular(&d0) * (side * half_width);
+        let n1 = perpendicular(&d1) * (side * half_width);
+        match style.join {
+            LineJoin::Round => shapes.push(Shape::Disk {
+                center: v,
+                radius: half_width,
+            }),
+            LineJoin::Bevel => shapes.push(Shape::Polygon(vec![v, v + n0, v + n1])),
+            LineJoin::Miter => {
+                let bisector = (n0 + n1).normalize();
+                let cos_half = bisector.dot(&n0.normalize());
+                if cos_half > 1e-4 && 1.0 / cos_half <= style.miter_limit {
+                    let miter = v + bisector * (half_width / cos_half);
+                    shapes.push(Shape::Polygon(vec![v, v + n0, miter, v + n1]));
+                } else {
+                    shapes.push(Shape::Polygon(vec![v, v + n0, v + n1]));
+                }
+            }
+        }
+    }
+}
+
+impl Renderer {
+    /// Blends the sRGB encoded `color` over the pixel in linear light with the given coverage,
+    /// scaled by the color's alpha.
+    pub fn blend_pixel(&mut self, x: u32, y: u32, color: Color, coverage: f32) {
+        let alpha = coverage.clamp(0.0, 1.0) * color.a as f32 / 255.0;
+        if alpha <= 0.0 {
+            return;
+        }
+        let src = ColorSpace::Srgb.decode(color);
+        self.update_pixel(x, y, |dst| dst.lerp(&src, alpha));
+    }
+
+    /// Draws an anti-aliased one pixel wide line with Xiaolin Wu's algorithm.
+    #[profiling::function]
+    pub fn draw_line_aa(&mut self, v0: &Vec2, v1: &Vec2, color: Color) {
+        let clip = self.clip_rect();
+        if clip.is_empty() {
+            return;
+        }
+        // work with pixel centers on integer coordinates, one pixel of margin for the edges
+        let min = Vec2::new(clip.x as f32 - 1.0, clip.y as f32 - 1.0);
+        let max = Vec2::new((clip.x + clip.width) as f32, (clip.y + clip.height) as f32);
+        let half = Vec2::splat(0.5);
+        let Some((p0, p1)) = clip_line(&(*v0 - half), &(*v1 - half), &min, &max) else {
+            return;
+        };
+
+        let (mut x0, mut y0, mut x1, mut y1) = (p0.x, p0.y, p1.x, p1.y);
+        let steep = (y1 - y0).abs() > (x1 - x0).abs();
+        if steep {
+            std::mem::swap(&mut x0, &mut y0);
+            std::mem::swap(&mut x1, &mut y1);
+        }
+        if x0 > x1 {
+            std::mem::swap(&mut x0, &mut x1);
+            std::mem::swap(&mut y0, &mut y1);
+        }
+        let dx = x1 - x0;
+        let dy = y1 - y0;
+        let gradient = if dx < f32::EPSILON { 1.0 } else { dy / dx };
+
+        let plot = |renderer: &mut Renderer, x: f32, y: f32, coverage: f32| {
+            let (x, y) = if steep { (y, x) } else { (x, y) };
+            if x >= 0.0 && y >= 0.0 {
+                renderer.blend_pixel(x as u32, y as u32, color, coverage);
+            }
+        };
+        let fract = |x: f32| x - x.floor();
+
+        // first endpoint
+        let x_end = (x0 + 0.5).floor();
+        let y_end = y0 + gradient * (x_end - x0);
+        let x_gap = 1.0 - fract(x0 + 0.5);
+        let x_start = x_end;
+        plot(self, x_end, y_end.floor(), (1.0 - fract(y_end)) * x_gap);
+        plot(self, x_end, y_end.floor() + 1.0, fract(y_end) * x_gap);
+        let mut inter_y = y_end + gradient;
+
+        // second endpoint
+        let x_end = (x1 + 0.5).floor();
+        let y_end = y1 + gradient * (x_end - x1);
+        let x_gap = fract(x1 + 0.5);
+        if x_end > x_start {
+            plot(self, x_end, y_end.floor(), (1.0 - fract(y_end)) * x_gap);
+            plot(self, x_end, y_end.floor() + 1.0, fract(y_end) * x_gap);
+        }
+
+        let mut x = x_start + 1.0;
+        while x < x_end {
+            plot(self, x, inter_y.floor(), 1.0 - fract(inter_y));
+            plot(self, x, inter_y.floor() + 1.0, fract(inter_y));
+            inter_y += gradient;
+            x += 1.0;
+        }
+    }
+
+    pub fn draw_line_styled(&mut self, v0: &Vec2, v1: &Vec2, style: &LineStyle, color: Color) {
+        self.draw_polyline(&[*v0, *v1], style, color);
+    }
+
+    /// Draws connected segments with the width, caps, joins and dash pattern of `style`.
+    #[profiling::function]
+    pub fn draw_polyline(&mut self, points: &[Vec2], style: &LineStyle, color: Color) {
+        if points.is_empty() || style.width <= 0.0 {
+            return;
+        }
+        let mut shapes = vec![];
+        for run in dash_runs(points, &style.dash, style.dash_offset) {
+            stroke_shapes(&run, style, &mut shapes);
+        }
+        if shapes.is_empty() {
+            return;
+        }
+
+        let clip = self.clip_rect();
+        let (mut min, mut max) = (Vec2::splat(f32::MAX), Vec2::splat(-f32::MAX));
+        let bounds: Vec<(Vec2, Vec2)> = shapes.iter().map(|s| s.bounds()).collect();
+        for (shape_min, shape_max) in &bounds {
+            min = Vec2::new(min.x.min(shape_min.x), min.y.min(shape_min.y));
+            max = Vec2::new(max.x.max(shape_max.x), max.y.max(shape_max.y));
+        }
+        let x0 = (min.x - 1.0).floor().max(clip.x as f32) as u32;
+        let y0 = (min.y - 1.0).floor().max(clip.y as f32) as u32;
+        let x1 = (max.x + 1.0).ceil().min((clip.x + clip.width) as f32);
+        let y1 = (max.y + 1.0).ceil().min((clip.y + clip.height) as f32);
+        if x1 <= 0.0 || y1 <= 0.0 {
+            return;
+        }
+        for y in y0..y1 as u32 {
+            for x in x0..x1 as u32 {
+                let p = Vec2::new(x as f32 + 0.5, y as f32 + 0.5);
+                // overlapping shapes must not blend twice, keep the strongest coverage
+                let mut coverage = 0.0f32;
+                for (shape, (shape_min, shape_max)) in shapes.iter().zip(&bounds) {
+                    if p.x < shape_min.x - 1.0
+                        || p.y < shape_min.y - 1.0
+                        || p.x > shape_max.x + 1.0
+                        || p.y > shape_max.y + 1.0
+                    {
+                        continue;
+                    }
+                    coverage = coverage.max((0.5 - shape.distance(&p)).clamp(0.0, 1.0));
+                }
+                if !style.anti_aliased {
+                    coverage = if coverage >= 0.5 { 1.0 } else { 0.0 };
+                }
+                self.blend_pixel(x, y, color, coverage);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clip_line() {
+        let min = Vec2::new(0.0, 0.0);
+        let max = Vec2::new(10.0, 10.0);
+        let clipped = clip_line(&Vec2::new(-5.0, 5.0), &Vec2::new(15.0, 5.0), &min, &max);
+        assert_eq!(clipped, Some((Vec2::new(0.0, 5.0), Vec2::new(10.0, 5.0))));
+
+        let inside = (Vec2::new(1.0, 2.0), Vec2::new(3.0, 4.0));
+        assert_eq!(clip_line(&inside.0, &inside.1, &min, &max), Some(inside));
+
+        assert_eq!(
+            clip_line(&Vec2::new(-5.0, -1.0), &Vec2::new(15.0, -1.0), &min, &max),
+            None
+        );
+        assert_eq!(
+            clip_line(&Vec2::new(-5.0, 4.0), &Vec2::new(4.0, 15.0), &min, &max),
+            None
+        );
+    }
+
+    #[test]
+    fn test_dash_runs() {
+        let points = [Vec2::new(0.0, 0.0), Vec2::new(10.0, 0.0)];
+        let runs = dash_runs(&points, &[3.0, 2.0], 0.0);
+        let starts: Vec<f32> = runs.iter().map(|r| r[0].x).collect();
+        assert_eq!(starts, vec![0.0, 5.0]);
+        assert_eq!(runs[1][1].x, 8.0);
+
+        let runs = dash_runs(&points, &[3.0, 2.0], 4.0);
+        assert_eq!(runs[0], vec![Vec2::new(1.0, 0.0), Vec2::new(4.0, 0.0)]);
+    }
+
+    #[test]
+    fn test_draw_lines() {
+        let mut renderer = Renderer::new(16, 16, false);
+        renderer.clear(Color::BLACK);
+        let row = |renderer: &Renderer, y: usize| -> Vec<Color> {
+            renderer.pixels()[y * 16..(y + 1) * 16].to_vec()
+        };
+
+        // anti-aliased, partly off screen, exactly on the pixel centers of row 2
+        renderer.draw_line_aa(&Vec2::new(-10.0, 2.5), &Vec2::new(30.0, 2.5), Color::WHITE);
+        assert!(row(&renderer, 2).iter().all(|c| *c == Color::WHITE));
+        assert!(row(&renderer, 1).iter().all(|c| *c == Color::BLACK));
+        // a line between two rows covers both halfway, blended in linear light
+        renderer.draw_line_aa(&Vec2::new(0.0, 5.0), &Vec2::new(16.0, 5.0), Color::WHITE);
+        assert_eq!(row(&renderer, 4)[8], Color::rgb(188, 188, 188));
+        assert_eq!(row(&renderer, 5)[8], Color::rgb(188, 188, 188));
+        renderer.clear(Color::BLACK);
+
+        // thick and dashed
+        let style = LineStyle {
+            width: 3.0,
+            dash: vec![4.0, 4.0],
+            ..Default::default()
+        };
+        renderer.draw_line_styled(
+            &Vec2::new(0.0, 10.5),
+            &Vec2::new(16.0, 10.5),
+            &style,
+            Color::RED,
+        );
+        for y in 9..=11 {
+            let row = row(&renderer, y);
+            assert_eq!(&row[0..4], &[Color::RED; 4]);
+            assert_eq!(&row[4..8], &[Color::BLACK; 4]);
+        }
+        assert!(row(&renderer, 8).iter().all(|c| *c == Color::BLACK));
+
+        // a round cap reaches past the endpoint
+        let style = LineStyle {
+            width: 4.0,
+            cap: LineCap::Round,
+            anti_aliased: false,
+            ..Default::default()
+        };
+        renderer.draw_line_styled(
+            &Vec2::new(4.0, 14.0),
+            &Vec2::new(8.0, 14.0),
+            &style,
+            Color::GREEN,
+        );
+        assert_eq!(renderer.pixels()[14 * 16 + 2], Color::GREEN);
+        assert_eq!(renderer.pixels()[14 * 16 + 9], Color::GREEN);
+    }
+}
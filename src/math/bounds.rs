@@ -0,0 +1,208 @@
+use crate::math::{Mat4x1, Mat4x4, Vec3};
+
+/// Axis aligned bounding box. The default box is empty, it contains no point and is the
+/// identity of [`Aabb::union`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Default for Aabb {
+    fn default() -> Self {
+        Aabb::EMPTY
+    }
+}
+
+impl Aabb {
+    pub const EMPTY: Aabb = Aabb {
+        min: Vec3::splat(f32::MAX),
+        max: Vec3::splat(-f32::MAX),
+    };
+
+    pub const fn new(min: Vec3, max: Vec3) -> Self {
+        Aabb { min, max }
+    }
+
+    pub fn from_points<'a, I: IntoIterator<Item = &'a Vec3>>(points: I) -> Self {
+        points
+            .into_iter()
+            .fold(Aabb::EMPTY, |aabb, point| aabb.expanded(point))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    /// Half of the size along each axis.
+    pub fn extent(&self) -> Vec3 {
+        (self.max - self.min) * 0.5
+    }
+
+    pub fn size(&self) -> Vec3 {
+        self.max - self.min
+    }
+
+    pub fn expanded(&self, point: &Vec3) -> Self {
+        Aabb {
+            min: Vec3::new(
+                self.min.x.min(point.x),
+                self.min.y.min(point.y),
+                self.min.z.min(point.z),
+            ),
+            max: Vec3::new(
+                self.max.x.max(point.x),
+                self.max.y.max(point.y),
+                self.max.z.max(point.z),
+            ),
+        }
+    }
+
+    pub fn union(&self, other: &Aabb) -> Self {
+        self.expanded(&other.min).expanded(&other.max)
+    }
+
+    pub fn contains_point(&self, point: &Vec3) -> bool {
+        (self.min.x..=self.max.x).contains(&point.x)
+            && (self.min.y..=self.max.y).contains(&point.y)
+            && (self.min.z..=self.max.z).contains(&point.z)
+    }
+
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        self.min.x <= other.max.x
+            && self.max.x >= other.min.x
+            && self.min.y <= other.max.y
+            && self.max.y >= other.min.y
+            && self.min.z <= other.max.z
+            && self.max.z >= other.min.z
+    }
+
+    /// Smallest box containing this box after an affine transform.
+    pub fn transform(&self, m: &Mat4x4) -> Self {
+        if self.is_empty() {
+            return *self;
+        }
+        // Arvo's method, the extent along each new axis is the sum of the absolute projections
+        let center = m.mul_mat41(&Mat4x1::from(self.center())).to_vec4();
+        let extent = self.extent();
+        let mut new_extent = Vec3::ZERO;
+        for i in 0..3 {
+            new_extent[i] = m[(i, 0)].abs() * extent.x
+                + m[(i, 1)].abs() * extent.y
+                + m[(i, 2)].abs() * extent.z;
+        }
+        let center = Vec3::new(center.x, center.y, center.z);
+        Aabb::new(center - new_extent, center + new_extent)
+    }
+
+    /// Sphere through the corners of the box.
+    pub fn bounding_sphere(&self) -> BoundingSphere {
+        BoundingSphere::new(self.center(), self.extent().sqrt())
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct BoundingSphere {
+    pub center: Vec3,
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    pub const fn new(center: Vec3, radius: f32) -> Self {
+        BoundingSphere { center, radius }
+    }
+
+    /// Sphere centered on the bounding box of `points`, usually much tighter than
+    /// [`Aabb::bounding_sphere`].
+    pub fn from_points(points: &[Vec3]) -> Self {
+        let center = Aabb::from_points(points).center();
+        let radius = points
+            .iter()
+            .map(|p| (*p - center).sqrt())
+            .fold(0.0, f32::max);
+        BoundingSphere::new(center, radius)
+    }
+
+    pub fn contains_point(&self, point: &Vec3) -> bool {
+        (*point - self.center).sqrt() <= self.radius
+    }
+
+    pub fn union(&self, other: &BoundingSphere) -> Self {
+        let offset = other.center - self.center;
+        let distance = offset.sqrt();
+        if distance + other.radius <= self.radius {
+            return *self;
+        }
+        if distance + self.radius <= other.radius {
+            return *other;
+        }
+        let radius = (distance + self.radius + other.radius) / 2.0;
+        let center = self.center + offset * ((radius - self.radius) / distance);
+        BoundingSphere::new(center, radius)
+    }
+
+    /// Sphere containing this sphere after an affine transform, scaled by the largest scale of
+    /// the transform.
+    pub fn transform(&self, m: &Mat4x4) -> Self {
+        let center = m.mul_mat41(&Mat4x1::from(self.center)).to_vec4();
+        let scale = (0..3)
+            .map(|j| Vec3::new(m[(0, j)], m[(1, j)], m[(2, j)]).sqrt())
+            .fold(0.0, f32::max);
+        BoundingSphere::new(Vec3::new(center.x, center.y, center.z), self.radius * scale)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::vec3;
+
+    #[test]
+    fn test_aabb() {
+        let points = [vec3(1.0, -2.0, 0.0), vec3(-1.0, 2.0, 4.0)];
+        let aabb = Aabb::from_points(&points);
+        assert_eq!(aabb, Aabb::new(vec3(-1.0, -2.0, 0.0), vec3(1.0, 2.0, 4.0)));
+        assert_eq!(aabb.center(), vec3(0.0, 0.0, 2.0));
+        assert_eq!(aabb.extent(), vec3(1.0, 2.0, 2.0));
+        assert!(Aabb::EMPTY.is_empty());
+        assert_eq!(Aabb::EMPTY.union(&aabb), aabb);
+
+        // a quarter turn around z followed by a translation
+        let mut m = Mat4x4::identity().translate(10.0, 0.0, 0.0);
+        m[(0, 0)] = 0.0;
+        m[(0, 1)] = -1.0;
+        m[(1, 0)] = 1.0;
+        m[(1, 1)] = 0.0;
+        let transformed = aabb.transform(&m);
+        assert_eq!(
+            transformed,
+            Aabb::new(vec3(8.0, -1.0, 0.0), vec3(12.0, 1.0, 4.0))
+        );
+        assert!(transformed.contains_point(&vec3(12.0, 0.0, 4.0)));
+        assert!(!transformed.intersects(&aabb));
+    }
+
+    #[test]
+    fn test_bounding_sphere() {
+        let a = BoundingSphere::new(vec3(0.0, 0.0, 0.0), 1.0);
+        let b = BoundingSphere::new(vec3(4.0, 0.0, 0.0), 1.0);
+        let union = a.union(&b);
+        assert_eq!(union, BoundingSphere::new(vec3(2.0, 0.0, 0.0), 3.0));
+        assert_eq!(union.union(&a), union);
+
+        let scaled = a.transform(&Mat4x4::identity().scale(1.0, 3.0, 2.0));
+        assert_eq!(scaled.radius, 3.0);
+        let points = [
+            vec3(-1.0, 0.0, 0.0),
+            vec3(1.0, 0.0, 0.0),
+            vec3(0.0, 0.5, 0.0),
+        ];
+        let sphere = BoundingSphere::from_points(&points);
+        assert!(points.iter().all(|p| sphere.contains_point(p)));
+        assert!(sphere.radius < Aabb::from_points(&points).bounding_sphere().radius + 1e-6);
+    }
+}
@@ -0,0 +1,114 @@
+use crate::math::{Aabb, BoundingSphere, Mat4x4, Vec3, Vec4};
+
+/// The six planes bounding the volume that a projection maps into clip space, with normals
+/// pointing inwards. A plane (a, b, c, d) contains the points where `a x + b y + c z + d = 0`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Frustum {
+    /// Left, right, bottom, top, near and far.
+    pub planes: [Vec4; 6],
+}
+
+impl Frustum {
+    /// Extracts the planes from a view-projection matrix with the Gribb-Hartmann method, for
+    /// clip volumes where x, y and z lie in [-w, w]. The planes are in the space the matrix
+    /// transforms from, so passing a model-view-projection matrix gives planes in model space.
+    pub fn from_matrix(m: &Mat4x4) -> Self {
+        let row = |i: usize| Vec4::new(m[(i, 0)], m[(i, 1)], m[(i, 2)], m[(i, 3)]);
+        let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+        let planes = [w + x, w - x, w + y, w - y, w + z, w - z].map(|plane| {
+            let length = Vec3::new(plane.x, plane.y, plane.z).sqrt();
+            if length > 0.0 {
+                plane / length
+            } else {
+                plane
+            }
+        });
+        Frustum { planes }
+    }
+
+    fn distance(plane: &Vec4, point: &Vec3) -> f32 {
+        plane.x * point.x + plane.y * point.y + plane.z * point.z + plane.w
+    }
+
+    pub fn contains_point(&self, point: &Vec3) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| Frustum::distance(plane, point) >= 0.0)
+    }
+
+    /// False when the sphere lies entirely outside of one of the planes. Spheres near the
+    /// corners of the frustum may be reported as intersecting although they are outside.
+    pub fn intersects_sphere(&self, sphere: &BoundingSphere) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| Frustum::distance(plane, &sphere.center) >= -sphere.radius)
+    }
+
+    /// False when the box lies entirely outside of one of the planes, with the same
+    /// conservative corners as [`Frustum::intersects_sphere`].
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        if aabb.is_empty() {
+            return false;
+        }
+        self.planes.iter().all(|plane| {
+            // the corner furthest along the plane normal
+            let corner = Vec3::new(
+                if plane.x >= 0.0 {
+                    aabb.max.x
+                } else {
+                    aabb.min.x
+                },
+                if plane.y >= 0.0 {
+                    aabb.max.y
+                } else {
+                    aabb.min.y
+                },
+                if plane.z >= 0.0 {
+                    aabb.max.z
+                } else {
+                    aabb.min.z
+                },
+            );
+            Frustum::distance(plane, &corner) >= 0.0
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::vec3;
+
+    #[test]
+    fn test_frustum_culling() {
+        // an orthographic box from -1 to 1 on every axis, seen through a translation
+        let frustum = Frustum::from_matrix(&Mat4x4::identity().translate(-5.0, 0.0, 0.0));
+        assert!(frustum.contains_point(&vec3(5.0, 0.0, 0.0)));
+        assert!(!frustum.contains_point(&vec3(0.0, 0.0, 0.0)));
+
+        let inside = Aabb::new(vec3(4.5, -0.5, -0.5), vec3(5.5, 0.5, 0.5));
+        let straddling = Aabb::new(vec3(5.5, 0.0, 0.0), vec3(7.0, 0.5, 0.5));
+        let outside = Aabb::new(vec3(6.5, 0.0, 0.0), vec3(7.0, 0.5, 0.5));
+        assert!(frustum.intersects_aabb(&inside));
+        assert!(frustum.intersects_aabb(&straddling));
+        assert!(!frustum.intersects_aabb(&outside));
+        assert!(!frustum.intersects_aabb(&Aabb::EMPTY));
+
+        assert!(frustum.intersects_sphere(&BoundingSphere::new(vec3(6.5, 0.0, 0.0), 0.6)));
+        assert!(!frustum.intersects_sphere(&BoundingSphere::new(vec3(6.5, 0.0, 0.0), 0.4)));
+    }
+
+    #[test]
+    fn test_perspective_planes() {
+        // w = -z, a camera at the origin looking down -z with a 90 degree field of view
+        let mut m = Mat4x4::identity();
+        m[(3, 2)] = -1.0;
+        m[(3, 3)] = 0.0;
+        let frustum = Frustum::from_matrix(&m);
+        assert!(frustum.contains_point(&vec3(0.0, 0.0, -0.5)));
+        assert!(frustum.contains_point(&vec3(0.9, -0.9, -1.0)));
+        assert!(!frustum.contains_point(&vec3(1.1, 0.0, -1.0)));
+        // behind the camera
+        assert!(!frustum.contains_point(&vec3(0.0, 0.0, 1.0)));
+    }
+}
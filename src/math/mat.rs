@@ -1,4 +1,4 @@
-use crate::math::{Vec3, Vec4};
+use crate::math::{Quat, Vec3, Vec4};
 use bytemuck::{Pod, Zeroable};
 use std::fmt::Display;
 use std::ops::{Deref, DerefMut, Index, IndexMut};
@@ -89,6 +89,55 @@ impl Mat4x4 {
         res
     }
 
+    /// Scales, then rotates, then translates.
+    pub fn from_scale_rotation_translation(
+        scale: &Vec3,
+        rotation: &Quat,
+        translation: &Vec3,
+    ) -> Self {
+        let mut res = rotation.to_mat4();
+        for i in 0..3 {
+            res[(i, 0)] *= scale.x;
+            res[(i, 1)] *= scale.y;
+            res[(i, 2)] *= scale.z;
+        }
+        res[(0, 3)] = translation.x;
+        res[(1, 3)] = translation.y;
+        res[(2, 3)] = translation.z;
+        res
+    }
+
+    /// Gauss-Jordan elimination with partial pivoting, `None` for singular matrices.
+    pub fn inverse(&self) -> Option<Self> {
+        let mut a = *self;
+        let mut res = Self::identity();
+        for col in 0..4 {
+            let pivot = (col..4)
+                .max_by(|&i, &j| a[(i, col)].abs().total_cmp(&a[(j, col)].abs()))
+                .unwrap();
+            if a[(pivot, col)].abs() <= f32::EPSILON * 1e-3 {
+                return None;
+            }
+            for j in 0..4 {
+                a.m.swap(col * 4 + j, pivot * 4 + j);
+                res.m.swap(col * 4 + j, pivot * 4 + j);
+            }
+            let factor = 1.0 / a[(col, col)];
+            for j in 0..4 {
+                a[(col, j)] *= factor;
+                res[(col, j)] *= factor;
+            }
+            for i in (0..4).filter(|&i| i != col) {
+                let factor = a[(i, col)];
+                for j in 0..4 {
+                    a[(i, j)] -= factor * a[(col, j)];
+                    res[(i, j)] -= factor * res[(col, j)];
+                }
+            }
+        }
+        Some(res)
+    }
+
     pub const fn at(&self, i: usize, j: usize) -> f32 {
         let index = (i * self.num_cols) + j;
         self.m[index]
@@ -441,6 +490,27 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_mat4x4_inverse() {
+        let rotation = Quat::from_euler(0.3, 1.1, -0.7);
+        let m = Mat4x4::from_scale_rotation_translation(
+            &Vec3::new(2.0, 0.5, 3.0),
+            &rotation,
+            &Vec3::new(1.0, -4.0, 2.5),
+        );
+        let product = m.mul(&m.inverse().unwrap());
+        for i in 0..4 {
+            for j in 0..4 {
+                let expected = if i == j { 1.0 } else { 0.0 };
+                assert!((product[(i, j)] - expected).abs() < 1e-5);
+            }
+        }
+        let p = m.mul_mat41(&Mat4x1::from(Vec3::new(0.0, 0.0, 0.0)));
+        assert_eq!(p.to_vec3(), Vec3::new(1.0, -4.0, 2.5));
+
+        assert_eq!(Mat4x4::identity().scale(1.0, 0.0, 1.0).inverse(), None);
+    }
+
     #[test]
     fn test_mat4x1() {
         let m = Mat4x1::from([1.0, 2.0, 3.0, 4.0]);
@@ -1,7 +1,13 @@
+pub mod bounds;
+pub mod frustum;
 pub mod mat;
+pub mod quat;
 pub mod vec;
 
+pub use bounds::*;
+pub use frustum::*;
 pub use mat::*;
+pub use quat::*;
 pub use vec::*;
 
 pub type Vec2u = TVec2<u32>;
@@ -0,0 +1,211 @@
+use crate::math::{Mat4x4, Vec3};
+use std::ops::Mul;
+
+/// Rotation quaternion `w + xi + yj + zk`. Rotations are only meaningful for unit quaternions,
+/// the constructors return normalized values.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quat {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub w: f32,
+}
+
+impl Default for Quat {
+    fn default() -> Self {
+        Quat::IDENTITY
+    }
+}
+
+impl Quat {
+    pub const IDENTITY: Quat = Quat::new(0.0, 0.0, 0.0, 1.0);
+
+    pub const fn new(x: f32, y: f32, z: f32, w: f32) -> Self {
+        Quat { x, y, z, w }
+    }
+
+    /// Counter-clockwise rotation by `angle` radians around `axis`, looking down the axis
+    /// towards the origin.
+    pub fn from_axis_angle(axis: &Vec3, angle: f32) -> Self {
+        let axis = axis.normalize();
+        let (sin, cos) = (angle * 0.5).sin_cos();
+        Quat::new(axis.x * sin, axis.y * sin, axis.z * sin, cos)
+    }
+
+    /// Rotation around x by `pitch`, then around y by `yaw`, then around z by `roll`.
+    pub fn from_euler(pitch: f32, yaw: f32, roll: f32) -> Self {
+        Quat::from_axis_angle(&Vec3::new(0.0, 0.0, 1.0), roll)
+            * Quat::from_axis_angle(&Vec3::new(0.0, 1.0, 0.0), yaw)
+            * Quat::from_axis_angle(&Vec3::new(1.0, 0.0, 0.0), pitch)
+    }
+
+    /// Shortest rotation turning the direction `from` into the direction `to`.
+    pub fn from_rotation_arc(from: &Vec3, to: &Vec3) -> Self {
+        let (from, to) = (from.normalize(), to.normalize());
+        let cos = from.dot(&to);
+        if cos < -1.0 + 1e-6 {
+            // opposite directions, any perpendicular axis works
+            let axis = if from.x.abs() < 0.9 {
+                from.cross(&Vec3::new(1.0, 0.0, 0.0))
+            } else {
+                from.cross(&Vec3::new(0.0, 1.0, 0.0))
+            };
+            return Quat::from_axis_angle(&axis, std::f32::consts::PI);
+        }
+        let axis = from.cross(&to);
+        Quat::new(axis.x, axis.y, axis.z, 1.0 + cos).normalize()
+    }
+
+    pub fn dot(&self, rhs: &Quat) -> f32 {
+        self.x * rhs.x + self.y * rhs.y + self.z * rhs.z + self.w * rhs.w
+    }
+
+    pub fn length(&self) -> f32 {
+        self.dot(self).sqrt()
+    }
+
+    pub fn normalize(&self) -> Quat {
+        let length = self.length();
+        Quat::new(
+            self.x / length,
+            self.y / length,
+            self.z / length,
+            self.w / length,
+        )
+    }
+
+    /// The inverse rotation of a unit quaternion.
+    pub fn conjugate(&self) -> Quat {
+        Quat::new(-self.x, -self.y, -self.z, self.w)
+    }
+
+    pub fn rotate(&self, v: &Vec3) -> Vec3 {
+        let u = Vec3::new(self.x, self.y, self.z);
+        let t = u.cross(v) * 2.0;
+        *v + t * self.w + u.cross(&t)
+    }
+
+    /// Normalized linear interpolation, cheaper than [`Quat::slerp`] but not at constant speed.
+    pub fn nlerp(&self, other: &Quat, t: f32) -> Quat {
+        let sign = if self.dot(other) < 0.0 { -1.0 } else { 1.0 };
+        Quat::new(
+            self.x + (other.x * sign - self.x) * t,
+            self.y + (other.y * sign - self.y) * t,
+            self.z + (other.z * sign - self.z) * t,
+            self.w + (other.w * sign - self.w) * t,
+        )
+        .normalize()
+    }
+
+    /// Spherical linear interpolation along the shortest arc.
+    pub fn slerp(&self, other: &Quat, t: f32) -> Quat {
+        let mut cos = self.dot(other);
+        let mut other = *other;
+        if cos < 0.0 {
+            cos = -cos;
+            other = Quat::new(-other.x, -other.y, -other.z, -other.w);
+        }
+        if cos > 0.9995 {
+            return self.nlerp(&other, t);
+        }
+        let angle = cos.acos();
+        let sin = angle.sin();
+        let a = ((1.0 - t) * angle).sin() / sin;
+        let b = (t * angle).sin() / sin;
+        Quat::new(
+            self.x * a + other.x * b,
+            self.y * a + other.y * b,
+            self.z * a + other.z * b,
+            self.w * a + other.w * b,
+        )
+    }
+
+    pub fn to_mat4(&self) -> Mat4x4 {
+        let Quat { x, y, z, w } = *self;
+        Mat4x4::from([
+            [
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y - w * z),
+                2.0 * (x * z + w * y),
+                0.0,
+            ],
+            [
+                2.0 * (x * y + w * z),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z - w * x),
+                0.0,
+            ],
+            [
+                2.0 * (x * z - w * y),
+                2.0 * (y * z + w * x),
+                1.0 - 2.0 * (x * x + y * y),
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+}
+
+/// Hamilton product, `a * b` rotates by `b` first and then by `a`.
+impl Mul for Quat {
+    type Output = Quat;
+
+    fn mul(self, rhs: Quat) -> Quat {
+        Quat::new(
+            self.w * rhs.x + self.x * rhs.w + self.y * rhs.z - self.z * rhs.y,
+            self.w * rhs.y - self.x * rhs.z + self.y * rhs.w + self.z * rhs.x,
+            self.w * rhs.z + self.x * rhs.y - self.y * rhs.x + self.z * rhs.w,
+            self.w * rhs.w - self.x * rhs.x - self.y * rhs.y - self.z * rhs.z,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::{vec3, Mat4x1};
+    use std::f32::consts::FRAC_PI_2;
+
+    fn assert_near(a: Vec3, b: Vec3) {
+        assert!((a - b).sqrt() < 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn test_quat_rotation() {
+        let q = Quat::from_axis_angle(&vec3(0.0, 0.0, 1.0), FRAC_PI_2);
+        assert_near(q.rotate(&vec3(1.0, 0.0, 0.0)), vec3(0.0, 1.0, 0.0));
+        assert_near(
+            q.conjugate().rotate(&vec3(0.0, 1.0, 0.0)),
+            vec3(1.0, 0.0, 0.0),
+        );
+
+        // the matrix agrees with the quaternion
+        let v = vec3(0.3, -2.0, 1.5);
+        let q = Quat::from_euler(0.4, -1.2, 2.0);
+        let m = q.to_mat4().mul_mat41(&Mat4x1::from(v)).to_vec3();
+        assert_near(m, q.rotate(&v));
+
+        // products apply the right hand side first
+        let x = Quat::from_axis_angle(&vec3(1.0, 0.0, 0.0), FRAC_PI_2);
+        let y = Quat::from_axis_angle(&vec3(0.0, 1.0, 0.0), FRAC_PI_2);
+        assert_near((y * x).rotate(&v), y.rotate(&x.rotate(&v)));
+
+        let arc = Quat::from_rotation_arc(&vec3(0.0, 0.0, 2.0), &vec3(0.0, 3.0, 0.0));
+        assert_near(arc.rotate(&vec3(0.0, 0.0, 1.0)), vec3(0.0, 1.0, 0.0));
+        let flip = Quat::from_rotation_arc(&vec3(1.0, 0.0, 0.0), &vec3(-1.0, 0.0, 0.0));
+        assert_near(flip.rotate(&vec3(1.0, 0.0, 0.0)), vec3(-1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_quat_slerp() {
+        let a = Quat::IDENTITY;
+        let b = Quat::from_axis_angle(&vec3(0.0, 1.0, 0.0), FRAC_PI_2);
+        let half = a.slerp(&b, 0.5);
+        let expected = Quat::from_axis_angle(&vec3(0.0, 1.0, 0.0), FRAC_PI_2 / 2.0);
+        assert!((half.dot(&expected) - 1.0).abs() < 1e-6);
+        assert_eq!(a.slerp(&b, 0.0), a);
+        // -b is the same rotation, the interpolation takes the short way around
+        let negated = Quat::new(-b.x, -b.y, -b.z, -b.w);
+        assert!((a.slerp(&negated, 0.5).dot(&expected).abs() - 1.0).abs() < 1e-6);
+    }
+}
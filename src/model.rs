@@ -1,10 +1,11 @@
-use crate::math::{vec2, vec3, Vec2f, Vec3f};
+use crate::math::{vec2, vec3, Aabb, Vec2f, Vec3f};
 use crate::texture::Texture;
 use anyhow::Result;
 use bytemuck::Zeroable;
 use std::collections::HashMap;
 use std::fmt;
 use std::hash::{Hash, Hasher};
+use std::ops::Range;
 use std::path::Path;
 
 #[repr(C)]
@@ -12,11 +13,13 @@ use std::path::Path;
 pub struct Vertex {
     pub position: Vec3f,
     pub uv: Vec2f,
+    /// Unit length, pointing out of the front face.
+    pub normal: Vec3f,
 }
 
 impl PartialEq for Vertex {
     fn eq(&self, other: &Self) -> bool {
-        self.position == other.position && self.uv == other.uv
+        self.position == other.position && self.uv == other.uv && self.normal == other.normal
     }
 }
 
@@ -29,6 +32,23 @@ impl Hash for Vertex {
         self.position[2].to_bits().hash(state);
         self.uv[0].to_bits().hash(state);
         self.uv[1].to_bits().hash(state);
+        self.normal[0].to_bits().hash(state);
+        self.normal[1].to_bits().hash(state);
+        self.normal[2].to_bits().hash(state);
+    }
+}
+
+/// Range of a model's index buffer drawn as one piece, such as one object of an OBJ file.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Submesh {
+    pub first_index: u32,
+    pub index_count: u32,
+    pub bounds: Aabb,
+}
+
+impl Submesh {
+    pub fn index_range(&self) -> Range<usize> {
+        self.first_index as usize..(self.first_index + self.index_count) as usize
     }
 }
 
@@ -36,6 +56,9 @@ pub struct Model {
     pub vertices: Vec<Vertex>,
     pub indices: Vec<u32>,
     pub diffuse: Texture,
+    /// Bounds of the vertices referenced by the submeshes, see [`Model::compute_bounds`].
+    pub bounds: Aabb,
+    pub submeshes: Vec<Submesh>,
 }
 
 impl Model {
@@ -56,12 +79,28 @@ impl Model {
             vertices: vec![],
             indices: vec![],
             diffuse,
+            bounds: Aabb::EMPTY,
+            submeshes: vec![],
         };
         let mut unique_vertices = HashMap::new();
         for model in &loaded_models {
+            data.submeshes.push(Submesh {
+                first_index: data.indices.len() as u32,
+                index_count: model.mesh.indices.len() as u32,
+                bounds: Aabb::EMPTY,
+            });
             for index in &model.mesh.indices {
                 let pos_offset = (3 * index) as usize;
                 let tex_coord_offset = (2 * index) as usize;
+                let normal = if model.mesh.normals.is_empty() {
+                    Vec3f::ZERO
+                } else {
+                    vec3(
+                        model.mesh.normals[pos_offset],
+                        model.mesh.normals[pos_offset + 1],
+                        model.mesh.normals[pos_offset + 2],
+                    )
+                };
 
                 let vertex = Vertex {
                     position: vec3(
@@ -74,6 +113,7 @@ impl Model {
                         model.mesh.texcoords[tex_coord_offset],
                         1.0 - model.mesh.texcoords[tex_coord_offset + 1],
                     ),
+                    normal,
                 };
                 // Vertex deduplication
                 if let Some(index) = unique_vertices.get(&vertex) {
@@ -86,6 +126,64 @@ impl Model {
                 }
             }
         }
+        if loaded_models
+            .iter()
+            .any(|model| model.mesh.normals.is_empty())
+        {
+            data.compute_normals();
+        }
+        data.compute_bounds();
         Ok(data)
     }
+
+    /// Wraps vertices and a triangle list in a model with a single submesh.
+    pub fn new(vertices: Vec<Vertex>, indices: Vec<u32>, diffuse: Texture) -> Self {
+        let mut model = Model {
+            submeshes: vec![Submesh {
+                first_index: 0,
+                index_count: indices.len() as u32,
+                bounds: Aabb::EMPTY,
+            }],
+            vertices,
+            indices,
+            diffuse,
+            bounds: Aabb::EMPTY,
+        };
+        model.compute_bounds();
+        model
+    }
+
+    /// Recomputes the bounds of every submesh and of the whole model, needed after moving
+    /// vertices.
+    pub fn compute_bounds(&mut self) {
+        self.bounds = Aabb::EMPTY;
+        for submesh in &mut self.submeshes {
+            submesh.bounds = Aabb::from_points(
+                self.indices[submesh.index_range()]
+                    .iter()
+                    .map(|&i| &self.vertices[i as usize].position),
+            );
+            self.bounds = self.bounds.union(&submesh.bounds);
+        }
+    }
+
+    /// Replaces the vertex normals by the average of the normals of the faces around each
+    /// vertex, weighted by face area.
+    pub fn compute_normals(&mut self) {
+        let mut normals = vec![Vec3f::ZERO; self.vertices.len()];
+        for triangle in self.indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| self.vertices[triangle[i] as usize].position);
+            let normal = (b - a).cross(&(c - a));
+            for &index in triangle {
+                normals[index as usize] += normal;
+            }
+        }
+        for (vertex, normal) in self.vertices.iter_mut().zip(normals) {
+            vertex.normal = if normal.sqrt() > 0.0 {
+                normal.normalize()
+            } else {
+                normal
+            };
+        }
+    }
 }
@@ -0,0 +1,437 @@
+use crate::math::Vec3;
+use crate::model::Model;
+
+/// Number of vertices the vertex cache optimizer assumes a post-transform cache holds.
+pub const VERTEX_CACHE_SIZE: usize = 32;
+
+/// Efficiency of an index buffer with a FIFO post-transform cache.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct VertexCacheStats {
+    /// Vertex shader invocations, every cache miss transforms a vertex.
+    pub vertices_transformed: u32,
+    /// Average cache miss ratio, transformed vertices per triangle. 0.5 is the ideal for large
+    /// regular meshes, 3 means no vertex is ever reused.
+    pub acmr: f32,
+    /// Average transform to vertex ratio, transformed vertices per referenced vertex. 1 is ideal.
+    pub atvr: f32,
+}
+
+/// Simulates a FIFO cache of `cache_size` vertices over a triangle list.
+#[profiling::function]
+pub fn analyze_vertex_cache(
+    indices: &[u32],
+    vertex_count: usize,
+    cache_size: usize,
+) -> VertexCacheStats {
+    let mut cache = FifoCache::new(vertex_count, cache_size);
+    let mut referenced = vec![false; vertex_count];
+    let mut misses = 0;
+    for &index in indices {
+        referenced[index as usize] = true;
+        if !cache.access(index) {
+            misses += 1;
+        }
+    }
+    let triangles = indices.len() / 3;
+    let unique = referenced.iter().filter(|r| **r).count();
+    VertexCacheStats {
+        vertices_transformed: misses,
+        acmr: if triangles > 0 {
+            misses as f32 / triangles as f32
+        } else {
+            0.0
+        },
+        atvr: if unique > 0 {
+            misses as f32 / unique as f32
+        } else {
+            0.0
+        },
+    }
+}
+
+/// FIFO vertex cache keyed by the time each vertex entered it.
+struct FifoCache {
+    entered: Vec<Option<u32>>,
+    time: u32,
+    size: u32,
+}
+
+impl FifoCache {
+    fn new(vertex_count: usize, size: usize) -> Self {
+        FifoCache {
+            entered: vec![None; vertex_count],
+            time: 0,
+            size: size as u32,
+        }
+    }
+
+    /// Returns true on a hit, a miss pushes the vertex and evicts the oldest one.
+    fn access(&mut self, index: u32) -> bool {
+        let entered = &mut self.entered[index as usize];
+        match entered {
+            Some(t) if self.time - *t < self.size => true,
+            _ => {
+                *entered = Some(self.time);
+                self.time += 1;
+                false
+            }
+        }
+    }
+
+    fn clear(&mut self) {
+        // everything is older than the cache size
+        self.time += self.size;
+    }
+}
+
+// Scoring constants from Tom Forsyth, "Linear-Speed Vertex Cache Optimisation".
+const CACHE_DECAY_POWER: f32 = 1.5;
+const LAST_TRIANGLE_SCORE: f32 = 0.75;
+const VALENCE_BOOST_SCALE: f32 = 2.0;
+const VALENCE_BOOST_POWER: f32 = 0.5;
+
+fn vertex_score(cache_position: Option<usize>, remaining_triangles: u32) -> f32 {
+    if remaining_triangles == 0 {
+        return -1.0;
+    }
+    let cache_score = match cache_position {
+        None => 0.0,
+        // the vertices of the last triangle are used at once, whatever their order
+        Some(position) if position < 3 => LAST_TRIANGLE_SCORE,
+        Some(position) => {
+            let scale = 1.0 / (VERTEX_CACHE_SIZE - 3) as f32;
+            (1.0 - (position - 3) as f32 * scale).powf(CACHE_DECAY_POWER)
+        }
+    };
+    // favor vertices with few triangles left, so that they can leave the cache for good
+    let valence_boost =
+        VALENCE_BOOST_SCALE * (remaining_triangles as f32).powf(-VALENCE_BOOST_POWER);
+    cache_score + valence_boost
+}
+
+/// Reorders the triangles of a triangle list so that consecutive triangles share vertices,
+/// using Forsyth's greedy algorithm over a simulated LRU cache. Winding is preserved.
+#[profiling::function]
+pub fn optimize_vertex_cache(indices: &[u32], vertex_count: usize) -> Vec<u32> {
+    let triangle_count = indices.len() / 3;
+    if triangle_count == 0 {
+        return vec![];
+    }
+
+    // triangles adjacent to every vertex, in compressed rows
+    let mut remaining = vec![0u32; vertex_count];
+    for &index in &indices[..triangle_count * 3] {
+        remaining[index as usize] += 1;
+    }
+    let mut offsets = vec![0usize; vertex_count + 1];
+    for v in 0..vertex_count {
+        offsets[v + 1] = offsets[v] + remaining[v] as usize;
+    }
+    let mut adjacency = vec![0u32; offsets[vertex_count]];
+    let mut fill = offsets.clone();
+    for (triangle, corners) in indices.chunks_exact(3).enumerate() {
+        for &index in corners {
+            adjacency[fill[index as usize]] = triangle as u32;
+            fill[index as usize] += 1;
+        }
+    }
+
+    let mut vertex_scores: Vec<f32> = remaining.iter().map(|&r| vertex_score(None, r)).collect();
+    let triangle_score = |scores: &[f32], triangle: usize| -> f32 {
+        indices[triangle * 3..triangle * 3 + 3]
+            .iter()
+            .map(|&i| scores[i as usize])
+            .sum()
+    };
+    let mut emitted = vec![false; triangle_count];
+
+    let mut cache: Vec<u32> = Vec::with_capacity(VERTEX_CACHE_SIZE + 3);
+    let mut result = Vec::with_capacity(triangle_count * 3);
+    let mut best = None;
+    let mut scan_cursor = 0;
+    while result.len() < triangle_count * 3 {
+        let triangle = match best {
+            Some(triangle) => triangle,
+            None => {
+                // nothing in the cache has triangles left, restart from the first unused one
+                while emitted[scan_cursor] {
+                    scan_cursor += 1;
+                }
+                scan_cursor
+            }
+        };
+        emitted[triangle] = true;
+        let corners = &indices[triangle * 3..triangle * 3 + 3];
+        result.extend_from_slice(corners);
+
+        for &index in corners {
+            let v = index as usize;
+            remaining[v] -= 1;
+            // move the emitted triangle past the live part of the adjacency row
+            let row = &mut adjacency[offsets[v]..offsets[v] + remaining[v] as usize + 1];
+            if let Some(slot) = row.iter().position(|&t| t as usize == triangle) {
+                row.swap(slot, remaining[v] as usize);
+            }
+        }
+
+        // the triangle's vertices move to the front of the LRU cache
+        let mut new_cache: Vec<u32> = corners.to_vec();
+        new_cache.extend(cache.iter().filter(|i| !corners.contains(i)));
+        for &evicted in new_cache.iter().skip(VERTEX_CACHE_SIZE) {
+            vertex_scores[evicted as usize] = vertex_score(None, remaining[evicted as usize]);
+        }
+        new_cache.truncate(VERTEX_CACHE_SIZE);
+        for (position, &index) in new_cache.iter().enumerate() {
+            vertex_scores[index as usize] = vertex_score(Some(position), remaining[index as usize]);
+        }
+        cache = new_cache;
+
+        // only triangles around cached vertices changed score
+        best = None;
+        let mut best_score = -1.0;
+        for &index in &cache {
+            let v = index as usize;
+            for &t in &adjacency[offsets[v]..offsets[v] + remaining[v] as usize] {
+                let t = t as usize;
+                let score = triangle_score(&vertex_scores, t);
+                if score > best_score {
+                    best_score = score;
+                    best = Some(t);
+                }
+            }
+        }
+    }
+    result
+}
+
+/// Groups a vertex cache optimized triangle list into clusters and sorts them so that clusters
+/// facing away from the mesh center come first, which lets the depth test reject more of the
+/// triangles drawn after them. Clusters are split wherever the ACMR of a cluster drawn on its
+/// own stays within `threshold` times the ACMR of `indices`, 1.05 is a good default.
+#[profiling::function]
+pub fn optimize_overdraw(
+    indices: &[u32],
+    positions: &[Vec3],
+    cache_size: usize,
+    threshold: f32,
+) -> Vec<u32> {
+    let triangle_count = indices.len() / 3;
+    if triangle_count == 0 {
+        return vec![];
+    }
+    let target_acmr = analyze_vertex_cache(indices, positions.len(), cache_size).acmr * threshold;
+
+    // a triangle missing all three vertices starts over with a cold cache, the order before it
+    // does not matter for the cache
+    let mut fifo = FifoCache::new(positions.len(), cache_size);
+    let mut cluster_starts = vec![0];
+    let mut cluster_misses = 0;
+    let mut cluster_start = 0;
+    for (triangle, corners) in indices.chunks_exact(3).enumerate() {
+        let misses = corners.iter().filter(|&&i| !fifo.access(i)).count();
+        let hard_boundary = misses == 3;
+        let cluster_length = triangle - cluster_start;
+        let soft_boundary =
+            cluster_length > 0 && cluster_misses as f32 / cluster_length as f32 <= target_acmr;
+        if triangle > 0 && (hard_boundary || soft_boundary) {
+            cluster_starts.push(triangle);
+            cluster_start = triangle;
+            cluster_misses = 0;
+            if !hard_boundary {
+                // the next cluster may be drawn after any other one
+                fifo.clear();
+                cluster_misses = corners.iter().filter(|&&i| !fifo.access(i)).count();
+                continue;
+            }
+        }
+        cluster_misses += misses;
+    }
+    cluster_starts.push(triangle_count);
+
+    let triangle = |t: usize| {
+        [
+            positions[indices[t * 3] as usize],
+            positions[indices[t * 3 + 1] as usize],
+            positions[indices[t * 3 + 2] as usize],
+        ]
+    };
+    let mesh_center = (0..triangle_count)
+        .map(|t| {
+            let [a, b, c] = triangle(t);
+            (a + b + c) / 3.0
+        })
+        .fold(Vec3::ZERO, |sum, center| sum + center)
+        / triangle_count as f32;
+
+    let mut clusters: Vec<(f32, usize, usize)> = cluster_starts
+        .windows(2)
+        .map(|range| {
+            // area weighted centroid and normal of the cluster
+            let mut center = Vec3::ZERO;
+            let mut normal = Vec3::ZERO;
+            let mut area = 0.0;
+            for t in range[0]..range[1] {
+                let [a, b, c] = triangle(t);
+                let n = (b - a).cross(&(c - a));
+                let triangle_area = n.sqrt();
+                center += (a + b + c) * (triangle_area / 3.0);
+                normal += n;
+                area += triangle_area;
+            }
+            let center = if area > 0.0 { center / area } else { center };
+            let length = normal.sqrt();
+            let normal = if length > 0.0 {
+                normal / length
+            } else {
+                normal
+            };
+            let facing = (center - mesh_center).dot(&normal);
+            (facing, range[0], range[1])
+        })
+        .collect();
+    clusters.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    clusters
+        .iter()
+        .flat_map(|&(_, start, end)| indices[start * 3..end * 3].iter().copied())
+        .collect()
+}
+
+/// Reorders `vertices` by first use in `indices` and rewrites the indices to match, so that
+/// vertex fetches walk memory linearly. Vertices that no index refers to are dropped.
+#[profiling::function]
+pub fn optimize_vertex_fetch<T: Copy>(vertices: &[T], indices: &mut [u32]) -> Vec<T> {
+    let mut remap = vec![u32::MAX; vertices.len()];
+    let mut reordered = Vec::with_capacity(vertices.len());
+    for index in indices.iter_mut() {
+        let new_index = &mut remap[*index as usize];
+        if *new_index == u32::MAX {
+            *new_index = reordered.len() as u32;
+            reordered.push(vertices[*index as usize]);
+        }
+        *index = *new_index;
+    }
+    reordered
+}
+
+impl Model {
+    /// See [`optimize_vertex_cache`], triangles stay within their submesh.
+    pub fn optimize_vertex_cache(&mut self) {
+        for submesh in &self.submeshes {
+            let range = submesh.index_range();
+            let optimized =
+                optimize_vertex_cache(&self.indices[range.clone()], self.vertices.len());
+            self.indices[range].copy_from_slice(&optimized);
+        }
+    }
+
+    /// See [`optimize_overdraw`], the index order should already be optimized for the cache.
+    /// Triangles stay within their submesh.
+    pub fn optimize_overdraw(&mut self, threshold: f32) {
+        let positions: Vec<Vec3> = self.vertices.iter().map(|v| v.position).collect();
+        for submesh in &self.submeshes {
+            let range = submesh.index_range();
+            let sorted = optimize_overdraw(
+                &self.indices[range.clone()],
+                &positions,
+                VERTEX_CACHE_SIZE,
+                threshold,
+            );
+            self.indices[range].copy_from_slice(&sorted);
+        }
+    }
+
+    /// See [`optimize_vertex_fetch`].
+    pub fn optimize_vertex_fetch(&mut self) {
+        self.vertices = optimize_vertex_fetch(&self.vertices, &mut self.indices);
+    }
+
+    /// Runs the vertex cache, overdraw and vertex fetch passes in that order.
+    pub fn optimize(&mut self) {
+        self.optimize_vertex_cache();
+        self.optimize_overdraw(1.05);
+        self.optimize_vertex_fetch();
+    }
+
+    pub fn vertex_cache_stats(&self, cache_size: usize) -> VertexCacheStats {
+        analyze_vertex_cache(&self.indices, self.vertices.len(), cache_size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::grid;
+
+    // grid of n x n unit quads, with triangles in a scrambled order
+    fn scrambled_grid(n: u32) -> (Vec<Vec3>, Vec<u32>) {
+        let (vertices, indices) = grid(n, |_, _| 0.0);
+        let positions = vertices.iter().map(|v| v.position * n as f32).collect();
+        let triangles: Vec<&[u32]> = indices.chunks_exact(3).collect();
+        // a fixed stride permutation, coprime with the triangle count
+        let count = triangles.len();
+        let indices = (0..count)
+            .flat_map(|t| triangles[(t * 7919) % count].iter().copied())
+            .collect();
+        (positions, indices)
+    }
+
+    fn sorted_triangles(indices: &[u32]) -> Vec<[u32; 3]> {
+        let mut triangles: Vec<[u32; 3]> = indices
+            .chunks_exact(3)
+            .map(|t| {
+                // rotate the smallest index first, keeping the winding
+                let min = (0..3).min_by_key(|&i| t[i]).unwrap();
+                [t[min], t[(min + 1) % 3], t[(min + 2) % 3]]
+            })
+            .collect();
+        triangles.sort();
+        triangles
+    }
+
+    #[test]
+    fn test_cache_stats() {
+        let stats = analyze_vertex_cache(&[0, 1, 2], 3, 16);
+        assert_eq!(stats.acmr, 3.0);
+        assert_eq!(stats.atvr, 1.0);
+        // two triangles sharing an edge
+        let stats = analyze_vertex_cache(&[0, 1, 2, 2, 1, 3], 4, 16);
+        assert_eq!(stats.vertices_transformed, 4);
+        assert_eq!(stats.acmr, 2.0);
+        // a cache too small to hold the shared edge
+        let stats = analyze_vertex_cache(&[0, 1, 2, 3, 4, 5, 0, 1, 2], 6, 3);
+        assert_eq!(stats.atvr, 1.5);
+    }
+
+    #[test]
+    fn test_vertex_cache_optimization() {
+        let (positions, indices) = scrambled_grid(32);
+        let before = analyze_vertex_cache(&indices, positions.len(), 16);
+        let optimized = optimize_vertex_cache(&indices, positions.len());
+        let after = analyze_vertex_cache(&optimized, positions.len(), 16);
+        assert_eq!(sorted_triangles(&indices), sorted_triangles(&optimized));
+        assert!(before.acmr > 2.0, "{:?}", before);
+        assert!(after.acmr < 0.8, "{:?}", after);
+    }
+
+    #[test]
+    fn test_overdraw_and_fetch_keep_triangles() {
+        let (positions, indices) = scrambled_grid(16);
+        let cached = optimize_vertex_cache(&indices, positions.len());
+        let sorted = optimize_overdraw(&cached, &positions, VERTEX_CACHE_SIZE, 1.05);
+        assert_eq!(sorted_triangles(&cached), sorted_triangles(&sorted));
+
+        let mut remapped = sorted.clone();
+        let vertices = optimize_vertex_fetch(&positions, &mut remapped);
+        assert_eq!(vertices.len(), positions.len());
+        // indices first appear in increasing order
+        let mut next = 0;
+        for &index in &remapped {
+            assert!(index <= next);
+            next = next.max(index + 1);
+        }
+        for (a, b) in sorted.iter().zip(&remapped) {
+            assert_eq!(positions[*a as usize], vertices[*b as usize]);
+        }
+    }
+}
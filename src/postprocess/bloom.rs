@@ -0,0 +1,152 @@
+use crate::color::LinearColor;
+use crate::postprocess::{gaussian_blur, sample_bilinear, FrameBuffers, PostEffect, PostStage};
+
+/// Glow around bright areas: the parts brighter than a threshold are downsampled, blurred and
+/// added back on top of the frame.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bloom {
+    /// Brightness of the brightest channel where the glow starts.
+    pub threshold: f32,
+    /// Width of the soft transition around the threshold.
+    pub knee: f32,
+    /// Scale of the glow added back.
+    pub intensity: f32,
+    /// Factor the bright areas are downsampled by before blurring, larger is cheaper.
+    pub downsample: u32,
+    /// Standard deviation of the blur in downsampled pixels.
+    pub radius: f32,
+}
+
+impl Default for Bloom {
+    fn default() -> Self {
+        Bloom {
+            threshold: 0.8,
+            knee: 0.4,
+            intensity: 0.6,
+            downsample: 2,
+            radius: 3.0,
+        }
+    }
+}
+
+impl Bloom {
+    /// The part of `color` above the threshold, without alpha.
+    fn bright_pass(&self, color: &LinearColor) -> LinearColor {
+        let brightness = color.r.max(color.g).max(color.b);
+        let soft = (brightness - self.threshold + self.knee).clamp(0.0, 2.0 * self.knee);
+        let soft = soft * soft / (4.0 * self.knee + 1e-5);
+        let contribution = soft.max(brightness - self.threshold) / brightness.max(1e-5);
+        (*color * contribution).with_alpha(0.0)
+    }
+}
+
+impl PostEffect for Bloom {
+    #[profiling::function]
+    fn apply(&self, frame: &mut FrameBuffers) {
+        let factor = self.downsample.max(1);
+        let width = frame.width.div_ceil(factor);
+        let height = frame.height.div_ceil(factor);
+        let mut bright = vec![LinearColor::TRANSPARENT; (width * height) as usize];
+        for y in 0..frame.height {
+            for x in 0..frame.width {
+                let color = self.bright_pass(&frame.color[frame.index(x, y)]);
+                bright[((y / factor) * width + x / factor) as usize] += color;
+            }
+        }
+        let block = (factor * factor) as f32;
+        bright.iter_mut().for_each(|c| *c = *c / block);
+        gaussian_blur(&mut bright, width, height, self.radius);
+
+        let scale = 1.0 / factor as f32;
+        for y in 0..frame.height {
+            for x in 0..frame.width {
+                let glow = sample_bilinear(
+                    &bright,
+                    width,
+                    height,
+                    (x as f32 + 0.5) * scale,
+                    (y as f32 + 0.5) * scale,
+                );
+                let index = frame.index(x, y);
+                frame.color[index] += glow * self.intensity;
+            }
+        }
+    }
+
+    fn stage(&self) -> PostStage {
+        PostStage::Hdr
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bloom() {
+        let mut frame = FrameBuffers::new(32, 32);
+        let dim = LinearColor::rgb(0.3, 0.3, 0.3);
+        let spot = frame.index(2, 2);
+        frame.color[spot] = dim;
+        let light = frame.index(24, 24);
+        frame.color[light] = LinearColor::rgb(4.0, 2.0, 1.0);
+        let original = frame.clone();
+        Bloom::default().apply(&mut frame);
+
+        // the light glows onto its neighborhood, weaker with distance and in its own hue
+        let near = frame.color[frame.index(26, 24)];
+        let far = frame.color[frame.index(30, 24)];
+        assert!(near.r > far.r && far.r > 0.0);
+        assert!(near.r > near.g && near.g > near.b);
+        assert_eq!(near.a, 1.0);
+        // colors below the threshold neither glow nor lose brightness
+        assert_eq!(frame.color[spot], dim);
+        assert_eq!(frame.color[frame.index(6, 2)], LinearColor::BLACK);
+        assert!(frame.color[light].r > original.color[light].r);
+    }
+
+    #[test]
+    fn test_bloom_hdr_frame() {
+        use crate::color::Color;
+        use crate::math::{vec3, Mat4};
+        use crate::postprocess::PostStack;
+        use crate::rasterizer::PrimitiveTopology;
+        use crate::renderer::Renderer;
+        use crate::testing::TestShader;
+        use crate::tonemap::{ToneMapper, ToneMapping};
+
+        // a 4x4 pixel light in the middle of a black frame
+        let quad = [
+            vec3(-0.125, -0.125, 0.0),
+            vec3(0.125, -0.125, 0.0),
+            vec3(0.125, 0.125, 0.0),
+            vec3(-0.125, 0.125, 0.0),
+        ];
+        let bloom = PostStack::new().with(Bloom::default());
+        let render = |intensity: f32, stack: &PostStack| {
+            let mut renderer = Renderer::new(32, 32, false);
+            renderer.set_tone_mapping(Some(ToneMapping::new(0.0, ToneMapper::Reinhard)));
+            renderer.clear(Color::BLACK);
+            renderer.draw_indexed(
+                &TestShader::new(Mat4::identity())
+                    .with_color(LinearColor::rgb(intensity, intensity, intensity)),
+                &quad,
+                &[0, 1, 2, 0, 2, 3],
+                PrimitiveTopology::TriangleList,
+                0,
+            );
+            let center = renderer.frame_buffers().color[16 * 32 + 16];
+            renderer.post_process(stack);
+            (center, renderer.pixels()[16 * 32 + 22])
+        };
+
+        // the effects see the light before tone mapping compresses it into 0..1
+        let (center, unlit) = render(8.0, &PostStack::new());
+        assert_eq!(center, LinearColor::rgb(8.0, 8.0, 8.0));
+        assert_eq!(unlit, Color::BLACK);
+        // so an over-bright light glows further than a white one
+        let (_, hdr) = render(8.0, &bloom);
+        let (_, white) = render(1.0, &bloom);
+        assert!(hdr.r > white.r && white.r > 0, "{:?} {:?}", hdr, white);
+    }
+}
@@ -0,0 +1,61 @@
+use crate::color::LinearColor;
+use crate::postprocess::{FrameBuffers, PostEffect};
+
+/// Lens fringing, red is scaled away from the center of the frame and blue towards it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ChromaticAberration {
+    /// Offset of the red and blue channels at the corners, in pixels.
+    pub intensity: f32,
+}
+
+impl Default for ChromaticAberration {
+    fn default() -> Self {
+        ChromaticAberration { intensity: 2.0 }
+    }
+}
+
+impl PostEffect for ChromaticAberration {
+    fn apply(&self, frame: &mut FrameBuffers) {
+        let (cx, cy) = (frame.width as f32 * 0.5, frame.height as f32 * 0.5);
+        let scale = self.intensity / (cx * cx + cy * cy).sqrt();
+        let mut output = frame.color.clone();
+        for y in 0..frame.height {
+            for x in 0..frame.width {
+                let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+                let (dx, dy) = ((px - cx) * scale, (py - cy) * scale);
+                let red = frame.sample(px - dx, py - dy);
+                let blue = frame.sample(px + dx, py + dy);
+                let index = frame.index(x, y);
+                let color = frame.color[index];
+                output[index] = LinearColor::rgba(red.r, color.g, blue.b, color.a);
+            }
+        }
+        frame.color = output;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chromatic_aberration() {
+        // a white column right of the center
+        let mut frame = FrameBuffers::new(21, 21);
+        for y in 0..21 {
+            let index = frame.index(16, y);
+            frame.color[index] = LinearColor::WHITE;
+        }
+        let center = frame.index(10, 10);
+        frame.color[center] = LinearColor::WHITE;
+        ChromaticAberration { intensity: 4.0 }.apply(&mut frame);
+
+        assert_eq!(frame.color[center], LinearColor::WHITE);
+        // red spreads outwards and blue inwards, green stays in place
+        let outer = frame.color[frame.index(17, 10)];
+        let inner = frame.color[frame.index(15, 10)];
+        assert!(outer.r > 0.0 && outer.g == 0.0 && outer.b == 0.0);
+        assert!(inner.b > 0.0 && inner.g == 0.0 && inner.r == 0.0);
+        assert_eq!(frame.color[frame.index(16, 10)].g, 1.0);
+    }
+}
@@ -0,0 +1,231 @@
+use crate::camera::Camera;
+use crate::color::LinearColor;
+use crate::math::{Mat4, Mat4x1, Vec3};
+use crate::postprocess::{FrameBuffers, PostEffect, PostStage};
+
+/// How the fog thickens with the distance from the camera.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FogFalloff {
+    /// No fog before `start`, full fog from `end` on.
+    Linear { start: f32, end: f32 },
+    /// `1 - e^(-density * distance)`, like light scattering in a uniform medium.
+    Exponential { density: f32 },
+    /// `1 - e^(-(density * distance)^2)`, clearer close to the camera with a sharper falloff.
+    ExponentialSquared { density: f32 },
+}
+
+impl FogFalloff {
+    pub fn amount(&self, distance: f32) -> f32 {
+        match *self {
+            FogFalloff::Linear { start, end } => {
+                ((distance - start) / (end - start).max(f32::EPSILON)).clamp(0.0, 1.0)
+            }
+            FogFalloff::Exponential { density } => 1.0 - (-density * distance).exp(),
+            FogFalloff::ExponentialSquared { density } => {
+                1.0 - (-(density * distance).powi(2)).exp()
+            }
+        }
+    }
+}
+
+/// Fog settling in valleys, its density falls off exponentially above `height`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HeightFog {
+    /// World y where the fog has `density`.
+    pub height: f32,
+    /// Density at `height` per unit of distance.
+    pub density: f32,
+    /// How quickly the density decreases per unit of height.
+    pub falloff: f32,
+}
+
+impl HeightFog {
+    /// Fog accumulated along the straight ray from `from` to `to`.
+    pub fn amount(&self, from: &Vec3, to: &Vec3) -> f32 {
+        let distance = (*to - *from).sqrt();
+        let rise = self.falloff * (to.y - from.y);
+        // the density integrated along the ray, in closed form since it is exponential in y
+        let integral = if rise.abs() > 1e-4 {
+            (1.0 - (-rise).exp()) / rise
+        } else {
+            1.0
+        };
+        let start = self.density * (-self.falloff * (from.y - self.height)).exp();
+        1.0 - (-start * distance * integral).exp()
+    }
+}
+
+/// Distance and height fog over a rendered frame. The depth is reconstructed into world
+/// positions with the camera the frame was drawn with.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Fog {
+    pub camera: Camera,
+    pub color: LinearColor,
+    pub falloff: FogFalloff,
+    /// Added on top of the distance fog.
+    pub height: Option<HeightFog>,
+    /// Whether pixels where nothing was drawn are fogged as if infinitely far away.
+    pub background: bool,
+}
+
+impl Fog {
+    pub fn new(camera: Camera, falloff: FogFalloff, color: LinearColor) -> Self {
+        Fog {
+            camera,
+            color,
+            falloff,
+            height: None,
+            background: true,
+        }
+    }
+
+    /// Fog covering a world space point in 0..1, also usable to fog colors in a shader.
+    pub fn amount(&self, position: &Vec3) -> f32 {
+        let distance = (*position - self.camera.position).sqrt();
+        let clear = 1.0 - self.falloff.amount(distance);
+        let height_clear = self
+            .height
+            .map_or(1.0, |h| 1.0 - h.amount(&self.camera.position, position));
+        1.0 - clear * height_clear
+    }
+
+    /// Mixes a color with the fog at a world space point.
+    pub fn apply_to(&self, color: &LinearColor, position: &Vec3) -> LinearColor {
+        color.lerp(&self.color.with_alpha(color.a), self.amount(position))
+    }
+}
+
+impl PostEffect for Fog {
+    #[profiling::function]
+    fn apply(&self, frame: &mut FrameBuffers) {
+        let inverse = self
+            .camera
+            .view_projection_matrix()
+            .inverse()
+            .unwrap_or_else(Mat4::identity);
+        for y in 0..frame.height {
+            for x in 0..frame.width {
+                let index = frame.index(x, y);
+                let color = frame.color[index];
+                frame.color[index] = match frame.ndc(x, y) {
+                    Some(ndc) => {
+                        let position = inverse.mul_mat41(&Mat4x1::from(ndc)).to_vec3();
+                        self.apply_to(&color, &position)
+                    }
+                    None if self.background => self.color.with_alpha(color.a),
+                    None => color,
+                };
+            }
+        }
+    }
+
+    fn stage(&self) -> PostStage {
+        PostStage::Hdr
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::{Color, ColorSpace};
+    use crate::math::vec3;
+    use crate::rasterizer::PrimitiveTopology;
+    use crate::renderer::Renderer;
+
+    #[test]
+    fn test_fog_falloff() {
+        let linear = FogFalloff::Linear {
+            start: 10.0,
+            end: 30.0,
+        };
+        assert_eq!(linear.amount(5.0), 0.0);
+        assert_eq!(linear.amount(20.0), 0.5);
+        assert_eq!(linear.amount(40.0), 1.0);
+        let exp = FogFalloff::Exponential { density: 0.1 };
+        assert!((exp.amount(10.0) - (1.0 - (-1.0f32).exp())).abs() < 1e-6);
+        // the squared falloff starts clearer and ends thicker
+        let exp2 = FogFalloff::ExponentialSquared { density: 0.1 };
+        assert!(exp2.amount(5.0) < exp.amount(5.0));
+        assert!(exp2.amount(20.0) > exp.amount(20.0));
+
+        let height = HeightFog {
+            height: 0.0,
+            density: 0.2,
+            falloff: 1.0,
+        };
+        let eye = vec3(0.0, 1.0, 0.0);
+        // looking down into the fog is thicker than looking up out of it
+        let down = height.amount(&eye, &vec3(0.0, -2.0, 4.0));
+        let up = height.amount(&eye, &vec3(0.0, 4.0, 4.0));
+        assert!(down > up * 4.0);
+        // horizontal rays match a uniform medium of the density at their height
+        let level = height.amount(&eye, &vec3(0.0, 1.0, 5.0));
+        let uniform = FogFalloff::Exponential {
+            density: 0.2 * (-1.0f32).exp(),
+        };
+        assert!((level - uniform.amount(5.0)).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_fog_pass() {
+        let mut camera = Camera::new(1.0, 1.0, 0.1, 100.0);
+        camera.position = vec3(0.0, 0.0, 10.0);
+        // a near square on the left and a far one on the right
+        let square = |x: f32, z: f32| {
+            let (a, b, c, d) = (
+                vec3(x - 1.0, -1.0, z),
+                vec3(x + 1.0, -1.0, z),
+                vec3(x + 1.0, 1.0, z),
+                vec3(x - 1.0, 1.0, z),
+            );
+            [a, b, c, a, c, d]
+        };
+        let mut renderer = Renderer::new(32, 32, false);
+        renderer.set_color_space(ColorSpace::Linear);
+        renderer.clear(Color::BLACK);
+        let transform = camera.view_projection_matrix();
+        for quad in [square(-1.5, 5.0), square(4.0, -10.0)] {
+            renderer.draw_primitives(
+                &quad,
+                PrimitiveTopology::TriangleList,
+                &transform,
+                Color::WHITE,
+            );
+        }
+        let project = |p: Vec3| {
+            let ndc = transform.mul_mat41(&Mat4x1::from(p)).to_vec3();
+            let window = renderer.viewport().to_window(&ndc);
+            (window.x as usize, window.y as usize)
+        };
+        let (near, far) = (
+            project(vec3(-1.5, 0.0, 5.0)),
+            project(vec3(4.0, 0.0, -10.0)),
+        );
+
+        let gray = LinearColor::rgb(0.5, 0.5, 0.5);
+        let mut fog = Fog::new(
+            camera,
+            FogFalloff::Linear {
+                start: 0.0,
+                end: 40.0,
+            },
+            gray,
+        );
+        let mut frame = renderer.frame_buffers();
+        fog.apply(&mut frame);
+        let at = |(x, y): (usize, usize)| frame.color[y * 32 + x];
+        // about 5 and 20 units away, an eighth and half of the way to the fog
+        assert!(
+            (at(near).r - (1.0 - 0.5 * 5.0 / 40.0)).abs() < 0.01,
+            "{:?}",
+            at(near)
+        );
+        assert!((at(far).r - 0.75).abs() < 0.01, "{:?}", at(far));
+        assert_eq!(frame.color[0], gray);
+
+        fog.background = false;
+        let mut frame = renderer.frame_buffers();
+        fog.apply(&mut frame);
+        assert_eq!(frame.color[0], LinearColor::BLACK);
+    }
+}
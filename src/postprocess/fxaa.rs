@@ -0,0 +1,172 @@
+use crate::postprocess::{sample_bilinear, FrameBuffers, PostEffect};
+
+/// Step lengths while searching for the ends of an edge, growing like FXAA 3.11's quality
+/// presets.
+const SEARCH_STEPS: [f32; 12] = [1.0, 1.0, 1.0, 1.0, 1.0, 1.5, 2.0, 2.0, 2.0, 2.0, 4.0, 8.0];
+
+/// Timothy Lottes' fast approximate anti-aliasing, blends across edges found by their contrast
+/// in luma.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Fxaa {
+    /// Contrast below this fraction of the brightest neighbor isn't treated as an edge.
+    pub edge_threshold: f32,
+    /// Contrast below this absolute value is ignored, which keeps dark areas untouched.
+    pub edge_threshold_min: f32,
+    /// Strength of the sub-pixel aliasing removal in 0..1, higher is softer.
+    pub subpixel: f32,
+}
+
+impl Default for Fxaa {
+    fn default() -> Self {
+        Fxaa {
+            edge_threshold: 0.125,
+            edge_threshold_min: 0.0312,
+            subpixel: 0.75,
+        }
+    }
+}
+
+impl PostEffect for Fxaa {
+    #[profiling::function]
+    fn apply(&self, frame: &mut FrameBuffers) {
+        let (width, height) = (frame.width, frame.height);
+        // perceptual luma, close to the gamma encoded luma FXAA is tuned for
+        let luma: Vec<f32> = frame
+            .color
+            .iter()
+            .map(|c| c.saturate().luminance().sqrt())
+            .collect();
+        let at = |x: i32, y: i32| {
+            let x = x.clamp(0, width as i32 - 1) as u32;
+            let y = y.clamp(0, height as i32 - 1) as u32;
+            luma[(y * width + x) as usize]
+        };
+        let luma_at = |(x, y): (f32, f32)| sample_bilinear(&luma, width, height, x, y);
+
+        let mut output = frame.color.clone();
+        for y in 0..height as i32 {
+            for x in 0..width as i32 {
+                let center = at(x, y);
+                let (n, s, w, e) = (at(x, y - 1), at(x, y + 1), at(x - 1, y), at(x + 1, y));
+                let max = center.max(n).max(s).max(w).max(e);
+                let min = center.min(n).min(s).min(w).min(e);
+                let range = max - min;
+                if range < self.edge_threshold_min.max(max * self.edge_threshold) {
+                    continue;
+                }
+                let (nw, ne) = (at(x - 1, y - 1), at(x + 1, y - 1));
+                let (sw, se) = (at(x - 1, y + 1), at(x + 1, y + 1));
+                let horizontal_edge = (nw + sw - 2.0 * w).abs()
+                    + 2.0 * (n + s - 2.0 * center).abs()
+                    + (ne + se - 2.0 * e).abs();
+                let vertical_edge = (nw + ne - 2.0 * n).abs()
+                    + 2.0 * (w + e - 2.0 * center).abs()
+                    + (sw + se - 2.0 * s).abs();
+                let horizontal = horizontal_edge >= vertical_edge;
+
+                // step towards the neighbor across the edge with the steepest gradient
+                let (luma1, luma2) = if horizontal { (n, s) } else { (w, e) };
+                let (gradient1, gradient2) = (luma1 - center, luma2 - center);
+                let (step, side) = if gradient1.abs() >= gradient2.abs() {
+                    (-1.0, luma1)
+                } else {
+                    (1.0, luma2)
+                };
+                let gradient = gradient1.abs().max(gradient2.abs()) * 0.25;
+                let average = (side + center) * 0.5;
+
+                // search both ways along the edge, halfway between the pixel and that neighbor
+                let (cx, cy) = (x as f32 + 0.5, y as f32 + 0.5);
+                let (start, (dx, dy)) = if horizontal {
+                    ((cx, cy + step * 0.5), (1.0, 0.0))
+                } else {
+                    ((cx + step * 0.5, cy), (0.0, 1.0))
+                };
+                let mut p1 = (start.0 - dx, start.1 - dy);
+                let mut p2 = (start.0 + dx, start.1 + dy);
+                let mut end1 = luma_at(p1) - average;
+                let mut end2 = luma_at(p2) - average;
+                for length in SEARCH_STEPS {
+                    let done1 = end1.abs() >= gradient;
+                    let done2 = end2.abs() >= gradient;
+                    if done1 && done2 {
+                        break;
+                    }
+                    if !done1 {
+                        p1 = (p1.0 - dx * length, p1.1 - dy * length);
+                        end1 = luma_at(p1) - average;
+                    }
+                    if !done2 {
+                        p2 = (p2.0 + dx * length, p2.1 + dy * length);
+                        end2 = luma_at(p2) - average;
+                    }
+                }
+
+                let (distance1, distance2) = if horizontal {
+                    (cx - p1.0, p2.0 - cx)
+                } else {
+                    (cy - p1.1, p2.1 - cy)
+                };
+                let (distance, end) = if distance1 < distance2 {
+                    (distance1, end1)
+                } else {
+                    (distance2, end2)
+                };
+                // blend only when the closer end of the edge varies the same way as the center
+                let edge_offset = if (end < 0.0) != (center < average) {
+                    0.5 - distance / (distance1 + distance2)
+                } else {
+                    0.0
+                };
+                let neighbors = (2.0 * (n + s + w + e) + nw + ne + sw + se) / 12.0;
+                let subpixel = ((neighbors - center).abs() / range).clamp(0.0, 1.0);
+                let subpixel = (-2.0 * subpixel + 3.0) * subpixel * subpixel;
+                let offset = edge_offset.max(subpixel * subpixel * self.subpixel) * step;
+                let (sx, sy) = if horizontal {
+                    (cx, cy + offset)
+                } else {
+                    (cx + offset, cy)
+                };
+                output[frame.index(x as u32, y as u32)] = frame.sample(sx, sy);
+            }
+        }
+        frame.color = output;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::LinearColor;
+
+    #[test]
+    fn test_fxaa() {
+        // a shallow black on white edge with one pixel steps
+        let mut frame = FrameBuffers::new(16, 16);
+        for y in 0..16 {
+            for x in 0..16 {
+                if y * 4 > x + 20 {
+                    let index = frame.index(x, y);
+                    frame.color[index] = LinearColor::WHITE;
+                }
+            }
+        }
+        let original = frame.clone();
+        Fxaa::default().apply(&mut frame);
+
+        let changed: Vec<usize> = (0..frame.color.len())
+            .filter(|&i| frame.color[i] != original.color[i])
+            .collect();
+        assert!(!changed.is_empty());
+        for &i in &changed {
+            // only pixels next to the edge change, to shades between black and white
+            let (x, y) = (i as u32 % 16, i as u32 / 16);
+            let row = |y: u32| original.color[original.index(x, y.min(15))];
+            assert!(row(y.saturating_sub(1)) != row(y + 1), "{} {}", x, y);
+            assert!(frame.color[i].r > 0.0 && frame.color[i].r < 1.0);
+        }
+        // flat regions are left alone
+        assert_eq!(frame.color[0], LinearColor::BLACK);
+        assert_eq!(frame.color[frame.index(0, 15)], LinearColor::WHITE);
+    }
+}
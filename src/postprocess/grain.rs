@@ -0,0 +1,69 @@
+use crate::color::LinearColor;
+use crate::postprocess::{hash, FrameBuffers, PostEffect};
+
+/// Monochrome film grain, strongest in the mid tones.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FilmGrain {
+    /// Largest change of a channel.
+    pub intensity: f32,
+    /// Selects the noise pattern, change it every frame to animate the grain.
+    pub seed: u32,
+}
+
+impl Default for FilmGrain {
+    fn default() -> Self {
+        FilmGrain {
+            intensity: 0.05,
+            seed: 0,
+        }
+    }
+}
+
+impl PostEffect for FilmGrain {
+    fn apply(&self, frame: &mut FrameBuffers) {
+        for y in 0..frame.height {
+            for x in 0..frame.width {
+                let noise = hash(x, y, self.seed) as f32 / u32::MAX as f32 * 2.0 - 1.0;
+                let index = frame.index(x, y);
+                let color = frame.color[index];
+                // no grain in pure black and white
+                let luminance = color.luminance().clamp(0.0, 1.0);
+                let response = 4.0 * luminance * (1.0 - luminance);
+                let grain = noise * self.intensity * response;
+                frame.color[index] =
+                    LinearColor::rgba(color.r + grain, color.g + grain, color.b + grain, color.a)
+                        .clamp(0.0, f32::MAX);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_film_grain() {
+        let gray = LinearColor::rgb(0.5, 0.5, 0.5);
+        let mut frame = FrameBuffers::new(64, 64);
+        frame.color.fill(gray);
+        let black = frame.index(0, 0);
+        frame.color[black] = LinearColor::BLACK;
+        let mut grain = FilmGrain::default();
+        let mut first = frame.clone();
+        grain.apply(&mut first);
+
+        // unbiased noise within the intensity, the same for the same seed
+        let mean = first.color.iter().map(|c| c.r).sum::<f32>() / first.color.len() as f32;
+        assert!((mean - 0.5).abs() < 0.005, "{}", mean);
+        assert!(first.color.iter().all(|c| (c.r - 0.5).abs() <= 0.5));
+        assert!(first.color.iter().all(|c| c.r == c.b && c.a == 1.0));
+        assert_eq!(first.color[black], LinearColor::BLACK);
+        let mut again = frame.clone();
+        grain.apply(&mut again);
+        assert_eq!(first, again);
+        grain.seed = 1;
+        grain.apply(&mut frame);
+        assert_ne!(first, frame);
+    }
+}
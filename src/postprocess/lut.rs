@@ -0,0 +1,217 @@
+use crate::color::{srgb_eotf, srgb_oetf, LinearColor};
+use crate::math::Vec3;
+use crate::postprocess::{FrameBuffers, PostEffect};
+use anyhow::{anyhow, bail, Result};
+use std::path::Path;
+
+/// Color lookup table over the cube of sRGB encoded colors, the form grading tools export.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lut3d {
+    size: u32,
+    /// Encoded output colors with red changing fastest, then green, then blue.
+    table: Vec<Vec3>,
+}
+
+impl Lut3d {
+    /// A table with `size` entries per axis that leaves colors unchanged.
+    pub fn identity(size: u32) -> Self {
+        Lut3d::from_encoded_fn(size, |c| c)
+    }
+
+    /// A table of the linear color transform `f`, sampled at `size` points per axis.
+    pub fn from_fn<F>(size: u32, f: F) -> Self
+    where
+        F: Fn(&LinearColor) -> LinearColor,
+    {
+        Lut3d::from_encoded_fn(size, |c| {
+            let linear = LinearColor::rgb(srgb_eotf(c.x), srgb_eotf(c.y), srgb_eotf(c.z));
+            let out = f(&linear);
+            Vec3::new(srgb_oetf(out.r), srgb_oetf(out.g), srgb_oetf(out.b))
+        })
+    }
+
+    fn from_encoded_fn(size: u32, f: impl Fn(Vec3) -> Vec3) -> Self {
+        assert!(size >= 2);
+        let step = 1.0 / (size - 1) as f32;
+        let mut table = Vec::with_capacity((size * size * size) as usize);
+        for b in 0..size {
+            for g in 0..size {
+                for r in 0..size {
+                    table.push(f(Vec3::new(
+                        r as f32 * step,
+                        g as f32 * step,
+                        b as f32 * step,
+                    )));
+                }
+            }
+        }
+        Lut3d { size, table }
+    }
+
+    /// Parses an Adobe/Resolve `.cube` file with a 3D table and the default 0..1 domain.
+    pub fn parse_cube(text: &str) -> Result<Self> {
+        let mut size = None;
+        let mut table = Vec::new();
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') || line.starts_with("TITLE") {
+                continue;
+            }
+            if let Some(value) = line.strip_prefix("LUT_3D_SIZE") {
+                size = Some(value.trim().parse::<u32>()?);
+                continue;
+            }
+            if line.starts_with("DOMAIN_MIN") || line.starts_with("DOMAIN_MAX") {
+                let default = if line.starts_with("DOMAIN_MIN") {
+                    0.0
+                } else {
+                    1.0
+                };
+                let values = line.split_whitespace().skip(1);
+                if values
+                    .map(str::parse::<f32>)
+                    .any(|v| v.ok() != Some(default))
+                {
+                    bail!("unsupported cube domain: {}", line);
+                }
+                continue;
+            }
+            if line.starts_with("LUT_1D_SIZE") {
+                bail!("1D cube tables are not supported");
+            }
+            let values = line
+                .split_whitespace()
+                .map(str::parse::<f32>)
+                .collect::<Result<Vec<_>, _>>()?;
+            let [r, g, b] = values[..] else {
+                bail!("expected three values per entry: {}", line);
+            };
+            table.push(Vec3::new(r, g, b));
+        }
+        let size = size.ok_or_else(|| anyhow!("missing LUT_3D_SIZE"))?;
+        if size < 2 || table.len() != (size * size * size) as usize {
+            bail!("expected {}^3 entries, found {}", size, table.len());
+        }
+        Ok(Lut3d { size, table })
+    }
+
+    pub fn load_cube<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Lut3d::parse_cube(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// Transforms a linear color with trilinear interpolation between the entries, alpha is
+    /// kept.
+    pub fn lookup(&self, color: &LinearColor) -> LinearColor {
+        let max = (self.size - 1) as f32;
+        let coordinate = |c: f32| {
+            let x = srgb_oetf(c.clamp(0.0, 1.0)) * max;
+            let i = (x.floor() as u32).min(self.size - 2);
+            (i, x - i as f32)
+        };
+        let (r, tr) = coordinate(color.r);
+        let (g, tg) = coordinate(color.g);
+        let (b, tb) = coordinate(color.b);
+        let at =
+            |r: u32, g: u32, b: u32| self.table[((b * self.size + g) * self.size + r) as usize];
+        let lerp = |a: Vec3, b: Vec3, t: f32| a + (b - a) * t;
+        let plane = |b: u32| {
+            let low = lerp(at(r, g, b), at(r + 1, g, b), tr);
+            let high = lerp(at(r, g + 1, b), at(r + 1, g + 1, b), tr);
+            lerp(low, high, tg)
+        };
+        let encoded = lerp(plane(b), plane(b + 1), tb);
+        LinearColor::rgba(
+            srgb_eotf(encoded.x.clamp(0.0, 1.0)),
+            srgb_eotf(encoded.y.clamp(0.0, 1.0)),
+            srgb_eotf(encoded.z.clamp(0.0, 1.0)),
+            color.a,
+        )
+    }
+}
+
+/// Color grading through a 3D lookup table.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ColorGrading {
+    pub lut: Lut3d,
+    /// Blend between the original colors at 0 and the graded ones at 1.
+    pub strength: f32,
+}
+
+impl ColorGrading {
+    pub fn new(lut: Lut3d) -> Self {
+        ColorGrading { lut, strength: 1.0 }
+    }
+}
+
+impl PostEffect for ColorGrading {
+    fn apply(&self, frame: &mut FrameBuffers) {
+        for color in &mut frame.color {
+            *color = color.lerp(&self.lut.lookup(color), self.strength);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+
+    #[test]
+    fn test_color_grading() {
+        let colors = [
+            Color::rgb(0, 0, 0),
+            Color::rgb(255, 128, 12),
+            Color::rgba(37, 200, 99, 128),
+        ];
+        let mut frame = FrameBuffers::new(3, 1);
+        frame.color = colors.iter().map(|c| LinearColor::from(*c)).collect();
+        let original = frame.clone();
+        ColorGrading::new(Lut3d::identity(17)).apply(&mut frame);
+        let graded: Vec<Color> = frame.color.iter().map(|c| Color::from(*c)).collect();
+        assert_eq!(graded, colors);
+
+        // a warm tint at half strength
+        let warm = Lut3d::from_fn(9, |c| *c * Vec3::new(1.0, 0.8, 0.5));
+        let mut grading = ColorGrading::new(warm);
+        grading.strength = 0.5;
+        frame = original.clone();
+        grading.apply(&mut frame);
+        let expected = original.color[2] * Vec3::new(1.0, 0.9, 0.75);
+        assert!((frame.color[2].to_vec3() - expected.to_vec3()).sqrt() < 0.01);
+        assert_eq!(frame.color[2].a, original.color[2].a);
+
+        // a table swapping red and blue
+        let cube = "# swap\nTITLE \"swap\"\nLUT_3D_SIZE 2\n\
+            0 0 0\n0 0 1\n0 1 0\n0 1 1\n1 0 0\n1 0 1\n1 1 0\n1 1 1\n";
+        let lut = Lut3d::parse_cube(cube).unwrap();
+        assert_eq!(lut.size(), 2);
+        assert_eq!(
+            lut.lookup(&LinearColor::rgb(1.0, 0.0, 0.0)),
+            LinearColor::rgb(0.0, 0.0, 1.0)
+        );
+        assert!(Lut3d::parse_cube("LUT_3D_SIZE 2\n0 0 0\n").is_err());
+    }
+
+    #[test]
+    fn test_grading_hdr_frame() {
+        use crate::postprocess::PostStack;
+        use crate::renderer::Renderer;
+        use crate::tonemap::{ToneMapper, ToneMapping};
+
+        let mut renderer = Renderer::new(2, 1, false);
+        renderer.set_tone_mapping(Some(ToneMapping::new(0.0, ToneMapper::Reinhard)));
+        renderer.clear(Color::BLACK);
+        renderer.update_pixel(0, 0, |_| LinearColor::rgb(8.0, 8.0, 8.0));
+        renderer.update_pixel(1, 0, |_| LinearColor::rgb(1.0, 1.0, 1.0));
+        renderer.resolve();
+        let tone_mapped = renderer.pixels().to_vec();
+        // grading runs after the tone mapping, an 8.0 highlight stays at 8 / 9 instead of 1 / 2
+        renderer.post_process(&PostStack::new().with(ColorGrading::new(Lut3d::identity(17))));
+        assert_eq!(renderer.pixels(), tone_mapped);
+        assert!(renderer.pixels()[0].r > 240);
+        assert_eq!(renderer.pixels()[1], Color::rgb(188, 188, 188));
+    }
+}
@@ -0,0 +1,328 @@
+pub mod bloom;
+pub mod chromatic;
+pub mod fog;
+pub mod fxaa;
+pub mod grain;
+pub mod lut;
+pub mod sharpen;
+pub mod ssao;
+pub mod vignette;
+
+pub use bloom::*;
+pub use chromatic::*;
+pub use fog::*;
+pub use fxaa::*;
+pub use grain::*;
+pub use lut::*;
+pub use sharpen::*;
+pub use ssao::*;
+pub use vignette::*;
+
+use crate::color::LinearColor;
+use crate::math::Vec3;
+use crate::renderer::Renderer;
+use crate::viewport::Viewport;
+use std::ops::{Add, Mul};
+
+/// Color and depth of a frame with its samples averaged, row-major in the order of
+/// [`Renderer::pixels`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct FrameBuffers {
+    pub width: u32,
+    pub height: u32,
+    /// Linear colors, brighter than 1 with a high dynamic range target until the tone mapping
+    /// between the [`PostStage`]s, in 0..1 after it.
+    pub color: Vec<LinearColor>,
+    /// Window space depth, larger is closer and `-f32::MAX` where nothing was drawn.
+    pub depth: Vec<f32>,
+    /// Mapping of the depth back to normalized device coordinates.
+    pub viewport: Viewport,
+    /// See [`Renderer::flip_y`].
+    pub flip_y: bool,
+}
+
+impl FrameBuffers {
+    /// A black frame with nothing drawn and a viewport covering it.
+    pub fn new(width: u32, height: u32) -> Self {
+        let len = (width * height) as usize;
+        FrameBuffers {
+            width,
+            height,
+            color: vec![LinearColor::BLACK; len],
+            depth: vec![-f32::MAX; len],
+            viewport: Viewport::new(0.0, 0.0, width as f32, height as f32),
+            flip_y: false,
+        }
+    }
+
+    pub fn index(&self, x: u32, y: u32) -> usize {
+        (y * self.width + x) as usize
+    }
+
+    /// Converts between rows of the buffers and window y, which is its own inverse.
+    fn window_row(&self, y: u32) -> u32 {
+        if self.flip_y {
+            self.height - y - 1
+        } else {
+            y
+        }
+    }
+
+    /// Normalized device coordinates of a pixel center, `None` where nothing was drawn.
+    pub fn ndc(&self, x: u32, y: u32) -> Option<Vec3> {
+        let depth = self.depth[self.index(x, y)];
+        if depth == -f32::MAX {
+            return None;
+        }
+        let window = Vec3::new(x as f32 + 0.5, self.window_row(y) as f32 + 0.5, depth);
+        Some(self.viewport.to_ndc(&window))
+    }
+
+    /// The pixel covering a point in window coordinates, such as
+    /// [`Fragment::position`](crate::shader::Fragment::position).
+    pub fn pixel_at(&self, window: &Vec3) -> Option<(u32, u32)> {
+        let (x, y) = (window.x.floor(), window.y.floor());
+        if x < 0.0 || y < 0.0 || x >= self.width as f32 || y >= self.height as f32 {
+            return None;
+        }
+        Some((x as u32, self.window_row(y as u32)))
+    }
+
+    /// Color of a pixel, coordinates outside of the frame are clamped to its edges.
+    pub fn color_at(&self, x: i32, y: i32) -> LinearColor {
+        let x = x.clamp(0, self.width as i32 - 1) as u32;
+        let y = y.clamp(0, self.height as i32 - 1) as u32;
+        self.color[self.index(x, y)]
+    }
+
+    /// Bilinearly filtered color, pixel centers lie at half integer coordinates.
+    pub fn sample(&self, x: f32, y: f32) -> LinearColor {
+        sample_bilinear(&self.color, self.width, self.height, x, y)
+    }
+}
+
+/// Where an effect runs in [`Renderer::post_process`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PostStage {
+    /// On the linear colors before tone mapping, for effects that model light such as bloom.
+    Hdr,
+    /// On the tone mapped colors in 0..1, for effects tuned for what the display shows.
+    #[default]
+    Display,
+}
+
+/// A full screen pass over a resolved frame.
+pub trait PostEffect {
+    fn apply(&self, frame: &mut FrameBuffers);
+
+    fn stage(&self) -> PostStage {
+        PostStage::Display
+    }
+}
+
+/// Post effects applied one after another.
+#[derive(Default)]
+pub struct PostStack {
+    pub effects: Vec<Box<dyn PostEffect>>,
+}
+
+impl PostStack {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, effect: impl PostEffect + 'static) -> Self {
+        self.push(effect);
+        self
+    }
+
+    pub fn push(&mut self, effect: impl PostEffect + 'static) {
+        self.effects.push(Box::new(effect));
+    }
+
+    /// Runs every effect in order, regardless of its stage.
+    pub fn apply(&self, frame: &mut FrameBuffers) {
+        for effect in &self.effects {
+            effect.apply(frame);
+        }
+    }
+
+    /// Runs the effects of `stage` in order.
+    pub fn apply_stage(&self, frame: &mut FrameBuffers, stage: PostStage) {
+        for effect in self.effects.iter().filter(|e| e.stage() == stage) {
+            effect.apply(frame);
+        }
+    }
+}
+
+impl Renderer {
+    /// The averaged samples in linear light before tone mapping, with the depth of every pixel.
+    pub fn frame_buffers(&self) -> FrameBuffers {
+        FrameBuffers {
+            width: self.width(),
+            height: self.height(),
+            color: self.resolve_linear(),
+            depth: self.resolved_depth(),
+            viewport: *self.viewport(),
+            flip_y: self.flip_y(),
+        }
+    }
+
+    /// Resolves the frame like [`Renderer::resolve`] with `stack` run in between: the samples are
+    /// averaged, the [`PostStage::Hdr`] effects run on the linear colors, the result is tone
+    /// mapped, the [`PostStage::Display`] effects run and their result is encoded into the
+    /// pixels. Call instead of [`Renderer::resolve`]. Every call starts again from the drawn
+    /// frame, also without a resolve, until the next [`Renderer::clear`].
+    #[profiling::function]
+    pub fn post_process(&mut self, stack: &PostStack) {
+        let mut frame = self.frame_buffers();
+        stack.apply_stage(&mut frame, PostStage::Hdr);
+        for color in &mut frame.color {
+            *color = self.tone_map(color);
+        }
+        stack.apply_stage(&mut frame, PostStage::Display);
+        self.write_display(&frame.color);
+    }
+}
+
+/// Bilinear filtering of a row-major image with its edges clamped.
+pub(crate) fn sample_bilinear<T>(values: &[T], width: u32, height: u32, x: f32, y: f32) -> T
+where
+    T: Copy + Add<Output = T> + Mul<f32, Output = T>,
+{
+    let x = (x - 0.5).clamp(0.0, (width - 1) as f32);
+    let y = (y - 0.5).clamp(0.0, (height - 1) as f32);
+    let (x0, y0) = (x.floor() as u32, y.floor() as u32);
+    let (x1, y1) = ((x0 + 1).min(width - 1), (y0 + 1).min(height - 1));
+    let (tx, ty) = (x - x0 as f32, y - y0 as f32);
+    let at = |x: u32, y: u32| values[(y * width + x) as usize];
+    let top = at(x0, y0) * (1.0 - tx) + at(x1, y0) * tx;
+    let bottom = at(x0, y1) * (1.0 - tx) + at(x1, y1) * tx;
+    top * (1.0 - ty) + bottom * ty
+}
+
+/// Separable Gaussian blur of a row-major image with its edges clamped.
+pub(crate) fn gaussian_blur<T>(values: &mut [T], width: u32, height: u32, sigma: f32)
+where
+    T: Copy + Add<Output = T> + Mul<f32, Output = T>,
+{
+    if sigma <= 0.0 {
+        return;
+    }
+    let radius = (sigma * 3.0).ceil() as i32;
+    let weights: Vec<f32> = (-radius..=radius)
+        .map(|i| (-(i * i) as f32 / (2.0 * sigma * sigma)).exp())
+        .collect();
+    let total: f32 = weights.iter().sum();
+    let weights: Vec<f32> = weights.iter().map(|w| w / total).collect();
+    blur_pass(values, width, height, &weights, true);
+    blur_pass(values, width, height, &weights, false);
+}
+
+fn blur_pass<T>(values: &mut [T], width: u32, height: u32, weights: &[f32], horizontal: bool)
+where
+    T: Copy + Add<Output = T> + Mul<f32, Output = T>,
+{
+    let src = values.to_vec();
+    let (w, h) = (width as i32, height as i32);
+    let radius = weights.len() as i32 / 2;
+    for y in 0..h {
+        for x in 0..w {
+            let at = |i: i32| {
+                let (sx, sy) = if horizontal {
+                    ((x + i).clamp(0, w - 1), y)
+                } else {
+                    (x, (y + i).clamp(0, h - 1))
+                };
+                src[(sy * w + sx) as usize]
+            };
+            let mut sum = at(-radius) * weights[0];
+            for (i, weight) in (-radius + 1..=radius).zip(&weights[1..]) {
+                sum = sum + at(i) * *weight;
+            }
+            values[(y * w + x) as usize] = sum;
+        }
+    }
+}
+
+pub(crate) fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// Well mixed hash of pixel coordinates and a seed, for noise that repeats between runs.
+pub(crate) fn hash(x: u32, y: u32, seed: u32) -> u32 {
+    let mut h =
+        x.wrapping_mul(0x8da6_b343) ^ y.wrapping_mul(0xd816_3841) ^ seed.wrapping_mul(0xcb1a_b31f);
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x7feb_352d);
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x846c_a68b);
+    h ^= h >> 16;
+    h
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::{srgb_to_linear, Color};
+    use crate::renderer::Msaa;
+
+    struct Invert;
+
+    impl PostEffect for Invert {
+        fn apply(&self, frame: &mut FrameBuffers) {
+            for color in &mut frame.color {
+                *color = LinearColor::rgba(1.0 - color.r, 1.0 - color.g, 1.0 - color.b, color.a);
+            }
+        }
+    }
+
+    #[test]
+    fn test_post_stack() {
+        let mut renderer = Renderer::new(4, 2, false);
+        renderer.clear(Color::BLACK);
+        renderer.draw_pixel(1, 0, Color::rgb(255, 188, 0));
+        let frame = renderer.frame_buffers();
+        assert_eq!(frame.color[1].g, srgb_to_linear(188));
+        assert_eq!(frame.depth, vec![-f32::MAX; 8]);
+
+        for msaa in [Msaa::Off, Msaa::X4] {
+            renderer.set_msaa(msaa);
+            renderer.clear(Color::BLACK);
+            // every pass starts from the drawn frame instead of the previous output
+            renderer.post_process(&PostStack::new().with(Invert));
+            renderer.post_process(&PostStack::new().with(Invert));
+            assert_eq!(renderer.pixels()[0], Color::WHITE, "{:?}", msaa);
+            // effects run in order, inverting twice restores the frame
+            renderer.post_process(&PostStack::new().with(Invert).with(Invert));
+            assert_eq!(renderer.pixels()[0], Color::BLACK, "{:?}", msaa);
+            // drawing after a pass shows up in the next one
+            renderer.draw_pixel(0, 0, Color::WHITE);
+            renderer.post_process(&PostStack::new().with(Invert));
+            assert_eq!(
+                renderer.pixels()[..2],
+                [Color::BLACK, Color::WHITE],
+                "{:?}",
+                msaa
+            );
+            renderer.resolve();
+            assert_eq!(
+                renderer.pixels()[..2],
+                [Color::WHITE, Color::BLACK],
+                "{:?}",
+                msaa
+            );
+        }
+
+        let mut values = vec![0.0; 9];
+        values[4] = 1.0;
+        gaussian_blur(&mut values, 9, 1, 1.0);
+        assert!((values.iter().sum::<f32>() - 1.0).abs() < 1e-5);
+        assert!(values[4] > values[3] && values[3] > values[2] && values[1] > 0.0);
+        assert_eq!(values[3], values[5]);
+        assert_eq!(sample_bilinear(&values, 9, 1, 4.5, 0.5), values[4]);
+        // pixel centers at 0.5 and 1.5
+        assert_eq!(sample_bilinear(&[0.0, 1.0], 2, 1, 1.25, 0.5), 0.75);
+    }
+}
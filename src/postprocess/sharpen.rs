@@ -0,0 +1,54 @@
+use crate::postprocess::{FrameBuffers, PostEffect};
+
+/// Unsharp masking with the four direct neighbors, increases the contrast of edges.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sharpen {
+    /// 0 leaves the frame alone, around 1 is strong.
+    pub amount: f32,
+}
+
+impl Default for Sharpen {
+    fn default() -> Self {
+        Sharpen { amount: 0.3 }
+    }
+}
+
+impl PostEffect for Sharpen {
+    fn apply(&self, frame: &mut FrameBuffers) {
+        let mut output = frame.color.clone();
+        for y in 0..frame.height as i32 {
+            for x in 0..frame.width as i32 {
+                let center = frame.color_at(x, y);
+                let neighbors = frame.color_at(x, y - 1)
+                    + frame.color_at(x, y + 1)
+                    + frame.color_at(x - 1, y)
+                    + frame.color_at(x + 1, y);
+                let detail = center * 4.0 - neighbors;
+                let sharpened = (center + detail * self.amount).clamp(0.0, f32::MAX);
+                output[frame.index(x as u32, y as u32)] = sharpened.with_alpha(center.a);
+            }
+        }
+        frame.color = output;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::LinearColor;
+
+    #[test]
+    fn test_sharpen() {
+        let mut frame = FrameBuffers::new(8, 1);
+        for x in 0..8 {
+            frame.color[x] = LinearColor::rgb(0.25, 0.25, 0.25) * if x < 4 { 1.0 } else { 2.0 };
+        }
+        Sharpen { amount: 0.5 }.apply(&mut frame);
+        // flat areas stay, the sides of the edge are pushed apart
+        assert_eq!(frame.color[0].r, 0.25);
+        assert_eq!(frame.color[7].r, 0.5);
+        assert_eq!(frame.color[3].r, 0.125);
+        assert_eq!(frame.color[4].r, 0.625);
+        assert_eq!(frame.color[3].a, 1.0);
+    }
+}
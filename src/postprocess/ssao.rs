@@ -0,0 +1,289 @@
+use crate::math::{Mat4, Mat4x1, Vec3};
+use crate::postprocess::{hash, smoothstep, FrameBuffers};
+
+/// Screen-space ambient occlusion, estimates how much of the hemisphere above every pixel is
+/// blocked by nearby geometry in the depth buffer. Normals are reconstructed from the depth.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ssao {
+    /// The projection the frame was drawn with, to get from depth back to view space.
+    pub projection: Mat4,
+    /// Radius of the sampled hemisphere in view space units.
+    pub radius: f32,
+    /// View space distance a sample must lie behind the surface to count, avoids acne on flat
+    /// surfaces.
+    pub bias: f32,
+    /// Exponent applied to the visibility, higher darkens the occlusion.
+    pub power: f32,
+    pub sample_count: u32,
+    /// Side of the tiled pattern rotating the samples per pixel, the blur removes it.
+    pub noise_size: u32,
+    /// Radius of the bilateral blur in pixels, 0 disables it.
+    pub blur_radius: u32,
+    /// Relative difference in view depth at which the blur stops mixing pixels.
+    pub blur_depth_tolerance: f32,
+}
+
+impl Ssao {
+    pub fn new(projection: Mat4) -> Self {
+        Ssao {
+            projection,
+            radius: 0.5,
+            bias: 0.025,
+            power: 1.0,
+            sample_count: 16,
+            noise_size: 4,
+            blur_radius: 2,
+            blur_depth_tolerance: 0.05,
+        }
+    }
+
+    /// Hemisphere samples around +z, more of them close to the center.
+    fn kernel(&self) -> Vec<Vec3> {
+        (0..self.sample_count)
+            .map(|i| {
+                let random = |k: u32| hash(i, k, 0x55a0) as f32 / u32::MAX as f32;
+                let direction =
+                    Vec3::new(random(0) * 2.0 - 1.0, random(1) * 2.0 - 1.0, random(2)).normalize();
+                let t = i as f32 / self.sample_count as f32;
+                direction * random(3) * (0.1 + 0.9 * t * t)
+            })
+            .collect()
+    }
+
+    /// Visibility of every pixel of `frame`, 1 where nothing was drawn.
+    #[profiling::function]
+    pub fn compute(&self, frame: &FrameBuffers) -> AmbientOcclusion {
+        let (width, height) = (frame.width, frame.height);
+        let inverse = self.projection.inverse().unwrap_or_else(Mat4::identity);
+        let positions: Vec<Option<Vec3>> = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let ndc = frame.ndc(x, y)?;
+                Some(inverse.mul_mat41(&Mat4x1::from(ndc)).to_vec3())
+            })
+            .collect();
+        let position = |x: i32, y: i32| {
+            if x < 0 || y < 0 || x >= width as i32 || y >= height as i32 {
+                return None;
+            }
+            positions[frame.index(x as u32, y as u32)]
+        };
+
+        let kernel = self.kernel();
+        let noise_size = self.noise_size.max(1);
+        let mut values = vec![1.0; positions.len()];
+        for y in 0..height {
+            for x in 0..width {
+                let Some(p) = positions[frame.index(x, y)] else {
+                    continue;
+                };
+                let Some(normal) =
+                    reconstruct_normal(&p, |dx, dy| position(x as i32 + dx, y as i32 + dy))
+                else {
+                    continue;
+                };
+                let angle = hash(x % noise_size, y % noise_size, 0x7a31) as f32 / u32::MAX as f32
+                    * std::f32::consts::TAU;
+                let random = Vec3::new(angle.cos(), angle.sin(), 0.0);
+                let mut tangent = random - normal * random.dot(&normal);
+                if tangent.sqrt() < 1e-3 {
+                    tangent = Vec3::new(0.0, 0.0, 1.0).cross(&normal);
+                }
+                let tangent = tangent.normalize();
+                let bitangent = normal.cross(&tangent);
+
+                let mut occlusion = 0.0;
+                for k in &kernel {
+                    let sample = p + (tangent * k.x + bitangent * k.y + normal * k.z) * self.radius;
+                    let ndc = self.projection.mul_mat41(&Mat4x1::from(sample)).to_vec3();
+                    let window = frame.viewport.to_window(&ndc);
+                    let Some((sx, sy)) = frame.pixel_at(&window) else {
+                        continue;
+                    };
+                    let Some(scene) = positions[frame.index(sx, sy)] else {
+                        continue;
+                    };
+                    // geometry far in front of the point doesn't shadow it
+                    let range = smoothstep(0.0, 1.0, self.radius / (p.z - scene.z).abs());
+                    if scene.z >= sample.z + self.bias {
+                        occlusion += range;
+                    }
+                }
+                let visibility = 1.0 - occlusion / kernel.len().max(1) as f32;
+                values[frame.index(x, y)] = visibility.max(0.0).powf(self.power);
+            }
+        }
+
+        let depth: Vec<Option<f32>> = positions.iter().map(|p| p.map(|p| p.z)).collect();
+        let values = self.bilateral_blur(&values, &depth, width, height);
+        AmbientOcclusion {
+            width,
+            height,
+            flip_y: frame.flip_y,
+            values,
+        }
+    }
+
+    /// Gaussian blur that only mixes pixels at a similar view depth, so the noise pattern goes
+    /// away without occlusion bleeding across silhouettes.
+    fn bilateral_blur(
+        &self,
+        values: &[f32],
+        depth: &[Option<f32>],
+        width: u32,
+        height: u32,
+    ) -> Vec<f32> {
+        if self.blur_radius == 0 {
+            return values.to_vec();
+        }
+        let radius = self.blur_radius as i32;
+        let sigma = (radius as f32 * 0.5).max(0.5);
+        let (w, h) = (width as i32, height as i32);
+        let mut output = values.to_vec();
+        for y in 0..h {
+            for x in 0..w {
+                let index = (y * w + x) as usize;
+                let Some(z) = depth[index] else {
+                    continue;
+                };
+                let tolerance = (z.abs() * self.blur_depth_tolerance).max(1e-6);
+                let (mut sum, mut total) = (0.0, 0.0);
+                for dy in -radius..=radius {
+                    for dx in -radius..=radius {
+                        let (nx, ny) = (x + dx, y + dy);
+                        if nx < 0 || ny < 0 || nx >= w || ny >= h {
+                            continue;
+                        }
+                        let neighbor = (ny * w + nx) as usize;
+                        let Some(nz) = depth[neighbor] else {
+                            continue;
+                        };
+                        let spatial = -((dx * dx + dy * dy) as f32) / (2.0 * sigma * sigma);
+                        let range = -((nz - z) / tolerance).powi(2) * 0.5;
+                        let weight = (spatial + range).exp();
+                        sum += values[neighbor] * weight;
+                        total += weight;
+                    }
+                }
+                output[index] = sum / total;
+            }
+        }
+        output
+    }
+}
+
+/// View space normal facing the camera from the neighbors of `p`, taking the neighbor with the
+/// smaller depth difference on each axis to keep silhouettes sharp.
+fn reconstruct_normal(p: &Vec3, position: impl Fn(i32, i32) -> Option<Vec3>) -> Option<Vec3> {
+    let derivative = |forward: Option<Vec3>, backward: Option<Vec3>| match (forward, backward) {
+        (Some(f), Some(b)) if (f.z - p.z).abs() <= (p.z - b.z).abs() => Some(f - *p),
+        (_, Some(b)) => Some(*p - b),
+        (Some(f), None) => Some(f - *p),
+        (None, None) => None,
+    };
+    let dx = derivative(position(1, 0), position(-1, 0))?;
+    let dy = derivative(position(0, 1), position(0, -1))?;
+    let normal = dx.cross(&dy);
+    if normal.sqrt() < 1e-12 {
+        return None;
+    }
+    let normal = normal.normalize();
+    // the camera sits at the origin of view space
+    Some(if normal.dot(p) > 0.0 { -normal } else { normal })
+}
+
+/// Result of an [`Ssao`] pass, the visibility of the ambient light in 0..1 per pixel.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AmbientOcclusion {
+    pub width: u32,
+    pub height: u32,
+    /// See [`Renderer::flip_y`](crate::renderer::Renderer::flip_y).
+    pub flip_y: bool,
+    /// Row-major in the order of [`FrameBuffers`].
+    pub values: Vec<f32>,
+}
+
+impl AmbientOcclusion {
+    /// Visibility at a point in window coordinates, such as
+    /// [`Fragment::position`](crate::shader::Fragment::position). 1 outside of the frame.
+    pub fn at(&self, window: &Vec3) -> f32 {
+        let (x, y) = (window.x.floor(), window.y.floor());
+        if x < 0.0 || y < 0.0 || x >= self.width as f32 || y >= self.height as f32 {
+            return 1.0;
+        }
+        let y = if self.flip_y {
+            self.height - y as u32 - 1
+        } else {
+            y as u32
+        };
+        self.values[(y * self.width + x as u32) as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::Camera;
+    use crate::color::Color;
+    use crate::math::vec3;
+    use crate::rasterizer::PrimitiveTopology;
+    use crate::renderer::Renderer;
+
+    #[test]
+    fn test_ssao() {
+        // looking down at a floor with a wall standing on its far half
+        let mut camera = Camera::new(1.2, 1.0, 0.1, 20.0);
+        camera.position = vec3(0.0, 3.0, 3.0);
+        camera.look_at(&vec3(0.0, 0.0, 0.0), &vec3(0.0, 1.0, 0.0));
+        let floor = [
+            vec3(-4.0, 0.0, -4.0),
+            vec3(-4.0, 0.0, 4.0),
+            vec3(4.0, 0.0, 4.0),
+            vec3(-4.0, 0.0, -4.0),
+            vec3(4.0, 0.0, 4.0),
+            vec3(4.0, 0.0, -4.0),
+        ];
+        let wall = [
+            vec3(-4.0, 0.0, -0.5),
+            vec3(4.0, 0.0, -0.5),
+            vec3(4.0, 3.0, -0.5),
+            vec3(-4.0, 0.0, -0.5),
+            vec3(4.0, 3.0, -0.5),
+            vec3(-4.0, 3.0, -0.5),
+        ];
+        let mut renderer = Renderer::new(64, 64, true);
+        renderer.clear(Color::BLACK);
+        let transform = camera.view_projection_matrix();
+        for quad in [floor, wall] {
+            renderer.draw_primitives(
+                &quad,
+                PrimitiveTopology::TriangleList,
+                &transform,
+                Color::WHITE,
+            );
+        }
+        let frame = renderer.frame_buffers();
+        let ssao = Ssao::new(camera.projection_matrix());
+        let occlusion = ssao.compute(&frame);
+
+        let project = |p: Vec3| {
+            let ndc = transform.mul_mat41(&Mat4x1::from(p)).to_vec3();
+            occlusion.at(&renderer.viewport().to_window(&ndc))
+        };
+        // the crease between the floor and the wall is darker than the open floor
+        let crease = project(vec3(0.0, 0.0, -0.45));
+        let open = project(vec3(0.0, 0.0, 1.5));
+        assert!(open > 0.95, "{}", open);
+        assert!(crease < 0.9, "{}", crease);
+        assert!(project(vec3(0.0, 0.0, -0.2)) > crease);
+        // the wall darkens towards the floor as well
+        assert!(project(vec3(0.0, 0.05, -0.5)) < 0.9);
+        assert!(project(vec3(0.0, 1.0, -0.5)) > 0.95);
+
+        // nothing drawn is fully visible
+        let mut empty = FrameBuffers::new(8, 8);
+        empty.flip_y = true;
+        assert!(ssao.compute(&empty).values.iter().all(|v| *v == 1.0));
+        assert_eq!(occlusion.at(&vec3(-1.0, 5.0, 0.0)), 1.0);
+    }
+}
@@ -0,0 +1,65 @@
+use crate::color::LinearColor;
+use crate::postprocess::{smoothstep, FrameBuffers, PostEffect};
+
+/// Fades the edges of the frame towards a color.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Vignette {
+    /// How much of `color` reaches the corners, in 0..1.
+    pub intensity: f32,
+    /// Distance from the center where the fade starts, 1 is a corner.
+    pub radius: f32,
+    /// Distance over which the fade reaches full intensity.
+    pub softness: f32,
+    pub color: LinearColor,
+}
+
+impl Default for Vignette {
+    fn default() -> Self {
+        Vignette {
+            intensity: 0.35,
+            radius: 0.6,
+            softness: 0.5,
+            color: LinearColor::BLACK,
+        }
+    }
+}
+
+impl PostEffect for Vignette {
+    fn apply(&self, frame: &mut FrameBuffers) {
+        let (cx, cy) = (frame.width as f32 * 0.5, frame.height as f32 * 0.5);
+        let half_diagonal = (cx * cx + cy * cy).sqrt();
+        for y in 0..frame.height {
+            for x in 0..frame.width {
+                let (dx, dy) = (x as f32 + 0.5 - cx, y as f32 + 0.5 - cy);
+                let distance = (dx * dx + dy * dy).sqrt() / half_diagonal;
+                let t = smoothstep(self.radius, self.radius + self.softness, distance);
+                let index = frame.index(x, y);
+                let color = frame.color[index];
+                frame.color[index] =
+                    color.lerp(&self.color.with_alpha(color.a), t * self.intensity);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vignette() {
+        let mut frame = FrameBuffers::new(20, 10);
+        frame.color.fill(LinearColor::WHITE);
+        Vignette::default().apply(&mut frame);
+        assert_eq!(frame.color[frame.index(10, 5)], LinearColor::WHITE);
+        // darker towards the corners, symmetric around the center
+        let row: Vec<f32> = (10..20).map(|x| frame.color[frame.index(x, 0)].r).collect();
+        assert!(row.windows(2).all(|w| w[1] <= w[0]));
+        assert!(row[9] < 0.8 && row[9] >= 0.65);
+        assert_eq!(
+            frame.color[frame.index(0, 0)],
+            frame.color[frame.index(19, 9)]
+        );
+        assert_eq!(frame.color[frame.index(0, 0)].a, 1.0);
+    }
+}
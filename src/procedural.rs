@@ -0,0 +1,507 @@
+use crate::math::{vec2, vec3, Vec2, Vec3};
+use crate::model::{Model, Vertex};
+use crate::texture::Texture;
+use std::collections::HashMap;
+use std::f32::consts::{FRAC_PI_2, PI, TAU};
+
+/// Point of the profile curve that [`MeshBuilder::revolve`] sweeps around the y axis.
+struct ProfilePoint {
+    /// Distance from the axis and height.
+    position: Vec2,
+    /// Outward normal in the same radius and height plane.
+    normal: Vec2,
+    v: f32,
+}
+
+/// Accumulates vertices and triangles, orienting every triangle so that it is counter-clockwise
+/// when seen from the side its vertex normals point to.
+#[derive(Default)]
+struct MeshBuilder {
+    vertices: Vec<Vertex>,
+    indices: Vec<u32>,
+}
+
+impl MeshBuilder {
+    fn push(&mut self, position: Vec3, normal: Vec3, uv: Vec2) -> u32 {
+        self.vertices.push(Vertex {
+            position,
+            uv,
+            normal,
+        });
+        self.vertices.len() as u32 - 1
+    }
+
+    /// Skips degenerate triangles, which appear where a row of a surface collapses to a point.
+    fn triangle(&mut self, [a, b, c]: [u32; 3]) {
+        let [va, vb, vc] = [a, b, c].map(|i| self.vertices[i as usize]);
+        let (ab, ac) = (vb.position - va.position, vc.position - va.position);
+        let face_normal = ab.cross(&ac);
+        // relative to the edge lengths, so slivers from rounding at the poles are caught too
+        if face_normal.sqrt() <= 1e-5 * (ab.dot(&ab) + ac.dot(&ac)) {
+            return;
+        }
+        if face_normal.dot(&(va.normal + vb.normal + vc.normal)) >= 0.0 {
+            self.indices.extend([a, b, c]);
+        } else {
+            self.indices.extend([a, c, b]);
+        }
+    }
+
+    /// Grid of `columns` x `rows` quads, `f` maps (u, v) in [0, 1] to a position and a normal.
+    /// The uvs are (u, v).
+    fn surface<F>(&mut self, columns: u32, rows: u32, f: F)
+    where
+        F: Fn(f32, f32) -> (Vec3, Vec3),
+    {
+        let base = self.vertices.len() as u32;
+        for j in 0..=rows {
+            for i in 0..=columns {
+                let (u, v) = (i as f32 / columns as f32, j as f32 / rows as f32);
+                let (position, normal) = f(u, v);
+                self.push(position, normal, vec2(u, v));
+            }
+        }
+        for j in 0..rows {
+            for i in 0..columns {
+                let a = base + j * (columns + 1) + i;
+                let (b, c, d) = (a + 1, a + columns + 2, a + columns + 1);
+                self.triangle([a, b, c]);
+                self.triangle([a, c, d]);
+            }
+        }
+    }
+
+    /// Sweeps `profile` around the y axis. u follows the angle, starting at +z and turning
+    /// towards +x, and v comes from the profile.
+    fn revolve(&mut self, segments: u32, profile: &[ProfilePoint]) {
+        let base = self.vertices.len() as u32;
+        for point in profile {
+            for i in 0..=segments {
+                let u = i as f32 / segments as f32;
+                let (sin, cos) = (u * TAU).sin_cos();
+                let position = vec3(
+                    point.position.x * sin,
+                    point.position.y,
+                    point.position.x * cos,
+                );
+                let normal = vec3(point.normal.x * sin, point.normal.y, point.normal.x * cos);
+                self.push(position, normal, vec2(u, point.v));
+            }
+        }
+        for j in 0..profile.len() as u32 - 1 {
+            for i in 0..segments {
+                let a = base + j * (segments + 1) + i;
+                let (b, c, d) = (a + 1, a + segments + 2, a + segments + 1);
+                self.triangle([a, b, c]);
+                self.triangle([a, c, d]);
+            }
+        }
+    }
+
+    /// Flat disk of `radius` at height `y`, facing up or down.
+    fn cap(&mut self, segments: u32, radius: f32, y: f32, up: bool) {
+        let normal = vec2(0.0, if up { 1.0 } else { -1.0 });
+        let center = ProfilePoint {
+            position: vec2(0.0, y),
+            normal,
+            v: 0.0,
+        };
+        let rim = ProfilePoint {
+            position: vec2(radius, y),
+            normal,
+            v: 1.0,
+        };
+        self.revolve(segments, &[center, rim]);
+    }
+
+    fn build(self) -> Model {
+        Model::new(self.vertices, self.indices, Texture::default())
+    }
+}
+
+/// Profile points along a straight segment from `start` to `end`.
+fn line_profile(start: Vec2, end: Vec2, segments: u32) -> Vec<ProfilePoint> {
+    let direction = end - start;
+    // a quarter turn of the direction, profiles run from top to bottom so this points outwards
+    let normal = vec2(-direction.y, direction.x).normalize();
+    (0..=segments)
+        .map(|j| {
+            let t = j as f32 / segments as f32;
+            ProfilePoint {
+                position: start + direction * t,
+                normal,
+                v: t,
+            }
+        })
+        .collect()
+}
+
+/// Profile points along the arc of a circle centered at `center`, between the polar angles
+/// `from` and `to` measured from +y.
+fn arc_profile(center: Vec2, radius: f32, from: f32, to: f32, segments: u32) -> Vec<ProfilePoint> {
+    (0..=segments)
+        .map(|j| {
+            let t = j as f32 / segments as f32;
+            let (sin, cos) = (from + (to - from) * t).sin_cos();
+            ProfilePoint {
+                position: center + vec2(sin, cos) * radius,
+                normal: vec2(sin, cos),
+                v: t,
+            }
+        })
+        .collect()
+}
+
+/// Procedural models, centered at the origin with y up and a white texture. Subdivision
+/// parameters are clamped to the smallest values that still give a closed shape.
+impl Model {
+    /// Square in the xz plane facing +y, `subdivisions` quads along each side.
+    pub fn plane(size: f32, subdivisions: u32) -> Model {
+        let mut builder = MeshBuilder::default();
+        let subdivisions = subdivisions.max(1);
+        builder.surface(subdivisions, subdivisions, |u, v| {
+            (
+                vec3((u - 0.5) * size, 0.0, (v - 0.5) * size),
+                vec3(0.0, 1.0, 0.0),
+            )
+        });
+        builder.build()
+    }
+
+    /// Axis aligned cube with `subdivisions` quads along each edge of every face. Faces don't
+    /// share vertices, so edges stay sharp.
+    pub fn cube(size: f32, subdivisions: u32) -> Model {
+        let mut builder = MeshBuilder::default();
+        let subdivisions = subdivisions.max(1);
+        // normal, then the directions of increasing u and v as seen from outside, so that the
+        // texture is never mirrored
+        let faces = [
+            (
+                vec3(0.0, 0.0, 1.0),
+                vec3(1.0, 0.0, 0.0),
+                vec3(0.0, -1.0, 0.0),
+            ),
+            (
+                vec3(0.0, 0.0, -1.0),
+                vec3(-1.0, 0.0, 0.0),
+                vec3(0.0, -1.0, 0.0),
+            ),
+            (
+                vec3(1.0, 0.0, 0.0),
+                vec3(0.0, 0.0, -1.0),
+                vec3(0.0, -1.0, 0.0),
+            ),
+            (
+                vec3(-1.0, 0.0, 0.0),
+                vec3(0.0, 0.0, 1.0),
+                vec3(0.0, -1.0, 0.0),
+            ),
+            (
+                vec3(0.0, 1.0, 0.0),
+                vec3(1.0, 0.0, 0.0),
+                vec3(0.0, 0.0, 1.0),
+            ),
+            (
+                vec3(0.0, -1.0, 0.0),
+                vec3(1.0, 0.0, 0.0),
+                vec3(0.0, 0.0, -1.0),
+            ),
+        ];
+        for (normal, right, down) in faces {
+            builder.surface(subdivisions, subdivisions, |u, v| {
+                let position = (normal * 0.5 + right * (u - 0.5) + down * (v - 0.5)) * size;
+                (position, normal)
+            });
+        }
+        builder.build()
+    }
+
+    /// Sphere made of `segments` slices around the y axis and `rings` stacks from pole to pole.
+    pub fn uv_sphere(radius: f32, segments: u32, rings: u32) -> Model {
+        let mut builder = MeshBuilder::default();
+        let profile = arc_profile(Vec2::ZERO, radius, 0.0, PI, rings.max(2));
+        builder.revolve(segments.max(3), &profile);
+        builder.build()
+    }
+
+    /// Sphere made by splitting every triangle of an icosahedron into four, `subdivisions` times,
+    /// which spreads the triangles much more evenly than [`Model::uv_sphere`].
+    pub fn ico_sphere(radius: f32, subdivisions: u32) -> Model {
+        let t = (1.0 + 5.0f32.sqrt()) / 2.0;
+        let mut positions: Vec<Vec3> = [
+            (-1.0, t, 0.0),
+            (1.0, t, 0.0),
+            (-1.0, -t, 0.0),
+            (1.0, -t, 0.0),
+            (0.0, -1.0, t),
+            (0.0, 1.0, t),
+            (0.0, -1.0, -t),
+            (0.0, 1.0, -t),
+            (t, 0.0, -1.0),
+            (t, 0.0, 1.0),
+            (-t, 0.0, -1.0),
+            (-t, 0.0, 1.0),
+        ]
+        .iter()
+        .map(|&(x, y, z)| vec3(x, y, z).normalize())
+        .collect();
+        let mut triangles: Vec<[u32; 3]> = vec![
+            [0, 11, 5],
+            [0, 5, 1],
+            [0, 1, 7],
+            [0, 7, 10],
+            [0, 10, 11],
+            [1, 5, 9],
+            [5, 11, 4],
+            [11, 10, 2],
+            [10, 7, 6],
+            [7, 1, 8],
+            [3, 9, 4],
+            [3, 4, 2],
+            [3, 2, 6],
+            [3, 6, 8],
+            [3, 8, 9],
+            [4, 9, 5],
+            [2, 4, 11],
+            [6, 2, 10],
+            [8, 6, 7],
+            [9, 8, 1],
+        ];
+        for _ in 0..subdivisions {
+            let mut midpoints: HashMap<(u32, u32), u32> = HashMap::new();
+            let mut midpoint = |a: u32, b: u32| {
+                *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                    let p = (positions[a as usize] + positions[b as usize]).normalize();
+                    positions.push(p);
+                    positions.len() as u32 - 1
+                })
+            };
+            triangles = triangles
+                .iter()
+                .flat_map(|&[a, b, c]| {
+                    let (ab, bc, ca) = (midpoint(a, b), midpoint(b, c), midpoint(c, a));
+                    [[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+                })
+                .collect();
+        }
+
+        // spherical uvs matching uv_sphere, vertices are duplicated where u wraps around and at
+        // the poles, where u depends on the triangle
+        let mut builder = MeshBuilder::default();
+        let mut unique: HashMap<(u32, u32), u32> = HashMap::new();
+        for triangle in triangles {
+            let mut uvs = triangle.map(|i| {
+                let p = positions[i as usize];
+                vec2(
+                    (p.x.atan2(p.z) / TAU).rem_euclid(1.0),
+                    p.y.clamp(-1.0, 1.0).acos() / PI,
+                )
+            });
+            let max_u = uvs.iter().map(|uv| uv.x).fold(0.0, f32::max);
+            for uv in uvs.iter_mut() {
+                if max_u - uv.x > 0.5 {
+                    uv.x += 1.0;
+                }
+            }
+            for k in 0..3 {
+                let p = positions[triangle[k] as usize];
+                if p.x.abs() < 1e-6 && p.z.abs() < 1e-6 {
+                    let others: Vec<f32> = (0..3).filter(|&o| o != k).map(|o| uvs[o].x).collect();
+                    uvs[k].x = (others[0] + others[1]) / 2.0;
+                }
+            }
+            let corners = [0, 1, 2].map(|k| {
+                let index = triangle[k];
+                let uv = uvs[k];
+                *unique.entry((index, uv.x.to_bits())).or_insert_with(|| {
+                    let p = positions[index as usize];
+                    builder.push(p * radius, p, uv)
+                })
+            });
+            builder.triangle(corners);
+        }
+        builder.build()
+    }
+
+    /// Cylinder along the y axis with flat caps, `height_segments` rows along its side.
+    pub fn cylinder(radius: f32, height: f32, segments: u32, height_segments: u32) -> Model {
+        let mut builder = MeshBuilder::default();
+        let segments = segments.max(3);
+        let half = height / 2.0;
+        let side = line_profile(
+            vec2(radius, half),
+            vec2(radius, -half),
+            height_segments.max(1),
+        );
+        builder.revolve(segments, &side);
+        builder.cap(segments, radius, half, true);
+        builder.cap(segments, radius, -half, false);
+        builder.build()
+    }
+
+    /// Cone along the y axis with its tip at the top and a flat base.
+    pub fn cone(radius: f32, height: f32, segments: u32, height_segments: u32) -> Model {
+        let mut builder = MeshBuilder::default();
+        let segments = segments.max(3);
+        let half = height / 2.0;
+        let side = line_profile(vec2(0.0, half), vec2(radius, -half), height_segments.max(1));
+        builder.revolve(segments, &side);
+        builder.cap(segments, radius, -half, false);
+        builder.build()
+    }
+
+    /// Torus around the y axis. `major_radius` is the distance from the center to the middle of
+    /// the tube, `minor_radius` the radius of the tube.
+    pub fn torus(
+        major_radius: f32,
+        minor_radius: f32,
+        major_segments: u32,
+        minor_segments: u32,
+    ) -> Model {
+        let mut builder = MeshBuilder::default();
+        let profile = arc_profile(
+            vec2(major_radius, 0.0),
+            minor_radius,
+            0.0,
+            TAU,
+            minor_segments.max(3),
+        );
+        builder.revolve(major_segments.max(3), &profile);
+        builder.build()
+    }
+
+    /// Cylinder of `height` along the y axis closed by two hemispheres, `rings` stacks each.
+    /// The total height is `height + 2 * radius`.
+    pub fn capsule(radius: f32, height: f32, segments: u32, rings: u32) -> Model {
+        let mut builder = MeshBuilder::default();
+        let rings = rings.max(1);
+        let half = height / 2.0;
+        let mut profile = arc_profile(vec2(0.0, half), radius, 0.0, FRAC_PI_2, rings);
+        profile.extend(arc_profile(vec2(0.0, -half), radius, FRAC_PI_2, PI, rings));
+        // v follows the length of the profile, so the texture isn't stretched along the side
+        let length = PI * radius + height;
+        let mut distance = 0.0;
+        for j in 1..profile.len() {
+            distance += (profile[j].position - profile[j - 1].position).sqrt();
+            profile[j].v = distance / length;
+        }
+        profile[0].v = 0.0;
+        builder.revolve(segments.max(3), &profile);
+        builder.build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Signed volume from the divergence theorem, positive when triangles face outwards.
+    fn volume(model: &Model) -> f32 {
+        model
+            .indices
+            .chunks_exact(3)
+            .map(|t| {
+                let [a, b, c] = [0, 1, 2].map(|i| model.vertices[t[i] as usize].position);
+                a.dot(&b.cross(&c)) / 6.0
+            })
+            .sum()
+    }
+
+    /// True when every edge between welded positions is shared by exactly two triangles.
+    fn is_closed(model: &Model) -> bool {
+        let key = |i: u32| {
+            let p = model.vertices[i as usize].position;
+            // round away the error of computing the same point twice
+            [p.x, p.y, p.z].map(|c| (c * 1e4).round() as i64)
+        };
+        let mut edges: HashMap<([i64; 3], [i64; 3]), i32> = HashMap::new();
+        for t in model.indices.chunks_exact(3) {
+            for i in 0..3 {
+                let (a, b) = (key(t[i]), key(t[(i + 1) % 3]));
+                // +1 for one direction, -1 for the other, consistent winding cancels out
+                if a < b {
+                    *edges.entry((a, b)).or_default() += 1;
+                } else {
+                    *edges.entry((b, a)).or_default() -= 1;
+                }
+            }
+        }
+        edges.values().all(|&uses| uses == 0)
+    }
+
+    fn assert_well_formed(model: &Model) {
+        assert!(!model.indices.is_empty());
+        for vertex in &model.vertices {
+            assert!((vertex.normal.sqrt() - 1.0).abs() < 1e-4, "{:?}", vertex);
+            assert!(vertex.uv.y >= 0.0 && vertex.uv.y <= 1.0, "{:?}", vertex);
+        }
+        // the winding agrees with the normals
+        for t in model.indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| model.vertices[t[i] as usize]);
+            let face = (b.position - a.position).cross(&(c.position - a.position));
+            assert!(face.dot(&(a.normal + b.normal + c.normal)) > 0.0);
+        }
+    }
+
+    #[test]
+    fn test_closed_shapes_have_the_right_volume() {
+        let (r, h) = (0.5, 2.0);
+        let shapes = [
+            ("cube", Model::cube(2.0, 3), 8.0),
+            (
+                "uv_sphere",
+                Model::uv_sphere(r, 64, 32),
+                4.0 / 3.0 * PI * r.powi(3),
+            ),
+            (
+                "ico_sphere",
+                Model::ico_sphere(r, 4),
+                4.0 / 3.0 * PI * r.powi(3),
+            ),
+            ("cylinder", Model::cylinder(r, h, 64, 2), PI * r * r * h),
+            ("cone", Model::cone(r, h, 64, 2), PI * r * r * h / 3.0),
+            ("torus", Model::torus(1.0, r, 64, 32), 2.0 * PI * PI * r * r),
+            (
+                "capsule",
+                Model::capsule(r, h, 64, 16),
+                PI * r * r * h + 4.0 / 3.0 * PI * r.powi(3),
+            ),
+        ];
+        for (name, model, expected) in shapes {
+            assert_well_formed(&model);
+            assert!(is_closed(&model), "{} has holes", name);
+            let volume = volume(&model);
+            assert!(
+                (volume - expected).abs() / expected < 0.02,
+                "{}: {} instead of {}",
+                name,
+                volume,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_plane_and_subdivisions() {
+        let plane = Model::plane(2.0, 4);
+        assert_well_formed(&plane);
+        assert_eq!(plane.vertices.len(), 25);
+        assert_eq!(plane.indices.len(), 4 * 4 * 6);
+        assert!(plane
+            .vertices
+            .iter()
+            .all(|v| v.normal == vec3(0.0, 1.0, 0.0)));
+        assert!(plane
+            .vertices
+            .iter()
+            .all(|v| v.uv.x >= 0.0 && v.uv.x <= 1.0));
+
+        assert_eq!(Model::cube(1.0, 2).indices.len(), 6 * 2 * 2 * 6);
+        // the poles of a uv sphere are fans, not quads
+        assert_eq!(
+            Model::uv_sphere(1.0, 8, 4).indices.len(),
+            (8 * 2 + 8 * 2 * 2) * 3
+        );
+        assert_eq!(Model::ico_sphere(1.0, 2).indices.len(), 20 * 16 * 3);
+    }
+}
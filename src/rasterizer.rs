@@ -0,0 +1,83 @@
+/// Which triangle facing is discarded before rasterization.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CullMode {
+    #[default]
+    None,
+    Front,
+    Back,
+}
+
+/// Winding order of front facing triangles, measured in the coordinates passed to the draw calls.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FrontFace {
+    #[default]
+    Ccw,
+    Cw,
+}
+
+/// How a list of vertices is assembled into primitives.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PrimitiveTopology {
+    PointList,
+    /// Every two vertices form a separate line.
+    LineList,
+    /// Every vertex after the first connects to the previous one.
+    LineStrip,
+    /// Every three vertices form a separate triangle.
+    #[default]
+    TriangleList,
+    /// Every vertex after the second forms a triangle with the two before it. The winding of
+    /// every other triangle is flipped so that all of them face the same way.
+    TriangleStrip,
+    /// Every vertex after the second forms a triangle with the previous one and the first.
+    TriangleFan,
+}
+
+/// Index that ends the current strip or fan in an indexed draw, the next index starts a new
+/// one. List topologies drop the incomplete primitive before it.
+pub const PRIMITIVE_RESTART_INDEX: u32 = u32::MAX;
+
+/// Fixed function state applied to every primitive after projection.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RasterizerState {
+    pub cull_mode: CullMode,
+    pub front_face: FrontFace,
+    /// Width and height in pixels of the squares drawn for points.
+    pub point_size: f32,
+    /// Added to the depth of every fragment before the depth test. Since larger depths are
+    /// closer, a small positive bias draws lines on top of the triangles they outline.
+    pub depth_bias: f32,
+    /// Whether fragments that pass the depth test store their depth. Off for backgrounds that
+    /// depth based effects should keep treating as empty, such as a skybox.
+    pub depth_write: bool,
+}
+
+impl Default for RasterizerState {
+    fn default() -> Self {
+        RasterizerState {
+            cull_mode: CullMode::None,
+            front_face: FrontFace::Ccw,
+            point_size: 1.0,
+            depth_bias: 0.0,
+            depth_write: true,
+        }
+    }
+}
+
+impl RasterizerState {
+    /// `signed_area` is positive for counter-clockwise triangles.
+    pub fn is_front_facing(&self, signed_area: f32) -> bool {
+        match self.front_face {
+            FrontFace::Ccw => signed_area > 0.0,
+            FrontFace::Cw => signed_area < 0.0,
+        }
+    }
+
+    pub fn is_culled(&self, front_facing: bool) -> bool {
+        match self.cull_mode {
+            CullMode::None => false,
+            CullMode::Front => front_facing,
+            CullMode::Back => !front_facing,
+        }
+    }
+}
@@ -1,13 +1,156 @@
-use crate::color::Color;
-use crate::math::{Vec2, Vec2u, Vec3};
+use crate::color::{Color, ColorSpace, LinearColor};
+use crate::line::{clip_line, clip_line_parameters};
+use crate::math::{Frustum, Mat4, Mat4x1, Vec2, Vec3, Vec4};
+use crate::model::{Model, Submesh, Vertex};
+use crate::rasterizer::{PrimitiveTopology, RasterizerState, PRIMITIVE_RESTART_INDEX};
+use crate::resample::{resample_linear, ResampleFilter};
+use crate::shader::{Fragment, Instance, Shader, Varying};
+use crate::simplify::{pixels_per_unit, LodChain};
+use crate::stencil::{StencilFaceState, StencilState};
 use crate::texture::Texture;
+use crate::tonemap::ToneMapping;
+use crate::viewport::{ScissorRect, Viewport};
+
+const MAX_SAMPLES: usize = 8;
+
+// primitives are clipped against w = W_CLIP_EPSILON, so nothing behind the eye is rasterized
+const W_CLIP_EPSILON: f32 = 1e-5;
+
+// triangles are clipped to this many times the viewport in x and y, far enough out that
+// clipping rarely adds corners and close enough that the edge functions keep their precision
+const GUARD_BAND: f32 = 4.0;
+
+const CLIP_PLANES: usize = 7;
+// points and lines skip the guard band, lines are clipped to the viewport in window space
+const DEPTH_CLIP_PLANES: usize = 3;
+
+/// Signed distances of a clip space position to the planes primitives are clipped against, it
+/// is inside where none is negative. After the w plane come z = w and z = -w, which the viewport
+/// maps to its max and min depth, then the guard band around the viewport in x and y.
+fn clip_distances(p: &Vec4) -> [f32; CLIP_PLANES] {
+    let guard = GUARD_BAND * p.w;
+    [
+        p.w - W_CLIP_EPSILON,
+        p.w - p.z,
+        p.w + p.z,
+        guard - p.x,
+        guard + p.x,
+        guard - p.y,
+        guard + p.y,
+    ]
+}
+
+/// Multisample anti-aliasing mode. Coverage and depth are tested per sample while shading runs
+/// once per pixel; call [`Renderer::resolve`] to average the samples into the visible pixels.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Msaa {
+    #[default]
+    Off,
+    X2,
+    X4,
+    X8,
+}
+
+// Standard Direct3D sample patterns, in pixels relative to the pixel center.
+const SAMPLES_1X: [Vec2; 1] = [Vec2::new(0.0, 0.0)];
+const SAMPLES_2X: [Vec2; 2] = [Vec2::new(0.25, 0.25), Vec2::new(-0.25, -0.25)];
+const SAMPLES_4X: [Vec2; 4] = [
+    Vec2::new(-0.125, -0.375),
+    Vec2::new(0.375, -0.125),
+    Vec2::new(-0.375, 0.125),
+    Vec2::new(0.125, 0.375),
+];
+const SAMPLES_8X: [Vec2; 8] = [
+    Vec2::new(0.0625, -0.1875),
+    Vec2::new(-0.0625, 0.1875),
+    Vec2::new(0.3125, 0.0625),
+    Vec2::new(-0.1875, -0.3125),
+    Vec2::new(-0.3125, 0.3125),
+    Vec2::new(-0.4375, -0.0625),
+    Vec2::new(0.1875, 0.4375),
+    Vec2::new(0.4375, -0.4375),
+];
+
+impl Msaa {
+    pub const fn sample_count(&self) -> usize {
+        match self {
+            Msaa::Off => 1,
+            Msaa::X2 => 2,
+            Msaa::X4 => 4,
+            Msaa::X8 => 8,
+        }
+    }
+
+    pub fn sample_positions(&self) -> &'static [Vec2] {
+        match self {
+            Msaa::Off => &SAMPLES_1X,
+            Msaa::X2 => &SAMPLES_2X,
+            Msaa::X4 => &SAMPLES_4X,
+            Msaa::X8 => &SAMPLES_8X,
+        }
+    }
+}
+
+/// Output of the vertex stage, before the perspective divide.
+#[derive(Clone, Copy)]
+struct ClipVertex<V> {
+    position: Vec4,
+    varying: V,
+}
+
+impl<V: Varying> ClipVertex<V> {
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        ClipVertex {
+            position: self.position + (other.position - self.position) * t,
+            varying: self.varying.lerp(&other.varying, t),
+        }
+    }
+}
+
+/// Transforms positions and fills them with one color, used by [`Renderer::draw_primitives`].
+struct FlatShader<'a> {
+    transform: &'a Mat4,
+    color: Color,
+}
+
+impl Shader for FlatShader<'_> {
+    type Vertex = Vec3;
+    type Varying = ();
+
+    fn vertex(&self, vertex: &Vec3, _instance_index: u32, _instance: &Instance) -> (Vec4, ()) {
+        (
+            self.transform.mul_mat41(&Mat4x1::from(*vertex)).to_vec4(),
+            (),
+        )
+    }
+
+    fn fragment(&self, _varying: &(), _fragment: &Fragment) -> Option<Color> {
+        Some(self.color)
+    }
+}
 
 pub struct Renderer {
     width: u32,
     height: u32,
     flip_y: bool,
+    msaa: Msaa,
+    // supersampling factor, the internal target is `width * supersampling` wide
+    supersampling: u32,
+    resolve_filter: ResampleFilter,
     pixels: Vec<Color>,
-    z_buffer: Vec<i32>,
+    // per-sample buffers of the internal target, `sample_count` consecutive entries per pixel.
+    // A direct target keeps its unprocessed frame here after a post process until the next clear
+    color_samples: Vec<Color>,
+    // replaces `color_samples` with linear colors when tone mapping is set
+    hdr_samples: Vec<LinearColor>,
+    tone_mapping: Option<ToneMapping>,
+    color_space: ColorSpace,
+    depth_samples: Vec<f32>,
+    stencil_samples: Vec<u8>,
+    stencil_state: StencilState,
+    rasterizer_state: RasterizerState,
+    viewport: Viewport,
+    scissor: Option<ScissorRect>,
 }
 
 impl Renderer {
@@ -16,8 +159,20 @@ impl Renderer {
             width,
             height,
             flip_y,
+            msaa: Msaa::Off,
+            supersampling: 1,
+            resolve_filter: ResampleFilter::Box,
             pixels: vec![Color::WHITE; (width * height) as usize],
-            z_buffer: vec![-i32::MAX; (width * height) as usize],
+            color_samples: vec![],
+            hdr_samples: vec![],
+            tone_mapping: None,
+            color_space: ColorSpace::Srgb,
+            depth_samples: vec![-f32::MAX; (width * height) as usize],
+            stencil_samples: vec![0; (width * height) as usize],
+            stencil_state: StencilState::default(),
+            rasterizer_state: RasterizerState::default(),
+            viewport: Viewport::new(0.0, 0.0, width as f32, height as f32),
+            scissor: None,
         }
     }
 
@@ -29,10 +184,203 @@ impl Renderer {
         self.height
     }
 
+    /// Whether window y grows downwards in [`Renderer::pixels`], with the first row at the
+    /// bottom of the window.
+    pub fn flip_y(&self) -> bool {
+        self.flip_y
+    }
+
+    pub fn msaa(&self) -> Msaa {
+        self.msaa
+    }
+
+    /// Changes the MSAA mode. The sample buffers are reallocated, so clear before drawing.
+    pub fn set_msaa(&mut self, msaa: Msaa) {
+        self.msaa = msaa;
+        self.allocate_samples();
+    }
+
+    pub fn supersampling(&self) -> u32 {
+        self.supersampling
+    }
+
+    pub fn resolve_filter(&self) -> ResampleFilter {
+        self.resolve_filter
+    }
+
+    /// Renders internally at `factor` times the output resolution. Coordinates passed to the draw
+    /// calls stay in output pixels, and [`Renderer::resolve`] downscales with `filter`.
+    /// The sample buffers are reallocated, so clear before drawing.
+    pub fn set_supersampling(&mut self, factor: u32, filter: ResampleFilter) {
+        assert!(factor > 0, "supersampling factor must be at least 1");
+        self.supersampling = factor;
+        self.resolve_filter = filter;
+        self.allocate_samples();
+    }
+
+    pub fn color_space(&self) -> ColorSpace {
+        self.color_space
+    }
+
+    /// Sets the encoding of `pixels`. Fragment colors are linear and get encoded when they are
+    /// written, or on resolve with tone mapping. Defaults to sRGB.
+    pub fn set_color_space(&mut self, color_space: ColorSpace) {
+        self.color_space = color_space;
+    }
+
+    pub fn tone_mapping(&self) -> Option<&ToneMapping> {
+        self.tone_mapping.as_ref()
+    }
+
+    /// With `Some`, fragments are stored as linear `f32` colors that may exceed 1, and
+    /// [`Renderer::resolve`] exposes and tone maps them into `pixels`. `None` returns to an
+    /// 8-bit color buffer. The sample buffers are reallocated, so clear before drawing.
+    pub fn set_tone_mapping(&mut self, tone_mapping: Option<ToneMapping>) {
+        let reallocate = self.tone_mapping.is_some() != tone_mapping.is_some();
+        self.tone_mapping = tone_mapping;
+        if reallocate {
+            self.allocate_samples();
+        }
+    }
+
+    /// Linear colors of the high dynamic range target, `sample_count` consecutive entries per
+    /// pixel of the internal target. Empty without tone mapping.
+    pub fn hdr_samples(&self) -> &[LinearColor] {
+        &self.hdr_samples
+    }
+
+    fn target_width(&self) -> u32 {
+        self.width * self.supersampling
+    }
+
+    fn target_height(&self) -> u32 {
+        self.height * self.supersampling
+    }
+
+    /// True when the target needs no resolve, one 8-bit sample per output pixel.
+    fn is_direct_target(&self) -> bool {
+        self.msaa == Msaa::Off && self.supersampling == 1 && self.tone_mapping.is_none()
+    }
+
+    /// True when fragments land in `pixels` directly, without a resolve.
+    fn is_direct(&self) -> bool {
+        self.is_direct_target() && self.color_samples.is_empty()
+    }
+
+    fn allocate_samples(&mut self) {
+        let sample_len =
+            (self.target_width() * self.target_height()) as usize * self.msaa.sample_count();
+        self.color_samples = if self.is_direct_target() || self.tone_mapping.is_some() {
+            vec![]
+        } else {
+            vec![Color::WHITE; sample_len]
+        };
+        self.hdr_samples = if self.tone_mapping.is_some() {
+            vec![LinearColor::WHITE; sample_len]
+        } else {
+            vec![]
+        };
+        self.depth_samples = vec![-f32::MAX; sample_len];
+        self.stencil_samples = vec![0; sample_len];
+    }
+
+    pub fn stencil_state(&self) -> &StencilState {
+        &self.stencil_state
+    }
+
+    /// Sets the stencil test and operations used by the following triangle draws.
+    /// Which face state applies is decided by the front face winding of the rasterizer state.
+    pub fn set_stencil_state(&mut self, state: StencilState) {
+        self.stencil_state = state;
+    }
+
+    /// Returns the stencil value of the first sample of a pixel.
+    pub fn stencil(&self, x: u32, y: u32) -> u8 {
+        assert!(
+            x < self.width && y < self.height,
+            "pixel ({}, {}) is outside of the {}x{} target",
+            x,
+            y,
+            self.width,
+            self.height
+        );
+        let factor = self.supersampling;
+        let index = self.pixel_index(x * factor, y * factor);
+        self.stencil_samples[index * self.msaa.sample_count()]
+    }
+
+    pub fn clear_stencil(&mut self, value: u8) {
+        self.stencil_samples.fill(value);
+    }
+
+    pub fn rasterizer_state(&self) -> &RasterizerState {
+        &self.rasterizer_state
+    }
+
+    /// Sets the culling and winding order used by the following triangle draws.
+    pub fn set_rasterizer_state(&mut self, state: RasterizerState) {
+        self.rasterizer_state = state;
+    }
+
+    pub fn viewport(&self) -> &Viewport {
+        &self.viewport
+    }
+
+    /// Sets the viewport, in output pixels. Defaults to the whole target.
+    pub fn set_viewport(&mut self, viewport: Viewport) {
+        self.viewport = viewport;
+    }
+
+    pub fn scissor(&self) -> Option<&ScissorRect> {
+        self.scissor.as_ref()
+    }
+
+    /// Restricts every following write to `scissor`, in output pixels. `None` disables it.
+    pub fn set_scissor(&mut self, scissor: Option<ScissorRect>) {
+        self.scissor = scissor;
+    }
+
+    /// Pixels that may be written: the viewport intersected with the scissor rectangle.
+    pub(crate) fn clip_rect(&self) -> ScissorRect {
+        let rect = self.viewport.pixel_rect(self.width, self.height);
+        match &self.scissor {
+            Some(scissor) => rect.intersect(scissor),
+            None => rect,
+        }
+    }
+
     pub fn pixels(&self) -> &[Color] {
         &self.pixels
     }
 
+    pub(crate) fn pixels_mut(&mut self) -> &mut [Color] {
+        &mut self.pixels
+    }
+
+    /// Depth of every output pixel in the order of [`Renderer::pixels`], the closest of the
+    /// samples covering it. Larger is closer, `-f32::MAX` where nothing was drawn.
+    pub fn resolved_depth(&self) -> Vec<f32> {
+        let sample_count = self.msaa.sample_count();
+        let factor = self.supersampling;
+        let target_width = self.target_width();
+        let mut depth = vec![-f32::MAX; (self.width * self.height) as usize];
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let mut closest = -f32::MAX;
+                for ty in y * factor..(y + 1) * factor {
+                    for tx in x * factor..(x + 1) * factor {
+                        let base = (ty * target_width + tx) as usize * sample_count;
+                        for z in &self.depth_samples[base..base + sample_count] {
+                            closest = closest.max(*z);
+                        }
+                    }
+                }
+                depth[(y * self.width + x) as usize] = closest;
+            }
+        }
+        depth
+    }
+
     pub fn rgba_bytes(&self) -> &[u8] {
         unsafe {
             std::slice::from_raw_parts(
@@ -42,25 +390,166 @@ impl Renderer {
         }
     }
 
+    /// Index of a pixel of the internal target, which is the output itself unless supersampling.
+    fn pixel_index(&self, x: u32, y: u32) -> usize {
+        let y = if self.flip_y {
+            self.target_height() - y - 1
+        } else {
+            y
+        };
+        (y * self.target_width() + x) as usize
+    }
+
+    /// Writes every sample of a pixel, `color` is sRGB encoded like in [`Renderer::clear`].
     pub fn draw_pixel(&mut self, x: u32, y: u32, color: Color) {
-        if x >= self.width || y >= self.height {
+        let linear = ColorSpace::Srgb.decode(color);
+        self.update_pixel(x, y, |_| linear);
+    }
+
+    /// Replaces every sample of an output pixel with `f` applied to its current color, in
+    /// linear light.
+    pub(crate) fn update_pixel<F>(&mut self, x: u32, y: u32, f: F)
+    where
+        F: Fn(LinearColor) -> LinearColor,
+    {
+        if !self.clip_rect().contains(x, y) {
             return;
         }
-        let y = if self.flip_y { self.height - y - 1 } else { y };
-        self.pixels[(y * self.width + x) as usize] = color;
+        let color_space = self.color_space;
+        let update =
+            |color: &mut Color| *color = color_space.encode(&f(color_space.decode(*color)));
+        if self.is_direct() {
+            let index = self.pixel_index(x, y);
+            update(&mut self.pixels[index]);
+            return;
+        }
+        let sample_count = self.msaa.sample_count();
+        let factor = self.supersampling;
+        for ty in y * factor..(y + 1) * factor {
+            for tx in x * factor..(x + 1) * factor {
+                let index = self.pixel_index(tx, ty);
+                let range = index * sample_count..(index + 1) * sample_count;
+                if self.tone_mapping.is_some() {
+                    for sample in &mut self.hdr_samples[range] {
+                        *sample = f(*sample);
+                    }
+                } else {
+                    self.color_samples[range].iter_mut().for_each(update);
+                }
+            }
+        }
     }
 
+    /// Fills every buffer, `color` is sRGB encoded like the colors returned by
+    /// [`Shader::fragment`].
     pub fn clear(&mut self, color: Color) {
-        self.pixels = vec![color; (self.width * self.height) as usize];
-        self.z_buffer = vec![-i32::MAX; (self.width * self.height) as usize];
+        let linear = ColorSpace::Srgb.decode(color);
+        let color = self.color_space.encode(&linear);
+        if self.is_direct_target() {
+            // draw into the pixels again after a post process
+            self.color_samples = vec![];
+        }
+        self.pixels.fill(color);
+        self.color_samples.fill(color);
+        self.hdr_samples.fill(linear);
+        self.depth_samples.fill(-f32::MAX);
+        self.stencil_samples.fill(0);
+    }
+
+    /// Averages the MSAA samples of every pixel, tone maps high dynamic range colors and
+    /// downscales the supersampled target into `pixels`. Does nothing when none of them is
+    /// enabled, except to undo a [`Renderer::post_process`], which resolves with post effects.
+    #[profiling::function]
+    pub fn resolve(&mut self) {
+        if self.is_direct() {
+            return;
+        }
+        let resolved = self.resolve_linear();
+        self.pixels = resolved.iter().map(|c| self.encode_output(c)).collect();
+    }
+
+    /// Averages the samples of every pixel and downscales the supersampled target, both in
+    /// linear light. Returns linear colors in the order of [`Renderer::pixels`], before tone
+    /// mapping.
+    pub(crate) fn resolve_linear(&self) -> Vec<LinearColor> {
+        if self.is_direct() {
+            return self
+                .pixels
+                .iter()
+                .map(|c| self.color_space.decode(*c))
+                .collect();
+        }
+        let sample_count = self.msaa.sample_count();
+        let average = |samples: &mut dyn Iterator<Item = LinearColor>| {
+            samples.fold(LinearColor::TRANSPARENT, |sum, s| sum + s) / sample_count as f32
+        };
+        let averaged: Vec<LinearColor> = if self.tone_mapping.is_some() {
+            self.hdr_samples
+                .chunks_exact(sample_count)
+                .map(|samples| average(&mut samples.iter().copied()))
+                .collect()
+        } else {
+            self.color_samples
+                .chunks_exact(sample_count)
+                .map(|samples| average(&mut samples.iter().map(|c| self.color_space.decode(*c))))
+                .collect()
+        };
+        resample_linear(
+            &averaged,
+            self.target_width(),
+            self.target_height(),
+            self.width,
+            self.height,
+            self.resolve_filter,
+        )
+    }
+
+    /// Tone maps a resolved color when the target has a high dynamic range, or clamps it, into
+    /// the 0..1 range of the display.
+    pub(crate) fn tone_map(&self, color: &LinearColor) -> LinearColor {
+        match &self.tone_mapping {
+            Some(tone_mapping) => {
+                let rgb = tone_mapping.apply(&color.to_vec3());
+                LinearColor::from_vec3(&rgb).with_alpha(color.a)
+            }
+            None => color.saturate(),
+        }
+    }
+
+    /// Tone maps a resolved color and encodes it into the color space of the target.
+    pub(crate) fn encode_output(&self, color: &LinearColor) -> Color {
+        self.color_space.encode(&self.tone_map(color))
     }
 
-    pub fn draw_line(&mut self, v0: &Vec2u, v1: &Vec2u, color: Color) {
+    /// Encodes tone mapped colors into `pixels`. A direct target first moves its frame into the
+    /// color samples, so that the next resolve starts from the unprocessed frame like with MSAA.
+    pub(crate) fn write_display(&mut self, colors: &[LinearColor]) {
+        if self.is_direct() {
+            self.color_samples = std::mem::take(&mut self.pixels);
+        }
+        self.pixels = colors.iter().map(|c| self.color_space.encode(c)).collect();
+    }
+
+    /// Draws a one pixel wide line with Bresenham's algorithm. The part of the line outside of
+    /// the viewport and scissor rectangle is clipped away.
+    pub fn draw_line(&mut self, v0: &Vec2, v1: &Vec2, color: Color) {
+        let clip = self.clip_rect();
+        if clip.is_empty() {
+            return;
+        }
+        let min = Vec2::new(clip.x as f32, clip.y as f32);
+        let max = Vec2::new(
+            (clip.x + clip.width) as f32 - 1e-3,
+            (clip.y + clip.height) as f32 - 1e-3,
+        );
+        let Some((v0, v1)) = clip_line(v0, v1, &min, &max) else {
+            return;
+        };
         let mut steep = false;
-        let mut x0 = v0.x as i32;
-        let mut x1 = v1.x as i32;
-        let mut y0 = v0.y as i32;
-        let mut y1 = v1.y as i32;
+        let mut x0 = v0.x.floor() as i32;
+        let mut x1 = v1.x.floor() as i32;
+        let mut y0 = v0.y.floor() as i32;
+        let mut y1 = v1.y.floor() as i32;
         if (x0 - x1).abs() < (y0 - y1).abs() {
             steep = true;
             std::mem::swap(&mut x0, &mut y0);
@@ -116,44 +605,8 @@ impl Renderer {
 
     #[profiling::function]
     pub fn draw_triangle(&mut self, t0: &Vec3, t1: &Vec3, t2: &Vec3, color: Color) {
-        let mut bbox_min = Vec2 {
-            x: f32::MAX,
-            y: f32::MAX,
-        };
-        let mut bbox_max = Vec2 {
-            x: -f32::MAX,
-            y: -f32::MAX,
-        };
-        let clamp = Vec2 {
-            x: self.width as f32 - 1.0,
-            y: self.height as f32 - 1.0,
-        };
-        let pts = [t0, t1, t2];
-        for pt in pts {
-            bbox_min.x = bbox_min.x.min(pt.x).max(0.0);
-            bbox_min.y = bbox_min.y.min(pt.y).max(0.0);
-            bbox_max.x = bbox_max.x.max(pt.x).min(clamp.x);
-            bbox_max.y = bbox_max.y.max(pt.y).min(clamp.y);
-        }
-        for x in bbox_min.x as u32..=bbox_max.x as u32 {
-            for y in bbox_min.y as u32..=bbox_max.y as u32 {
-                let p = Vec3 {
-                    x: x as f32,
-                    y: y as f32,
-                    z: 0.0,
-                };
-                let bc_screen = Renderer::barycentric(t0, t1, t2, &p);
-
-                if bc_screen.x < 0.0 || bc_screen.y < 0.0 || bc_screen.z < 0.0 {
-                    continue;
-                }
-                let index = (y * self.width + x) as usize;
-                if self.z_buffer[index] < p.z as i32 {
-                    self.z_buffer[index] = p.z as i32;
-                    self.draw_pixel(x, y, color);
-                }
-            }
-        }
+        let color = ColorSpace::Srgb.decode(color);
+        self.rasterize_triangle([t0, t1, t2], |_, _| Some(color));
     }
 
     #[allow(clippy::too_many_arguments)]
@@ -169,6 +622,438 @@ impl Renderer {
         diffuse: &Texture,
         intensity: f32,
     ) {
+        self.rasterize_triangle([t0, t1, t2], |bc_screen, _| {
+            //  interpolate uv coordinates using barycentric coordinates
+            let uv = *uv0 * bc_screen.x + *uv1 * bc_screen.y + *uv2 * bc_screen.z;
+            Some(diffuse.sample(&uv) * Vec3::splat(intensity))
+        });
+    }
+
+    /// Draws `positions` as the primitives of `topology` with a flat color. Positions are
+    /// transformed by `transform` to clip space, clipped against the w = 0, near and far planes,
+    /// mapped through the viewport and depth tested, so lines and points are hidden behind
+    /// triangles like any other geometry.
+    #[profiling::function]
+    pub fn draw_primitives(
+        &mut self,
+        positions: &[Vec3],
+        topology: PrimitiveTopology,
+        transform: &Mat4,
+        color: Color,
+    ) {
+        let indices: Vec<u32> = (0..positions.len() as u32).collect();
+        let shader = FlatShader { transform, color };
+        self.draw_indexed(&shader, positions, &indices, topology, 0);
+    }
+
+    /// Assembles `indices` into the primitives of `topology` and draws them with `shader`.
+    /// `base_vertex` is added to every index before fetching from `vertices`, and
+    /// [`PRIMITIVE_RESTART_INDEX`] starts a new strip or fan. Varyings are interpolated with
+    /// perspective correction. Panics before drawing anything when an index plus `base_vertex`
+    /// lies outside of `vertices`.
+    ///
+    /// Transformed vertices are kept for the whole draw, so a vertex shared by several
+    /// primitives runs through the vertex stage only once.
+    #[profiling::function]
+    pub fn draw_indexed<S: Shader>(
+        &mut self,
+        shader: &S,
+        vertices: &[S::Vertex],
+        indices: &[u32],
+        topology: PrimitiveTopology,
+        base_vertex: i32,
+    ) {
+        self.draw_indexed_instanced(
+            shader,
+            vertices,
+            indices,
+            topology,
+            base_vertex,
+            &[Instance::default()],
+        );
+    }
+
+    /// Draws the same indexed geometry once per element of `instances`, see
+    /// [`Renderer::draw_indexed`]. Within an instance every vertex runs through the vertex stage
+    /// once, no matter how many primitives share it.
+    #[profiling::function]
+    pub fn draw_indexed_instanced<S: Shader>(
+        &mut self,
+        shader: &S,
+        vertices: &[S::Vertex],
+        indices: &[u32],
+        topology: PrimitiveTopology,
+        base_vertex: i32,
+        instances: &[Instance],
+    ) {
+        for &index in indices.iter().filter(|&&i| i != PRIMITIVE_RESTART_INDEX) {
+            let vertex_index = index as i64 + base_vertex as i64;
+            assert!(
+                (0..vertices.len() as i64).contains(&vertex_index),
+                "index {} with base vertex {} is outside of the {} vertices of the draw",
+                index,
+                base_vertex,
+                vertices.len()
+            );
+        }
+        let mut cache: Vec<Option<ClipVertex<S::Varying>>> = vec![None; vertices.len()];
+        for (instance_index, instance) in instances.iter().enumerate() {
+            cache.fill(None);
+            self.assemble_primitives(shader, indices, topology, |index| {
+                let vertex_index = (index as i64 + base_vertex as i64) as usize;
+                *cache[vertex_index].get_or_insert_with(|| {
+                    let (position, varying) =
+                        shader.vertex(&vertices[vertex_index], instance_index as u32, instance);
+                    ClipVertex { position, varying }
+                })
+            });
+        }
+    }
+
+    /// Draws the triangles of `model` with `shader`, skipping the whole model or single
+    /// submeshes when their bounds are outside of the frustum of `model_view_projection`.
+    /// Returns the number of submeshes drawn.
+    #[profiling::function]
+    pub fn draw_model<S: Shader<Vertex = Vertex>>(
+        &mut self,
+        shader: &S,
+        model: &Model,
+        model_view_projection: &Mat4,
+    ) -> usize {
+        let frustum = Frustum::from_matrix(model_view_projection);
+        if !frustum.intersects_aabb(&model.bounds) {
+            return 0;
+        }
+        self.draw_submeshes(shader, model, &model.indices, &model.submeshes, &frustum)
+    }
+
+    /// Draws `model` at the coarsest level of `lods` whose error stays under `max_pixel_error`
+    /// pixels, measured at the corner of the model bounds that projects largest. `lods` must
+    /// have been generated from `model`. Returns the level drawn, or `None` when the model is
+    /// outside of the frustum.
+    #[profiling::function]
+    pub fn draw_model_lod<S: Shader<Vertex = Vertex>>(
+        &mut self,
+        shader: &S,
+        model: &Model,
+        lods: &LodChain,
+        model_view_projection: &Mat4,
+        max_pixel_error: f32,
+    ) -> Option<usize> {
+        let frustum = Frustum::from_matrix(model_view_projection);
+        if !frustum.intersects_aabb(&model.bounds) {
+            return None;
+        }
+        let (min, max) = (model.bounds.min, model.bounds.max);
+        let pixels_per_unit = (0..8)
+            .map(|corner| Vec3 {
+                x: if corner & 1 == 0 { min.x } else { max.x },
+                y: if corner & 2 == 0 { min.y } else { max.y },
+                z: if corner & 4 == 0 { min.z } else { max.z },
+            })
+            .map(|corner| pixels_per_unit(model_view_projection, &corner, &self.viewport))
+            .fold(0.0, f32::max);
+        let index = lods.select(pixels_per_unit, max_pixel_error);
+        let level = &lods.levels[index];
+        self.draw_submeshes(shader, model, &level.indices, &level.submeshes, &frustum);
+        Some(index)
+    }
+
+    /// Draws the ranges of `indices` given by `submeshes`, skipping those outside of `frustum`
+    /// when there is more than one. Returns the number of submeshes drawn.
+    fn draw_submeshes<S: Shader<Vertex = Vertex>>(
+        &mut self,
+        shader: &S,
+        model: &Model,
+        indices: &[u32],
+        submeshes: &[Submesh],
+        frustum: &Frustum,
+    ) -> usize {
+        let mut drawn = 0;
+        for submesh in submeshes {
+            if submeshes.len() > 1 && !frustum.intersects_aabb(&submesh.bounds) {
+                continue;
+            }
+            self.draw_indexed(
+                shader,
+                &model.vertices,
+                &indices[submesh.index_range()],
+                PrimitiveTopology::TriangleList,
+                0,
+            );
+            drawn += 1;
+        }
+        drawn
+    }
+
+    /// Splits `indices` at restart indices and draws the primitives of `topology`, `fetch`
+    /// returns the transformed vertex for an index.
+    fn assemble_primitives<S, F>(
+        &mut self,
+        shader: &S,
+        indices: &[u32],
+        topology: PrimitiveTopology,
+        mut fetch: F,
+    ) where
+        S: Shader,
+        F: FnMut(u32) -> ClipVertex<S::Varying>,
+    {
+        for primitives in indices.split(|&index| index == PRIMITIVE_RESTART_INDEX) {
+            match topology {
+                PrimitiveTopology::PointList => {
+                    for &index in primitives {
+                        self.draw_point_clip(shader, &fetch(index));
+                    }
+                }
+                PrimitiveTopology::LineList => {
+                    for line in primitives.chunks_exact(2) {
+                        self.draw_line_clip(shader, &fetch(line[0]), &fetch(line[1]));
+                    }
+                }
+                PrimitiveTopology::LineStrip => {
+                    for line in primitives.windows(2) {
+                        self.draw_line_clip(shader, &fetch(line[0]), &fetch(line[1]));
+                    }
+                }
+                PrimitiveTopology::TriangleList => {
+                    for triangle in primitives.chunks_exact(3) {
+                        let triangle = [triangle[0], triangle[1], triangle[2]].map(&mut fetch);
+                        self.draw_triangle_clip(shader, &triangle);
+                    }
+                }
+                PrimitiveTopology::TriangleStrip => {
+                    for (i, triangle) in primitives.windows(3).enumerate() {
+                        let triangle = if i % 2 == 0 {
+                            [triangle[0], triangle[1], triangle[2]]
+                        } else {
+                            [triangle[1], triangle[0], triangle[2]]
+                        };
+                        self.draw_triangle_clip(shader, &triangle.map(&mut fetch));
+                    }
+                }
+                PrimitiveTopology::TriangleFan => {
+                    let Some(&first) = primitives.first() else {
+                        continue;
+                    };
+                    for pair in primitives[1..].windows(2) {
+                        let triangle = [first, pair[0], pair[1]].map(&mut fetch);
+                        self.draw_triangle_clip(shader, &triangle);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Perspective divide followed by the viewport transform.
+    fn clip_to_window(&self, p: &Vec4) -> Vec3 {
+        let ndc = Vec3 {
+            x: p.x / p.w,
+            y: p.y / p.w,
+            z: p.z / p.w,
+        };
+        self.viewport.to_window(&ndc)
+    }
+
+    fn draw_point_clip<S: Shader>(&mut self, shader: &S, p: &ClipVertex<S::Varying>) {
+        if clip_distances(&p.position)[..DEPTH_CLIP_PLANES]
+            .iter()
+            .any(|d| *d < 0.0)
+        {
+            return;
+        }
+        let center = self.clip_to_window(&p.position);
+        let fragment = Fragment {
+            position: center,
+            front_facing: true,
+        };
+        let Some(color) = shader.fragment_linear(&p.varying, &fragment) else {
+            return;
+        };
+        let size = self.rasterizer_state.point_size.round().max(1.0);
+        let x0 = (center.x - size / 2.0).round();
+        let y0 = (center.y - size / 2.0).round();
+        let clip = self.clip_rect();
+        for y in y0 as i64..(y0 + size) as i64 {
+            for x in x0 as i64..(x0 + size) as i64 {
+                if x >= 0 && y >= 0 && clip.contains(x as u32, y as u32) {
+                    self.write_fragment(x as u32, y as u32, center.z, color);
+                }
+            }
+        }
+    }
+
+    fn draw_line_clip<S: Shader>(
+        &mut self,
+        shader: &S,
+        a: &ClipVertex<S::Varying>,
+        b: &ClipVertex<S::Varying>,
+    ) {
+        let (mut a, mut b) = (*a, *b);
+        for plane in 0..DEPTH_CLIP_PLANES {
+            let da = clip_distances(&a.position)[plane];
+            let db = clip_distances(&b.position)[plane];
+            if da < 0.0 && db < 0.0 {
+                return;
+            }
+            if da < 0.0 {
+                a = b.lerp(&a, db / (db - da));
+            } else if db < 0.0 {
+                b = a.lerp(&b, da / (da - db));
+            }
+        }
+        let (inv_wa, inv_wb) = (1.0 / a.position.w, 1.0 / b.position.w);
+        let wa = self.clip_to_window(&a.position);
+        let wb = self.clip_to_window(&b.position);
+
+        let clip = self.clip_rect();
+        if clip.is_empty() {
+            return;
+        }
+        let min = Vec2::new(clip.x as f32, clip.y as f32);
+        let max = Vec2::new(
+            (clip.x + clip.width) as f32 - 1e-3,
+            (clip.y + clip.height) as f32 - 1e-3,
+        );
+        let Some((t0, t1)) =
+            clip_line_parameters(&Vec2::new(wa.x, wa.y), &Vec2::new(wb.x, wb.y), &min, &max)
+        else {
+            return;
+        };
+        // window space depth is affine along the line, so a plain lerp is perspective correct
+        let p0 = wa + (wb - wa) * t0;
+        let p1 = wa + (wb - wa) * t1;
+        let steps = (p1.x - p0.x).abs().max((p1.y - p0.y).abs()).ceil().max(1.0) as u32;
+        for i in 0..=steps {
+            let t = t0 + (t1 - t0) * (i as f32 / steps as f32);
+            let p = wa + (wb - wa) * t;
+            // varyings are affine in clip space, not in window space
+            let t_clip = t * inv_wb / ((1.0 - t) * inv_wa + t * inv_wb);
+            let varying = a.varying.lerp(&b.varying, t_clip);
+            let fragment = Fragment {
+                position: p,
+                front_facing: true,
+            };
+            if let Some(color) = shader.fragment_linear(&varying, &fragment) {
+                self.write_fragment(p.x as u32, p.y as u32, p.z, color);
+            }
+        }
+    }
+
+    fn draw_triangle_clip<S: Shader>(&mut self, shader: &S, pts: &[ClipVertex<S::Varying>; 3]) {
+        // Sutherland-Hodgman against one clip plane after the other, every plane adds at most
+        // one corner to the polygon
+        let mut polygon: Vec<ClipVertex<S::Varying>> = pts.to_vec();
+        for plane in 0..CLIP_PLANES {
+            let input = std::mem::replace(&mut polygon, Vec::with_capacity(CLIP_PLANES + 3));
+            for (i, current) in input.iter().enumerate() {
+                let next = &input[(i + 1) % input.len()];
+                let d_current = clip_distances(&current.position)[plane];
+                let d_next = clip_distances(&next.position)[plane];
+                if d_current >= 0.0 {
+                    polygon.push(*current);
+                }
+                if (d_current >= 0.0) != (d_next >= 0.0) {
+                    polygon.push(current.lerp(next, d_current / (d_current - d_next)));
+                }
+            }
+            if polygon.len() < 3 {
+                return;
+            }
+        }
+        let window: Vec<Vec3> = polygon
+            .iter()
+            .map(|p| self.clip_to_window(&p.position))
+            .collect();
+        for i in 1..window.len() - 1 {
+            let corners = [0, i, i + 1];
+            let inv_w = corners.map(|c| 1.0 / polygon[c].position.w);
+            let varyings = corners.map(|c| &polygon[c].varying);
+            self.rasterize_triangle(corners.map(|c| &window[c]), |bc, fragment| {
+                let weights = Vec3 {
+                    x: bc.x * inv_w[0],
+                    y: bc.y * inv_w[1],
+                    z: bc.z * inv_w[2],
+                };
+                let weights = weights / (weights.x + weights.y + weights.z);
+                shader.fragment_linear(&Varying::interpolate(varyings, &weights), fragment)
+            });
+        }
+    }
+
+    /// Depth and stencil tests a fragment that covers every sample of an output pixel, as drawn
+    /// by points and lines. The pixel must lie inside the clip rectangle.
+    fn write_fragment(&mut self, x: u32, y: u32, z: f32, color: LinearColor) {
+        let z = z + self.rasterizer_state.depth_bias;
+        let stencil = self.stencil_state;
+        let stencil_enabled = stencil.is_enabled();
+        let sample_count = self.msaa.sample_count();
+        let factor = self.supersampling;
+        for ty in y * factor..(y + 1) * factor {
+            for tx in x * factor..(x + 1) * factor {
+                let base = self.pixel_index(tx, ty) * sample_count;
+                for index in base..base + sample_count {
+                    if stencil_enabled {
+                        self.write_sample_with_stencil(index, z, color, &stencil, &stencil.front);
+                    } else if self.depth_samples[index] < z {
+                        self.write_depth_sample(index, z);
+                        self.write_color_sample(index, color);
+                    }
+                }
+            }
+        }
+    }
+
+    fn write_sample_with_stencil(
+        &mut self,
+        index: usize,
+        z: f32,
+        color: LinearColor,
+        stencil: &StencilState,
+        face: &StencilFaceState,
+    ) {
+        let stored = self.stencil_samples[index];
+        let op = if !stencil.test(face, stored) {
+            face.fail_op
+        } else if self.depth_samples[index] < z {
+            self.write_depth_sample(index, z);
+            self.write_color_sample(index, color);
+            face.pass_op
+        } else {
+            face.depth_fail_op
+        };
+        self.stencil_samples[index] = stencil.update(op, stored);
+    }
+
+    fn write_depth_sample(&mut self, index: usize, z: f32) {
+        if self.rasterizer_state.depth_write {
+            self.depth_samples[index] = z;
+        }
+    }
+
+    fn write_color_sample(&mut self, index: usize, color: LinearColor) {
+        if self.tone_mapping.is_some() {
+            self.hdr_samples[index] = color;
+        } else if self.is_direct() {
+            self.pixels[index] = self.color_space.encode(&color);
+        } else {
+            self.color_samples[index] = self.color_space.encode(&color);
+        }
+    }
+
+    /// Rasterizes a screen space triangle, testing coverage and depth for every sample of the
+    /// current MSAA mode. `shade` runs once per pixel with the barycentric coordinates of the
+    /// pixel center, or of the first covered sample when the center lies outside the triangle.
+    fn rasterize_triangle<F>(&mut self, pts: [&Vec3; 3], mut shade: F)
+    where
+        F: FnMut(&Vec3, &Fragment) -> Option<LinearColor>,
+    {
+        // scale from output pixels to the internal target
+        let factor = self.supersampling as f32;
+        let [t0, t1, t2] = pts.map(|pt| Vec3 {
+            x: pt.x * factor,
+            y: pt.y * factor,
+            z: pt.z,
+        });
         let mut bbox_min = Vec2 {
             x: f32::MAX,
             y: f32::MAX,
@@ -177,38 +1062,119 @@ impl Renderer {
             x: -f32::MAX,
             y: -f32::MAX,
         };
-        let clamp = Vec2 {
-            x: self.width as f32 - 1.0,
-            y: self.height as f32 - 1.0,
+        let clip = self.clip_rect();
+        if clip.is_empty() {
+            return;
+        }
+        let clamp_min = Vec2 {
+            x: (clip.x * self.supersampling) as f32,
+            y: (clip.y * self.supersampling) as f32,
+        };
+        let clamp_max = Vec2 {
+            x: ((clip.x + clip.width) * self.supersampling) as f32 - 1.0,
+            y: ((clip.y + clip.height) * self.supersampling) as f32 - 1.0,
         };
-        let pts = [t0, t1, t2];
-        for pt in pts {
-            bbox_min.x = bbox_min.x.min(pt.x).max(0.0);
-            bbox_min.y = bbox_min.y.min(pt.y).max(0.0);
-            bbox_max.x = bbox_max.x.max(pt.x).min(clamp.x);
-            bbox_max.y = bbox_max.y.max(pt.y).min(clamp.y);
+        for pt in [t0, t1, t2] {
+            bbox_min.x = bbox_min.x.min(pt.x).max(clamp_min.x);
+            bbox_min.y = bbox_min.y.min(pt.y).max(clamp_min.y);
+            bbox_max.x = bbox_max.x.max(pt.x).min(clamp_max.x);
+            bbox_max.y = bbox_max.y.max(pt.y).min(clamp_max.y);
         }
 
+        let signed_area = (t1 - t0).cross(&(t2 - t0)).z;
+        let front_facing = self.rasterizer_state.is_front_facing(signed_area);
+        if self.rasterizer_state.is_culled(front_facing) {
+            return;
+        }
+        let stencil = self.stencil_state;
+        let stencil_enabled = stencil.is_enabled();
+        let stencil_face = *stencil.face(front_facing);
+
+        let depth_bias = self.rasterizer_state.depth_bias;
+        let positions = self.msaa.sample_positions();
+        let mut sample_depths = [0.0f32; MAX_SAMPLES];
         for x in bbox_min.x as u32..=bbox_max.x as u32 {
             for y in bbox_min.y as u32..=bbox_max.y as u32 {
-                let p = Vec3 {
-                    x: x as f32,
-                    y: y as f32,
+                let center = Vec3 {
+                    x: x as f32 + 0.5,
+                    y: y as f32 + 0.5,
                     z: 0.0,
                 };
-                let bc_screen = Renderer::barycentric(t0, t1, t2, &p);
-
-                if bc_screen.x < 0.0 || bc_screen.y < 0.0 || bc_screen.z < 0.0 {
+                let base = self.pixel_index(x, y) * positions.len();
+                // per-sample outcome of the stencil and depth tests
+                let mut passed = 0u32;
+                let mut stencil_failed = 0u32;
+                let mut depth_failed = 0u32;
+                let mut first_covered = None;
+                for (s, offset) in positions.iter().enumerate() {
+                    let p = Vec3 {
+                        x: center.x + offset.x,
+                        y: center.y + offset.y,
+                        z: 0.0,
+                    };
+                    let bc_screen = Renderer::barycentric(&t0, &t1, &t2, &p);
+                    if bc_screen.x < 0.0 || bc_screen.y < 0.0 || bc_screen.z < 0.0 {
+                        continue;
+                    }
+                    first_covered.get_or_insert(bc_screen);
+                    if stencil_enabled
+                        && !stencil.test(&stencil_face, self.stencil_samples[base + s])
+                    {
+                        stencil_failed |= 1 << s;
+                        continue;
+                    }
+                    let z =
+                        t0.z * bc_screen.x + t1.z * bc_screen.y + t2.z * bc_screen.z + depth_bias;
+                    if self.depth_samples[base + s] < z {
+                        sample_depths[s] = z;
+                        passed |= 1 << s;
+                    } else {
+                        depth_failed |= 1 << s;
+                    }
+                }
+                let Some(first_covered) = first_covered else {
+                    continue;
+                };
+                // a fragment that fails every test is only shaded when the stencil ops need to
+                // know whether it was discarded
+                if passed == 0 && !(stencil_enabled && (stencil_failed | depth_failed) != 0) {
                     continue;
                 }
-                let index = (y * self.width + x) as usize;
-                if self.z_buffer[index] < p.z as i32 {
-                    self.z_buffer[index] = p.z as i32;
-                    //  interpolate uv coordinates using barycentric coordinates
-                    let uv = *uv0 * bc_screen.x + *uv1 * bc_screen.y + *uv2 * bc_screen.z;
-                    let mut color = diffuse.get_color(&uv);
-                    color *= intensity;
-                    self.draw_pixel(x, y, color);
+
+                let bc_center = Renderer::barycentric(&t0, &t1, &t2, &center);
+                let bc_shade = if bc_center.x < 0.0 || bc_center.y < 0.0 || bc_center.z < 0.0 {
+                    first_covered
+                } else {
+                    bc_center
+                };
+                let shade_point = Vec3 {
+                    x: (t0.x * bc_shade.x + t1.x * bc_shade.y + t2.x * bc_shade.z) / factor,
+                    y: (t0.y * bc_shade.x + t1.y * bc_shade.y + t2.y * bc_shade.z) / factor,
+                    z: t0.z * bc_shade.x + t1.z * bc_shade.y + t2.z * bc_shade.z,
+                };
+                let fragment = Fragment {
+                    position: shade_point,
+                    front_facing,
+                };
+                let Some(color) = shade(&bc_shade, &fragment) else {
+                    continue;
+                };
+                for (s, depth) in sample_depths.iter().enumerate().take(positions.len()) {
+                    let op = if passed & (1 << s) != 0 {
+                        self.write_depth_sample(base + s, *depth);
+                        self.write_color_sample(base + s, color);
+                        stencil_face.pass_op
+                    } else if stencil_failed & (1 << s) != 0 {
+                        stencil_face.fail_op
+                    } else if depth_failed & (1 << s) != 0 {
+                        stencil_face.depth_fail_op
+                    } else {
+                        continue;
+                    };
+                    if stencil_enabled {
+                        let stored = &mut self.stencil_samples[base + s];
+                        *stored = stencil.update(op, *stored);
+                    }
                 }
             }
         }
@@ -239,4 +1205,732 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn test_msaa_resolve() {
+        use crate::color::Color;
+        use crate::math::vec3;
+        use crate::renderer::{Msaa, Renderer};
+        let mut renderer = Renderer::new(8, 8, false);
+        renderer.set_msaa(Msaa::X4);
+        renderer.clear(Color::BLACK);
+        renderer.draw_triangle(
+            &vec3(0.0, 0.0, 0.0),
+            &vec3(8.0, 0.0, 0.0),
+            &vec3(0.0, 8.0, 0.0),
+            Color::WHITE,
+        );
+        renderer.resolve();
+
+        let pixels = renderer.pixels();
+        // fully covered
+        assert_eq!(pixels[8 + 1], Color::WHITE);
+        // outside of the triangle
+        assert_eq!(pixels[6 * 8 + 6], Color::BLACK);
+        // the hypotenuse crosses this pixel diagonally, half of its samples are covered and
+        // half of the light of white is 188 in sRGB
+        assert_eq!(pixels[2 * 8 + 5], Color::rgb(188, 188, 188));
+    }
+
+    #[test]
+    fn test_msaa_depth_per_sample() {
+        use crate::color::Color;
+        use crate::math::vec3;
+        use crate::renderer::{Msaa, Renderer};
+        let mut renderer = Renderer::new(4, 4, false);
+        renderer.set_msaa(Msaa::X8);
+        renderer.clear(Color::BLACK);
+        let [t0, t1, t2] = [
+            vec3(0.0, 0.0, 1.0),
+            vec3(8.0, 0.0, 1.0),
+            vec3(0.0, 8.0, 1.0),
+        ];
+        renderer.draw_triangle(&t0, &t1, &t2, Color::RED);
+        // further away, hidden behind the red triangle
+        let back = [t0, t1, t2].map(|t| Vec3 { z: 0.0, ..t });
+        renderer.draw_triangle(&back[0], &back[1], &back[2], Color::GREEN);
+        renderer.resolve();
+
+        assert!(renderer.pixels().iter().all(|p| p.g == 0));
+        assert_eq!(renderer.pixels()[0], Color::RED);
+    }
+
+    #[test]
+    fn test_supersampling_resolve() {
+        use crate::color::Color;
+        use crate::math::vec3;
+        use crate::renderer::{Msaa, Renderer};
+        use crate::resample::ResampleFilter;
+        let mut renderer = Renderer::new(8, 8, false);
+        renderer.set_supersampling(4, ResampleFilter::Box);
+        renderer.clear(Color::BLACK);
+        renderer.draw_triangle(
+            &vec3(0.0, 0.0, 0.0),
+            &vec3(8.0, 0.0, 0.0),
+            &vec3(0.0, 8.0, 0.0),
+            Color::WHITE,
+        );
+        renderer.resolve();
+
+        let pixels = renderer.pixels();
+        assert_eq!(pixels.len(), 64);
+        assert_eq!(pixels[8 + 1], Color::WHITE);
+        assert_eq!(pixels[6 * 8 + 6], Color::BLACK);
+        // 10 of the 16 sub-pixels on the diagonal are covered, 0.625 of the light of white
+        assert_eq!(pixels[2 * 8 + 5], Color::rgb(207, 207, 207));
+
+        // MSAA on top of supersampling still resolves to the output size
+        renderer.set_msaa(Msaa::X2);
+        renderer.clear(Color::RED);
+        renderer.resolve();
+        assert!(renderer.pixels().iter().all(|p| *p == Color::RED));
+    }
+
+    #[test]
+    fn test_stencil_masking() {
+        use crate::color::Color;
+        use crate::math::vec3;
+        use crate::renderer::Renderer;
+        use crate::stencil::{CompareFunction, StencilFaceState, StencilOp, StencilState};
+        let mut renderer = Renderer::new(8, 8, false);
+        renderer.clear(Color::BLACK);
+        let [t0, t1, t2] = [
+            vec3(0.0, 0.0, 0.0),
+            vec3(8.0, 0.0, 0.0),
+            vec3(0.0, 8.0, 0.0),
+        ];
+        let write = StencilFaceState {
+            pass_op: StencilOp::Replace,
+            ..StencilFaceState::IGNORE
+        };
+        renderer.set_stencil_state(StencilState {
+            front: write,
+            reference: 1,
+            ..Default::default()
+        });
+        renderer.draw_triangle(&t0, &t1, &t2, Color::BLACK);
+        // clockwise, so the back face state applies and nothing is written
+        renderer.draw_triangle(&t0, &vec3(8.0, 8.0, 0.0), &t1, Color::BLACK);
+        assert_eq!(renderer.stencil(1, 1), 1);
+        assert_eq!(renderer.stencil(6, 6), 0);
+
+        let equal = StencilFaceState {
+            compare: CompareFunction::Equal,
+            ..StencilFaceState::IGNORE
+        };
+        renderer.set_stencil_state(StencilState {
+            front: equal,
+            back: equal,
+            reference: 1,
+            ..Default::default()
+        });
+        // a quad covering the whole target, masked by the first triangle
+        let [q0, q1, q2, q3] = [
+            vec3(0.0, 0.0, 1.0),
+            vec3(8.0, 0.0, 1.0),
+            vec3(8.0, 8.0, 1.0),
+            vec3(0.0, 8.0, 1.0),
+        ];
+        renderer.draw_triangle(&q0, &q1, &q2, Color::RED);
+        renderer.draw_triangle(&q0, &q2, &q3, Color::RED);
+        assert_eq!(renderer.pixels()[8 + 1], Color::RED);
+        assert_eq!(renderer.pixels()[6 * 8 + 6], Color::BLACK);
+    }
+
+    #[test]
+    #[should_panic(expected = "outside of the 8x8 target")]
+    fn test_stencil_out_of_bounds() {
+        use crate::renderer::Renderer;
+        let renderer = Renderer::new(8, 8, false);
+        renderer.stencil(8, 0);
+    }
+
+    #[test]
+    fn test_cull_mode() {
+        use crate::color::Color;
+        use crate::math::vec3;
+        use crate::rasterizer::{CullMode, FrontFace, RasterizerState};
+        use crate::renderer::Renderer;
+        let mut renderer = Renderer::new(8, 8, false);
+        let ccw = [
+            vec3(0.0, 0.0, 0.0),
+            vec3(8.0, 0.0, 0.0),
+            vec3(0.0, 8.0, 0.0),
+        ];
+        let cw = [ccw[0], ccw[2], ccw[1]];
+        let mut drawn = |state: RasterizerState, t: [Vec3; 3]| {
+            renderer.clear(Color::BLACK);
+            renderer.set_rasterizer_state(state);
+            renderer.draw_triangle(&t[0], &t[1], &t[2], Color::WHITE);
+            renderer.pixels()[8 + 1] == Color::WHITE
+        };
+
+        let back = RasterizerState {
+            cull_mode: CullMode::Back,
+            ..Default::default()
+        };
+        assert!(drawn(back, ccw));
+        assert!(!drawn(back, cw));
+
+        let front_cw = RasterizerState {
+            cull_mode: CullMode::Front,
+            front_face: FrontFace::Cw,
+            ..Default::default()
+        };
+        assert!(drawn(front_cw, ccw));
+        assert!(!drawn(front_cw, cw));
+
+        assert!(drawn(RasterizerState::default(), cw));
+    }
+
+    #[test]
+    fn test_viewport_and_scissor() {
+        use crate::color::Color;
+        use crate::math::vec3;
+        use crate::renderer::Renderer;
+        use crate::resample::ResampleFilter;
+        use crate::viewport::{ScissorRect, Viewport};
+        let mut renderer = Renderer::new(8, 8, false);
+        renderer.set_supersampling(2, ResampleFilter::Box);
+        let fill = |renderer: &mut Renderer| {
+            renderer.clear(Color::BLACK);
+            renderer.draw_triangle(
+                &vec3(-8.0, -8.0, 0.0),
+                &vec3(24.0, -8.0, 0.0),
+                &vec3(-8.0, 24.0, 0.0),
+                Color::WHITE,
+            );
+            renderer.draw_pixel(7, 7, Color::RED);
+            renderer.resolve();
+            let covered: Vec<bool> = renderer
+                .pixels()
+                .iter()
+                .map(|p| *p != Color::BLACK)
+                .collect();
+            covered
+        };
+
+        renderer.set_viewport(Viewport::new(2.0, 2.0, 4.0, 4.0));
+        let covered = fill(&mut renderer);
+        assert_eq!(covered.iter().filter(|c| **c).count(), 16);
+        assert!(covered[2 * 8 + 2] && covered[5 * 8 + 5]);
+        assert!(!covered[7 * 8 + 7]);
+
+        renderer.set_scissor(Some(ScissorRect::new(0, 0, 4, 8)));
+        let covered = fill(&mut renderer);
+        assert_eq!(covered.iter().filter(|c| **c).count(), 8);
+        assert!(!covered[2 * 8 + 4]);
+    }
+
+    #[test]
+    fn test_guard_band_clip() {
+        use crate::color::Color;
+        use crate::math::{vec4, Mat4};
+        use crate::rasterizer::PrimitiveTopology;
+        use crate::renderer::Renderer;
+        use crate::testing::TestShader;
+
+        // left of the target with a corner behind the eye, clipping at w close to 0 sends the
+        // other corners to x = -1e5 where the edge functions would lose all precision
+        let quad = [
+            (vec4(-1.5, -1.5, -1.0, 1.0), 1.0),
+            (vec4(-1.5, 1.5, -1.0, 1.0), 1.0),
+            (vec4(-1.5, -1.5, 1.0, -1.0), 1.0),
+            (vec4(-1.5, 1.5, 1.0, -1.0), 1.0),
+        ];
+        let mut renderer = Renderer::new(16, 16, false);
+        renderer.clear(Color::BLACK);
+        renderer.draw_indexed(
+            &TestShader::new(Mat4::identity()),
+            &quad,
+            &[2, 0, 1, 2, 1, 3],
+            PrimitiveTopology::TriangleList,
+            0,
+        );
+        assert!(renderer.pixels().iter().all(|c| *c == Color::BLACK));
+
+        // a triangle reaching far past the target still covers all of it
+        let huge = [
+            (vec4(-1e4, -1e4, 0.0, 1.0), 1.0),
+            (vec4(1e4, -1e4, 0.0, 1.0), 1.0),
+            (vec4(0.0, 1e4, 0.0, 1.0), 1.0),
+        ];
+        renderer.draw_indexed(
+            &TestShader::new(Mat4::identity()),
+            &huge,
+            &[0, 1, 2],
+            PrimitiveTopology::TriangleList,
+            0,
+        );
+        assert!(renderer.pixels().iter().all(|c| *c == Color::WHITE));
+    }
+
+    #[test]
+    fn test_viewport_depth_range_clip() {
+        use crate::color::Color;
+        use crate::math::{vec3, Mat4};
+        use crate::rasterizer::PrimitiveTopology;
+        use crate::renderer::Renderer;
+        use crate::viewport::Viewport;
+        let mut renderer = Renderer::new(16, 16, false);
+        renderer.set_viewport(Viewport {
+            min_depth: 0.25,
+            max_depth: 0.75,
+            ..Viewport::new(0.0, 0.0, 16.0, 16.0)
+        });
+        renderer.clear(Color::BLACK);
+        // a quad whose depth runs from z = -2 on the left to 2 on the right
+        let quad = [
+            vec3(-1.0, -1.0, -2.0),
+            vec3(1.0, -1.0, 2.0),
+            vec3(1.0, 1.0, 2.0),
+            vec3(-1.0, -1.0, -2.0),
+            vec3(1.0, 1.0, 2.0),
+            vec3(-1.0, 1.0, -2.0),
+        ];
+        let identity = Mat4::identity();
+        renderer.draw_primitives(
+            &quad,
+            PrimitiveTopology::TriangleList,
+            &identity,
+            Color::WHITE,
+        );
+        // only the middle half lies inside the depth range
+        let row = &renderer.pixels()[8 * 16..9 * 16];
+        assert_eq!(row[3], Color::BLACK);
+        assert_eq!(row[4], Color::WHITE);
+        assert_eq!(row[11], Color::WHITE);
+        assert_eq!(row[12], Color::BLACK);
+        let depth = renderer.resolved_depth();
+        assert!(depth
+            .iter()
+            .filter(|z| **z != -f32::MAX)
+            .all(|z| (0.25..=0.75).contains(z)));
+
+        // a line leaving through the near plane and points on both sides of the range
+        renderer.clear(Color::BLACK);
+        let line = [vec3(-1.0, 0.0, 0.0), vec3(1.0, 0.0, 4.0)];
+        renderer.draw_primitives(&line, PrimitiveTopology::LineList, &identity, Color::GREEN);
+        let row = &renderer.pixels()[8 * 16..9 * 16];
+        assert_eq!(row[1], Color::GREEN);
+        assert_eq!(row[6], Color::BLACK);
+        let points = [vec3(-0.5, -0.5, 1.5), vec3(0.5, -0.5, -0.5)];
+        renderer.draw_primitives(&points, PrimitiveTopology::PointList, &identity, Color::RED);
+        assert_eq!(renderer.pixels()[4 * 16 + 4], Color::BLACK);
+        assert_eq!(renderer.pixels()[4 * 16 + 12], Color::RED);
+    }
+
+    #[test]
+    fn test_primitives_depth_test() {
+        use crate::color::Color;
+        use crate::math::{vec3, Mat4};
+        use crate::rasterizer::{PrimitiveTopology, RasterizerState, PRIMITIVE_RESTART_INDEX};
+        use crate::renderer::Renderer;
+        let mut renderer = Renderer::new(16, 16, false);
+        renderer.clear(Color::BLACK);
+        let identity = Mat4::identity();
+        let quad = [
+            vec3(-1.0, -1.0, 0.0),
+            vec3(1.0, -1.0, 0.0),
+            vec3(1.0, 1.0, 0.0),
+            vec3(-1.0, -1.0, 0.0),
+            vec3(1.0, 1.0, 0.0),
+            vec3(-1.0, 1.0, 0.0),
+        ];
+        renderer.draw_primitives(
+            &quad,
+            PrimitiveTopology::TriangleList,
+            &identity,
+            Color::RED,
+        );
+
+        // behind the quad
+        let hidden = [vec3(-1.0, 0.0, -0.5), vec3(1.0, 0.0, -0.5)];
+        renderer.draw_primitives(
+            &hidden,
+            PrimitiveTopology::LineList,
+            &identity,
+            Color::GREEN,
+        );
+        // in front of it
+        let visible = [
+            vec3(-2.0, 0.5, 0.5),
+            vec3(0.0, 0.5, 0.5),
+            vec3(2.0, 0.5, 0.5),
+        ];
+        renderer.draw_primitives(
+            &visible,
+            PrimitiveTopology::LineStrip,
+            &identity,
+            Color::BLUE,
+        );
+
+        let row = |y: usize| renderer.pixels()[y * 16..(y + 1) * 16].to_vec();
+        assert!(row(8).iter().all(|c| *c == Color::RED));
+        assert!(row(12).iter().all(|c| *c == Color::BLUE));
+
+        renderer.set_rasterizer_state(RasterizerState {
+            point_size: 3.0,
+            ..Default::default()
+        });
+        let point = [vec3(0.0, -0.5, 1.0)];
+        renderer.draw_primitives(
+            &point,
+            PrimitiveTopology::PointList,
+            &identity,
+            Color::WHITE,
+        );
+        let white = renderer
+            .pixels()
+            .iter()
+            .filter(|c| **c == Color::WHITE)
+            .count();
+        assert_eq!(white, 9);
+        assert_eq!(renderer.pixels()[4 * 16 + 8], Color::WHITE);
+    }
+
+    #[test]
+    fn test_primitives_w_clip() {
+        use crate::color::Color;
+        use crate::math::{vec3, Mat4};
+        use crate::rasterizer::PrimitiveTopology;
+        use crate::renderer::Renderer;
+        let mut renderer = Renderer::new(16, 16, false);
+        renderer.clear(Color::BLACK);
+        // w = z, everything with z <= 0 is behind the eye
+        let mut transform = Mat4::identity();
+        transform[(3, 2)] = 1.0;
+        transform[(3, 3)] = 0.0;
+
+        let line = [vec3(-0.5, 0.0, 1.0), vec3(0.5, 0.0, -1.0)];
+        renderer.draw_primitives(&line, PrimitiveTopology::LineList, &transform, Color::GREEN);
+        let row = &renderer.pixels()[8 * 16..9 * 16];
+        assert_eq!(row[4], Color::GREEN);
+        assert_eq!(row[3], Color::BLACK);
+
+        let triangle = [
+            vec3(-0.5, -0.5, 1.0),
+            vec3(0.5, -0.5, 1.0),
+            vec3(0.0, 1.0, -1.0),
+        ];
+        renderer.draw_primitives(
+            &triangle,
+            PrimitiveTopology::TriangleList,
+            &transform,
+            Color::RED,
+        );
+        assert!(renderer.pixels().contains(&Color::RED));
+    }
+
+    #[test]
+    fn test_draw_indexed_topologies() {
+        use crate::color::Color;
+        use crate::math::{vec3, Mat4};
+        use crate::rasterizer::{PrimitiveTopology, PRIMITIVE_RESTART_INDEX};
+        use crate::renderer::{FlatShader, Renderer};
+        let identity = Mat4::identity();
+        let shader = FlatShader {
+            transform: &identity,
+            color: Color::WHITE,
+        };
+        // a padding vertex that base_vertex skips over, then the left and right half of the
+        // target as two separate strips
+        let vertices = [
+            vec3(9.0, 9.0, 0.0),
+            vec3(-1.0, -1.0, 0.0),
+            vec3(0.0, -1.0, 0.0),
+            vec3(-1.0, 1.0, 0.0),
+            vec3(0.0, 1.0, 0.0),
+            vec3(1.0, -1.0, 0.0),
+            vec3(1.0, 1.0, 0.0),
+        ];
+        let indices = [0, 1, 2, 3, PRIMITIVE_RESTART_INDEX, 1, 4, 3, 5];
+        let mut renderer = Renderer::new(8, 8, false);
+        renderer.clear(Color::BLACK);
+        renderer.draw_indexed(
+            &shader,
+            &vertices,
+            &indices,
+            PrimitiveTopology::TriangleStrip,
+            1,
+        );
+        assert!(renderer.pixels().iter().all(|c| *c == Color::WHITE));
+
+        // a fan around the center covering the whole target
+        let fan = [
+            vec3(0.0, 0.0, 0.0),
+            vec3(-1.0, -1.0, 0.0),
+            vec3(1.0, -1.0, 0.0),
+            vec3(1.0, 1.0, 0.0),
+            vec3(-1.0, 1.0, 0.0),
+            vec3(-1.0, -1.0, 0.0),
+        ];
+        renderer.clear(Color::BLACK);
+        renderer.draw_indexed(
+            &shader,
+            &fan,
+            &[0, 1, 2, 3, 4, 5],
+            PrimitiveTopology::TriangleFan,
+            0,
+        );
+        assert!(renderer.pixels().iter().all(|c| *c == Color::WHITE));
+    }
+
+    #[test]
+    #[should_panic(expected = "index 0 with base vertex -1 is outside of the 3 vertices")]
+    fn test_draw_indexed_negative_base_vertex() {
+        use crate::color::Color;
+        use crate::math::{vec3, Mat4};
+        use crate::rasterizer::PrimitiveTopology;
+        use crate::renderer::{FlatShader, Renderer};
+        let triangle = [
+            vec3(-1.0, -1.0, 0.0),
+            vec3(1.0, -1.0, 0.0),
+            vec3(0.0, 1.0, 0.0),
+        ];
+        let shader = FlatShader {
+            transform: &Mat4::identity(),
+            color: Color::WHITE,
+        };
+        let mut renderer = Renderer::new(8, 8, false);
+        renderer.draw_indexed(
+            &shader,
+            &triangle,
+            &[1, 2, 0],
+            PrimitiveTopology::TriangleList,
+            -1,
+        );
+    }
+
+    #[test]
+    fn test_perspective_correct_varyings() {
+        use crate::color::{Color, ColorSpace};
+        use crate::math::{vec4, Mat4};
+        use crate::rasterizer::PrimitiveTopology;
+        use crate::renderer::Renderer;
+        use crate::testing::TestShader;
+
+        // a line whose right end is three times as far away, halfway across the screen is only a
+        // quarter of the way along the line in 3D
+        let vertices = [
+            (vec4(-1.0, 0.0, 0.0, 1.0), 0.0),
+            (vec4(3.0, 0.0, 0.0, 3.0), 1.0),
+        ];
+        let mut renderer = Renderer::new(16, 16, false);
+        renderer.set_color_space(ColorSpace::Linear);
+        renderer.clear(Color::BLACK);
+        renderer.draw_indexed(
+            &TestShader::new(Mat4::identity()),
+            &vertices,
+            &[0, 1],
+            PrimitiveTopology::LineList,
+            0,
+        );
+        // the last fragment written to the pixel lies anywhere across it, an affine interpolation
+        // would give about 128 instead
+        let gray = renderer.pixels()[8 * 16 + 8].r;
+        assert!((60..=80).contains(&gray), "{}", gray);
+    }
+
+    #[test]
+    fn test_draw_indexed_instanced() {
+        use crate::color::Color;
+        use crate::math::{vec3, Mat4};
+        use crate::rasterizer::PrimitiveTopology;
+        use crate::renderer::Renderer;
+        use crate::shader::Instance;
+        use crate::testing::TestShader;
+
+        // a quad covering the bottom left quarter of the target
+        let quad = [
+            vec3(-1.0, -1.0, 0.0),
+            vec3(0.0, -1.0, 0.0),
+            vec3(0.0, 0.0, 0.0),
+            vec3(-1.0, 0.0, 0.0),
+        ];
+        let indices = [0, 1, 2, 0, 2, 3];
+        let instances = [
+            Instance {
+                transform: Mat4::identity(),
+                color: Color::RED,
+            },
+            Instance {
+                transform: Mat4::identity().translate(1.0, 1.0, 0.0),
+                color: Color::GREEN,
+            },
+        ];
+        let shader = TestShader::new(Mat4::identity());
+        let mut renderer = Renderer::new(8, 8, false);
+        renderer.clear(Color::BLACK);
+        renderer.draw_indexed_instanced(
+            &shader,
+            &quad,
+            &indices,
+            PrimitiveTopology::TriangleList,
+            0,
+            &instances,
+        );
+        assert_eq!(renderer.pixels()[8 + 1], Color::RED);
+        assert_eq!(renderer.pixels()[6 * 8 + 6], Color::GREEN);
+        assert_eq!(renderer.pixels()[6 * 8 + 1], Color::BLACK);
+        // every shared vertex is transformed once per instance
+        assert_eq!(shader.invocations.get(), 8);
+    }
+
+    #[test]
+    fn test_post_transform_cache() {
+        use crate::color::Color;
+        use crate::math::Mat4;
+        use crate::rasterizer::PrimitiveTopology;
+        use crate::renderer::Renderer;
+        use crate::testing::{grid, TestShader};
+
+        // a 16x16 quad grid stretched over the target, every inner vertex is shared by six
+        // triangles
+        let (vertices, indices) = grid(16, |_, _| 0.0);
+        let shader = TestShader::new(
+            Mat4::identity()
+                .scale(2.0, 2.0, 1.0)
+                .translate(-1.0, -1.0, 0.0),
+        );
+        let mut renderer = Renderer::new(32, 32, false);
+        renderer.clear(Color::BLACK);
+        renderer.draw_indexed(
+            &shader,
+            &vertices,
+            &indices,
+            PrimitiveTopology::TriangleList,
+            0,
+        );
+        assert_eq!(shader.invocations.get(), vertices.len() as u32);
+        assert!(indices.len() as u32 > 5 * shader.invocations.get());
+        assert!(renderer.pixels().iter().all(|c| *c == Color::WHITE));
+    }
+
+    #[test]
+    fn test_draw_model_culling() {
+        use crate::color::Color;
+        use crate::math::Mat4;
+        use crate::model::Model;
+        use crate::renderer::Renderer;
+        use crate::testing::TestShader;
+
+        let model = Model::cube(0.5, 1);
+        let mut renderer = Renderer::new(16, 16, false);
+        renderer.clear(Color::BLACK);
+
+        let outside = Mat4::identity().translate(3.0, 0.0, 0.0);
+        let shader = TestShader::new(outside);
+        assert_eq!(renderer.draw_model(&shader, &model, &outside), 0);
+        assert_eq!(shader.invocations.get(), 0);
+        assert!(renderer.pixels().iter().all(|c| *c == Color::BLACK));
+
+        let inside = Mat4::identity();
+        let shader = TestShader::new(inside);
+        assert_eq!(renderer.draw_model(&shader, &model, &inside), 1);
+        assert!(shader.invocations.get() > 0);
+        assert_eq!(renderer.pixels()[8 * 16 + 8], Color::WHITE);
+    }
+
+    #[test]
+    fn test_draw_model_lod() {
+        use crate::camera::Camera;
+        use crate::color::Color;
+        use crate::math::vec3;
+        use crate::model::Model;
+        use crate::renderer::Renderer;
+        use crate::testing::TestShader;
+
+        let model = Model::uv_sphere(1.0, 32, 16);
+        let lods = model.generate_lods(4, 0.5);
+        assert_eq!(lods.levels.len(), 4);
+        let mut camera = Camera::new(1.0, 1.0, 0.1, 200.0);
+        let mut renderer = Renderer::new(64, 64, false);
+        let mut draw = |camera: &Camera| {
+            let transform = camera.view_projection_matrix();
+            let shader = TestShader::new(transform);
+            renderer.clear(Color::BLACK);
+            let level = renderer.draw_model_lod(&shader, &model, &lods, &transform, 0.5);
+            let covered = renderer.pixels().contains(&Color::WHITE);
+            (level, covered)
+        };
+        camera.position = vec3(0.0, 0.0, 2.0);
+        assert_eq!(draw(&camera), (Some(0), true));
+        // far away the coarser levels are drawn, still covering pixels
+        camera.position = vec3(0.0, 0.0, 20.0);
+        let (far, covered) = draw(&camera);
+        assert!(far.unwrap() > 0 && covered);
+        camera.look_at(&vec3(0.0, 0.0, 200.0), &vec3(0.0, 1.0, 0.0));
+        assert_eq!(draw(&camera), (None, false));
+    }
+
+    #[test]
+    fn test_hdr_tone_mapping() {
+        use crate::color::{Color, ColorSpace, LinearColor};
+        use crate::math::{vec3, Mat4};
+        use crate::rasterizer::PrimitiveTopology;
+        use crate::renderer::{Msaa, Renderer};
+        use crate::testing::TestShader;
+        use crate::tonemap::{ToneMapper, ToneMapping};
+
+        // a left and a right half with the same hue at different over-bright intensities
+        let half = [
+            vec3(-1.0, -1.0, 0.0),
+            vec3(0.0, -1.0, 0.0),
+            vec3(0.0, 1.0, 0.0),
+            vec3(-1.0, 1.0, 0.0),
+        ];
+        let halves =
+            [(0.0, 2.0), (1.0, 4.0)].map(|(x, intensity)| {
+                TestShader::new(Mat4::identity().translate(x, 0.0, 0.0))
+                    .with_color(LinearColor::rgb(intensity, intensity * 0.5, 0.0))
+            });
+        let draw = |renderer: &mut Renderer| {
+            renderer.clear(Color::BLACK);
+            for shader in &halves {
+                renderer.draw_indexed(
+                    shader,
+                    &half,
+                    &[0, 1, 2, 0, 2, 3],
+                    PrimitiveTopology::TriangleList,
+                    0,
+                );
+            }
+            renderer.resolve();
+            (renderer.pixels()[4 * 8 + 1], renderer.pixels()[4 * 8 + 6])
+        };
+
+        // an 8-bit target clips both halves to the same color
+        let mut renderer = Renderer::new(8, 8, false);
+        renderer.set_color_space(ColorSpace::Linear);
+        let (left, right) = draw(&mut renderer);
+        assert_eq!(left, Color::rgb(255, 255, 0));
+        assert_eq!(left, right);
+
+        renderer.set_tone_mapping(Some(ToneMapping::new(0.0, ToneMapper::Reinhard)));
+        let (left, right) = draw(&mut renderer);
+        assert_eq!(
+            renderer.hdr_samples()[4 * 8 + 6],
+            LinearColor::rgb(4.0, 2.0, 0.0)
+        );
+        // 2 / 3 and 1 / 2, 4 / 5 and 2 / 3
+        assert_eq!(left, Color::rgb(170, 128, 0));
+        assert_eq!(right, Color::rgb(204, 170, 0));
+
+        // multisampling averages linear values before tone mapping
+        renderer.set_msaa(Msaa::X4);
+        renderer.set_tone_mapping(Some(ToneMapping::new(-1.0, ToneMapper::Reinhard)));
+        let (left, _) = draw(&mut renderer);
+        assert_eq!(left, Color::rgb(128, 85, 0));
+
+        // an sRGB target encodes the tone mapped values, 1 / 2 and 1 / 3
+        renderer.set_color_space(ColorSpace::Srgb);
+        let (left, _) = draw(&mut renderer);
+        assert_eq!(left, Color::rgb(188, 156, 0));
+
+        renderer.set_tone_mapping(None);
+        assert!(renderer.hdr_samples().is_empty());
+        let (left, _) = draw(&mut renderer);
+        assert_eq!(left, Color::rgb(255, 255, 0));
+    }
 }
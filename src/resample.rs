@@ -0,0 +1,246 @@
+use crate::color::{Color, LinearColor};
+use std::f32::consts::PI;
+
+/// Reconstruction filter used when resampling an image to a different size.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ResampleFilter {
+    /// Averages the source pixels covered by each destination pixel.
+    #[default]
+    Box,
+    /// Linear falloff, a bilinear filter when upscaling.
+    Tent,
+    /// Windowed sinc with three lobes. Sharpest, but may ring around hard edges.
+    Lanczos3,
+    /// Mitchell-Netravali cubic with B = C = 1/3, a good compromise between blur and ringing.
+    Mitchell,
+}
+
+impl ResampleFilter {
+    /// Radius of the filter kernel, in destination pixels.
+    pub fn support(&self) -> f32 {
+        match self {
+            ResampleFilter::Box => 0.5,
+            ResampleFilter::Tent => 1.0,
+            ResampleFilter::Lanczos3 => 3.0,
+            ResampleFilter::Mitchell => 2.0,
+        }
+    }
+
+    pub fn evaluate(&self, x: f32) -> f32 {
+        let x = x.abs();
+        match self {
+            ResampleFilter::Box => {
+                if x < 0.5 {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            ResampleFilter::Tent => (1.0 - x).max(0.0),
+            ResampleFilter::Lanczos3 => {
+                if x < 3.0 {
+                    sinc(x) * sinc(x / 3.0)
+                } else {
+                    0.0
+                }
+            }
+            ResampleFilter::Mitchell => {
+                const B: f32 = 1.0 / 3.0;
+                const C: f32 = 1.0 / 3.0;
+                if x < 1.0 {
+                    ((12.0 - 9.0 * B - 6.0 * C) * x.powi(3)
+                        + (-18.0 + 12.0 * B + 6.0 * C) * x.powi(2)
+                        + (6.0 - 2.0 * B))
+                        / 6.0
+                } else if x < 2.0 {
+                    ((-B - 6.0 * C) * x.powi(3)
+                        + (6.0 * B + 30.0 * C) * x.powi(2)
+                        + (-12.0 * B - 48.0 * C) * x
+                        + (8.0 * B + 24.0 * C))
+                        / 6.0
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+}
+
+fn sinc(x: f32) -> f32 {
+    if x.abs() < 1e-6 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+/// Source pixels and normalized weights contributing to one destination pixel.
+struct Contribution {
+    start: usize,
+    weights: Vec<f32>,
+}
+
+fn contributions(src_len: u32, dst_len: u32, filter: ResampleFilter) -> Vec<Contribution> {
+    let scale = src_len as f32 / dst_len as f32;
+    // widen the kernel when downscaling so every source pixel contributes
+    let filter_scale = scale.max(1.0);
+    let support = filter.support() * filter_scale;
+    (0..dst_len)
+        .map(|i| {
+            let center = (i as f32 + 0.5) * scale;
+            let start = (center - support).floor().max(0.0) as usize;
+            let end = ((center + support).ceil() as usize).min(src_len as usize);
+            let mut weights: Vec<f32> = (start..end)
+                .map(|j| filter.evaluate((j as f32 + 0.5 - center) / filter_scale))
+                .collect();
+            let sum: f32 = weights.iter().sum();
+            if sum.abs() > f32::EPSILON {
+                weights.iter_mut().for_each(|w| *w /= sum);
+            } else {
+                // kernel missed every pixel center, fall back to nearest neighbor
+                weights.iter_mut().for_each(|w| *w = 0.0);
+                let nearest = (center as usize).clamp(start, end - 1);
+                weights[nearest - start] = 1.0;
+            }
+            Contribution { start, weights }
+        })
+        .collect()
+}
+
+/// Resamples a row-major image to `dst_width`x`dst_height` with a separable filter. The
+/// channels are filtered as they are stored, see [`resample_linear`] to filter in linear light.
+#[profiling::function]
+pub fn resample(
+    src: &[Color],
+    src_width: u32,
+    src_height: u32,
+    dst_width: u32,
+    dst_height: u32,
+    filter: ResampleFilter,
+) -> Vec<Color> {
+    assert_eq!(src.len(), (src_width * src_height) as usize);
+    if src_width == dst_width && src_height == dst_height {
+        return src.to_vec();
+    }
+    let src: Vec<[f32; 4]> = src
+        .iter()
+        .map(|c| [c.r, c.g, c.b, c.a].map(|c| c as f32))
+        .collect();
+    resample_channels(&src, src_width, src_height, dst_width, dst_height, filter)
+        .into_iter()
+        .map(|c| {
+            let [r, g, b, a] = c.map(|c| c.round().clamp(0.0, 255.0) as u8);
+            Color::rgba(r, g, b, a)
+        })
+        .collect()
+}
+
+/// [`resample`] for linear colors, values outside of 0..1 are kept.
+#[profiling::function]
+pub fn resample_linear(
+    src: &[LinearColor],
+    src_width: u32,
+    src_height: u32,
+    dst_width: u32,
+    dst_height: u32,
+    filter: ResampleFilter,
+) -> Vec<LinearColor> {
+    assert_eq!(src.len(), (src_width * src_height) as usize);
+    if src_width == dst_width && src_height == dst_height {
+        return src.to_vec();
+    }
+    let src: Vec<[f32; 4]> = src.iter().map(|c| [c.r, c.g, c.b, c.a]).collect();
+    resample_channels(&src, src_width, src_height, dst_width, dst_height, filter)
+        .into_iter()
+        .map(|[r, g, b, a]| LinearColor::rgba(r, g, b, a))
+        .collect()
+}
+
+fn resample_channels(
+    src: &[[f32; 4]],
+    src_width: u32,
+    src_height: u32,
+    dst_width: u32,
+    dst_height: u32,
+    filter: ResampleFilter,
+) -> Vec<[f32; 4]> {
+    // horizontal pass, src_width x src_height -> dst_width x src_height
+    let columns = contributions(src_width, dst_width, filter);
+    let mut horizontal = vec![[0.0f32; 4]; (dst_width * src_height) as usize];
+    for y in 0..src_height as usize {
+        let src_row = &src[y * src_width as usize..(y + 1) * src_width as usize];
+        let dst_row = &mut horizontal[y * dst_width as usize..(y + 1) * dst_width as usize];
+        for (dst, contribution) in dst_row.iter_mut().zip(&columns) {
+            for (i, weight) in contribution.weights.iter().enumerate() {
+                let c = src_row[contribution.start + i];
+                for (d, c) in dst.iter_mut().zip(c) {
+                    *d += c * weight;
+                }
+            }
+        }
+    }
+
+    // vertical pass, dst_width x src_height -> dst_width x dst_height
+    let rows = contributions(src_height, dst_height, filter);
+    let mut dst = Vec::with_capacity((dst_width * dst_height) as usize);
+    for contribution in &rows {
+        for x in 0..dst_width as usize {
+            let mut sum = [0.0f32; 4];
+            for (i, weight) in contribution.weights.iter().enumerate() {
+                let c = horizontal[(contribution.start + i) * dst_width as usize + x];
+                for (s, c) in sum.iter_mut().zip(c) {
+                    *s += c * weight;
+                }
+            }
+            dst.push(sum);
+        }
+    }
+    dst
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_box_downscale_averages() {
+        let src = [Color::BLACK, Color::WHITE, Color::WHITE, Color::BLACK];
+        let dst = resample(&src, 2, 2, 1, 1, ResampleFilter::Box);
+        assert_eq!(dst, vec![Color::rgb(128, 128, 128)]);
+    }
+
+    #[test]
+    fn test_filters_preserve_flat_color() {
+        let color = Color::rgba(10, 120, 200, 255);
+        let src = vec![color; 12 * 9];
+        for filter in [
+            ResampleFilter::Box,
+            ResampleFilter::Tent,
+            ResampleFilter::Lanczos3,
+            ResampleFilter::Mitchell,
+        ] {
+            let dst = resample(&src, 12, 9, 5, 4, filter);
+            assert!(dst.iter().all(|c| *c == color), "{:?}", filter);
+        }
+    }
+
+    #[test]
+    fn test_texture_resized() {
+        use crate::color::ColorSpace;
+        use crate::texture::Texture;
+
+        let checker = Texture {
+            pixels: vec![Color::BLACK, Color::WHITE, Color::WHITE, Color::BLACK],
+            width: 2,
+            height: 2,
+            color_space: ColorSpace::Srgb,
+        };
+        // half of the light of white, encoded back to sRGB
+        let resized = checker.resized(1, 1, ResampleFilter::Box);
+        assert_eq!(resized.pixels, vec![Color::rgb(188, 188, 188)]);
+        let data = checker.with_color_space(ColorSpace::Linear);
+        let resized = data.resized(1, 1, ResampleFilter::Box);
+        assert_eq!(resized.pixels, vec![Color::rgb(128, 128, 128)]);
+        assert_eq!(resized.color_space, ColorSpace::Linear);
+    }
+}
@@ -0,0 +1,439 @@
+use crate::camera::Camera;
+use crate::light::Light;
+use crate::math::{Aabb, Mat4, Quat, Vec3};
+use crate::model::{Model, Vertex};
+use crate::renderer::Renderer;
+use crate::shader::Shader;
+
+/// Local transform of a node relative to its parent: scale, then rotation, then translation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform::IDENTITY
+    }
+}
+
+impl Transform {
+    pub const IDENTITY: Transform = Transform {
+        translation: Vec3::ZERO,
+        rotation: Quat::IDENTITY,
+        scale: Vec3::ONE,
+    };
+
+    pub fn from_translation(translation: Vec3) -> Self {
+        Transform {
+            translation,
+            ..Transform::IDENTITY
+        }
+    }
+
+    pub fn with_rotation(self, rotation: Quat) -> Self {
+        Transform { rotation, ..self }
+    }
+
+    pub fn with_scale(self, scale: Vec3) -> Self {
+        Transform { scale, ..self }
+    }
+
+    pub fn matrix(&self) -> Mat4 {
+        Mat4::from_scale_rotation_translation(&self.scale, &self.rotation, &self.translation)
+    }
+}
+
+/// Handle of a node, only valid for the scene that created it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct NodeId(usize);
+
+/// Handle of a mesh, only valid for the scene that created it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct MeshId(usize);
+
+/// Handle of a camera, only valid for the scene that created it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CameraId(usize);
+
+/// Handle of a light, only valid for the scene that created it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct LightId(usize);
+
+pub struct Node {
+    pub name: String,
+    /// Mesh drawn with the world transform of the node.
+    pub mesh: Option<MeshId>,
+    /// Camera placed at the node, looking down the node's -z axis.
+    pub camera: Option<CameraId>,
+    /// Light placed in the node's space.
+    pub light: Option<LightId>,
+    transform: Transform,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    world: Mat4,
+    dirty: bool,
+}
+
+impl Node {
+    pub fn transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    pub fn parent(&self) -> Option<NodeId> {
+        self.parent
+    }
+
+    pub fn children(&self) -> &[NodeId] {
+        &self.children
+    }
+
+    /// Transform from the node's space to world space, as of the last
+    /// [`Scene::update_world_transforms`].
+    pub fn world_transform(&self) -> &Mat4 {
+        &self.world
+    }
+}
+
+/// Tree of nodes with hierarchical transforms and the meshes they reference. A mesh can be
+/// attached to any number of nodes.
+#[derive(Default)]
+pub struct Scene {
+    nodes: Vec<Node>,
+    meshes: Vec<Model>,
+    cameras: Vec<Camera>,
+    lights: Vec<Light>,
+}
+
+impl Scene {
+    pub fn new() -> Self {
+        Scene::default()
+    }
+
+    pub fn add_mesh(&mut self, model: Model) -> MeshId {
+        self.meshes.push(model);
+        MeshId(self.meshes.len() - 1)
+    }
+
+    pub fn mesh(&self, id: MeshId) -> &Model {
+        &self.meshes[id.0]
+    }
+
+    pub fn mesh_mut(&mut self, id: MeshId) -> &mut Model {
+        &mut self.meshes[id.0]
+    }
+
+    /// Adds a camera, its position and orientation are replaced by the world transform of the
+    /// nodes it's attached to.
+    pub fn add_camera(&mut self, camera: Camera) -> CameraId {
+        self.cameras.push(camera);
+        CameraId(self.cameras.len() - 1)
+    }
+
+    pub fn camera(&self, id: CameraId) -> &Camera {
+        &self.cameras[id.0]
+    }
+
+    pub fn camera_mut(&mut self, id: CameraId) -> &mut Camera {
+        &mut self.cameras[id.0]
+    }
+
+    /// View and projection matrices of the camera attached to `id`, seen from the node's world
+    /// transform.
+    pub fn camera_matrices(&self, id: NodeId) -> Option<(Mat4, Mat4)> {
+        let node = &self.nodes[id.0];
+        let camera = &self.cameras[node.camera?.0];
+        Some((node.world.inverse()?, camera.projection_matrix()))
+    }
+
+    pub fn add_light(&mut self, light: Light) -> LightId {
+        self.lights.push(light);
+        LightId(self.lights.len() - 1)
+    }
+
+    pub fn light(&self, id: LightId) -> &Light {
+        &self.lights[id.0]
+    }
+
+    pub fn light_mut(&mut self, id: LightId) -> &mut Light {
+        &mut self.lights[id.0]
+    }
+
+    /// Every attached light moved to world space by the world transform of its node, ready to
+    /// be handed to shaders in a [`Lighting`](crate::light::Lighting).
+    pub fn world_lights(&self) -> Vec<Light> {
+        self.nodes
+            .iter()
+            .filter_map(|node| Some(self.lights[node.light?.0].transformed(&node.world)))
+            .collect()
+    }
+
+    /// Adds a node with an identity transform as the last child of `parent`, or as a root.
+    pub fn add_node(&mut self, name: &str, parent: Option<NodeId>) -> NodeId {
+        let id = NodeId(self.nodes.len());
+        self.nodes.push(Node {
+            name: name.to_string(),
+            mesh: None,
+            camera: None,
+            light: None,
+            transform: Transform::IDENTITY,
+            parent,
+            children: vec![],
+            world: Mat4::identity(),
+            dirty: true,
+        });
+        if let Some(parent) = parent {
+            self.nodes[parent.0].children.push(id);
+        }
+        id
+    }
+
+    pub fn node(&self, id: NodeId) -> &Node {
+        &self.nodes[id.0]
+    }
+
+    pub fn node_mut(&mut self, id: NodeId) -> &mut Node {
+        &mut self.nodes[id.0]
+    }
+
+    pub fn find_node(&self, name: &str) -> Option<NodeId> {
+        self.nodes.iter().position(|n| n.name == name).map(NodeId)
+    }
+
+    pub fn set_transform(&mut self, id: NodeId, transform: Transform) {
+        self.nodes[id.0].transform = transform;
+        self.mark_dirty(id);
+    }
+
+    /// Moves `id` and its subtree to the end of the children of `parent`, or makes it a root.
+    /// The local transform is kept, so the world transform changes with the new parent.
+    ///
+    /// Panics when `parent` is `id` or one of its descendants.
+    pub fn set_parent(&mut self, id: NodeId, parent: Option<NodeId>) {
+        let mut ancestor = parent;
+        while let Some(a) = ancestor {
+            assert!(a != id, "node {:?} can't be its own ancestor", id);
+            ancestor = self.nodes[a.0].parent;
+        }
+        if let Some(old) = self.nodes[id.0].parent {
+            self.nodes[old.0].children.retain(|&child| child != id);
+        }
+        if let Some(parent) = parent {
+            self.nodes[parent.0].children.push(id);
+        }
+        self.nodes[id.0].parent = parent;
+        self.mark_dirty(id);
+    }
+
+    fn mark_dirty(&mut self, id: NodeId) {
+        let mut stack = vec![id];
+        while let Some(id) = stack.pop() {
+            let node = &mut self.nodes[id.0];
+            node.dirty = true;
+            stack.extend_from_slice(&node.children);
+        }
+    }
+
+    pub fn roots(&self) -> impl Iterator<Item = NodeId> + '_ {
+        (0..self.nodes.len())
+            .filter(|&i| self.nodes[i].parent.is_none())
+            .map(NodeId)
+    }
+
+    /// Every node in depth first order, parents before their children.
+    pub fn depth_first(&self) -> Vec<NodeId> {
+        let mut order = Vec::with_capacity(self.nodes.len());
+        let mut stack: Vec<NodeId> = self.roots().collect();
+        stack.reverse();
+        while let Some(id) = stack.pop() {
+            order.push(id);
+            stack.extend(self.nodes[id.0].children.iter().rev());
+        }
+        order
+    }
+
+    /// Recomputes the cached world transforms of the nodes whose transform or ancestors changed
+    /// since the last update.
+    #[profiling::function]
+    pub fn update_world_transforms(&mut self) {
+        for id in self.depth_first() {
+            let node = &self.nodes[id.0];
+            if !node.dirty {
+                continue;
+            }
+            let local = node.transform.matrix();
+            let world = match node.parent {
+                Some(parent) => self.nodes[parent.0].world.mul(&local),
+                None => local,
+            };
+            let node = &mut self.nodes[id.0];
+            node.world = world;
+            node.dirty = false;
+        }
+    }
+
+    /// World space bounds of every attached mesh.
+    pub fn bounds(&self) -> Aabb {
+        self.nodes
+            .iter()
+            .filter_map(|node| {
+                let mesh = &self.meshes[node.mesh?.0];
+                Some(mesh.bounds.transform(&node.world))
+            })
+            .fold(Aabb::EMPTY, |bounds, b| bounds.union(&b))
+    }
+}
+
+impl Renderer {
+    /// Draws the mesh of every node of `scene` in depth first order, culled against the frustum
+    /// of `view_projection` combined with the node's world transform. `shader_for` creates the
+    /// shader of a node, which should apply the node's world transform. Returns the number of
+    /// submeshes drawn.
+    ///
+    /// Uses the world transforms of the last [`Scene::update_world_transforms`].
+    #[profiling::function]
+    pub fn render_scene<S, F>(
+        &mut self,
+        scene: &Scene,
+        view_projection: &Mat4,
+        mut shader_for: F,
+    ) -> usize
+    where
+        S: Shader<Vertex = Vertex>,
+        F: FnMut(&Node, &Model) -> S,
+    {
+        debug_assert!(
+            scene.nodes.iter().all(|node| !node.dirty),
+            "world transforms are out of date"
+        );
+        let mut drawn = 0;
+        for id in scene.depth_first() {
+            let node = scene.node(id);
+            let Some(mesh) = node.mesh else {
+                continue;
+            };
+            let model = scene.mesh(mesh);
+            let shader = shader_for(node, model);
+            drawn += self.draw_model(&shader, model, &view_projection.mul(&node.world));
+        }
+        drawn
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::color::LinearColor;
+    use crate::math::{vec3, Mat4x1};
+    use crate::testing::TestShader;
+    use std::f32::consts::FRAC_PI_2;
+
+    fn world_position(scene: &Scene, id: NodeId) -> Vec3 {
+        scene
+            .node(id)
+            .world_transform()
+            .mul_mat41(&Mat4x1::from(Vec3::ZERO))
+            .to_vec3()
+    }
+
+    fn assert_near(a: Vec3, b: Vec3) {
+        assert!((a - b).sqrt() < 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn test_hierarchical_transforms() {
+        let mut scene = Scene::new();
+        let sun = scene.add_node("sun", None);
+        let planet = scene.add_node("planet", Some(sun));
+        let moon = scene.add_node("moon", Some(planet));
+        scene.set_transform(
+            sun,
+            Transform::from_translation(vec3(10.0, 0.0, 0.0))
+                .with_rotation(Quat::from_axis_angle(&vec3(0.0, 0.0, 1.0), FRAC_PI_2)),
+        );
+        scene.set_transform(
+            planet,
+            Transform::from_translation(vec3(2.0, 0.0, 0.0)).with_scale(Vec3::splat(0.5)),
+        );
+        scene.set_transform(moon, Transform::from_translation(vec3(2.0, 0.0, 0.0)));
+        scene.update_world_transforms();
+        assert_near(world_position(&scene, planet), vec3(10.0, 2.0, 0.0));
+        assert_near(world_position(&scene, moon), vec3(10.0, 3.0, 0.0));
+
+        // moving a parent moves its subtree
+        scene.set_transform(sun, Transform::IDENTITY);
+        scene.update_world_transforms();
+        assert_near(world_position(&scene, moon), vec3(3.0, 0.0, 0.0));
+
+        scene.set_parent(moon, None);
+        scene.update_world_transforms();
+        assert_near(world_position(&scene, moon), vec3(2.0, 0.0, 0.0));
+        assert_eq!(scene.roots().collect::<Vec<_>>(), vec![sun, moon]);
+        assert_eq!(scene.depth_first(), vec![sun, planet, moon]);
+        assert_eq!(scene.find_node("planet"), Some(planet));
+
+        let camera = scene.add_camera(Camera::new(FRAC_PI_2, 1.0, 0.1, 10.0));
+        scene.node_mut(planet).camera = Some(camera);
+        let (view, projection) = scene.camera_matrices(planet).unwrap();
+        let origin = view.mul_mat41(&Mat4x1::from(Vec3::ZERO)).to_vec3();
+        assert_near(origin, vec3(-4.0, 0.0, 0.0));
+        assert_eq!(projection, scene.camera(camera).projection_matrix());
+        assert_eq!(scene.camera_matrices(sun), None);
+
+        let light = scene.add_light(Light::point(Vec3::ZERO, 10.0, Vec3::ONE, 1.0));
+        scene.node_mut(moon).light = Some(light);
+        let lights = scene.world_lights();
+        assert_eq!(lights.len(), 1);
+        assert_near(
+            lights[0].incident(&Vec3::ZERO).unwrap().0,
+            vec3(1.0, 0.0, 0.0),
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_parent_cycle() {
+        let mut scene = Scene::new();
+        let a = scene.add_node("a", None);
+        let b = scene.add_node("b", Some(a));
+        scene.set_parent(a, Some(b));
+    }
+
+    #[test]
+    fn test_render_scene() {
+        let mut scene = Scene::new();
+        let cube = scene.add_mesh(Model::cube(0.5, 1));
+        let group = scene.add_node("group", None);
+        let left = scene.add_node("left", Some(group));
+        let right = scene.add_node("right", Some(group));
+        let hidden = scene.add_node("hidden", Some(group));
+        for (id, x) in [(left, -0.5), (right, 0.5), (hidden, 5.0)] {
+            scene.node_mut(id).mesh = Some(cube);
+            scene.set_transform(id, Transform::from_translation(vec3(x, 0.0, 0.0)));
+        }
+        scene.set_transform(group, Transform::IDENTITY.with_scale(Vec3::splat(0.8)));
+        scene.update_world_transforms();
+        assert_near(scene.bounds().min, vec3(-0.6, -0.2, -0.2));
+
+        let mut renderer = Renderer::new(20, 20, false);
+        renderer.clear(Color::BLACK);
+        let view_projection = Mat4::identity();
+        let drawn = renderer.render_scene(&scene, &view_projection, |node, _model| {
+            let color = if node.name == "left" {
+                Color::RED
+            } else {
+                Color::GREEN
+            };
+            TestShader::new(view_projection.mul(node.world_transform()))
+                .with_color(LinearColor::from(color))
+        });
+        assert_eq!(drawn, 2);
+        // the cubes span -0.6..-0.2 and 0.2..0.6 in x
+        assert_eq!(renderer.pixels()[10 * 20 + 5], Color::RED);
+        assert_eq!(renderer.pixels()[10 * 20 + 14], Color::GREEN);
+        assert_eq!(renderer.pixels()[10 * 20 + 10], Color::BLACK);
+    }
+}
@@ -0,0 +1,125 @@
+use crate::color::{Color, ColorSpace, LinearColor};
+use crate::math::{Mat4, Vec2, Vec3, Vec4};
+
+/// Values written by the vertex stage and interpolated across lines and triangles before they
+/// reach the fragment stage.
+pub trait Varying: Copy {
+    fn scaled(&self, factor: f32) -> Self;
+
+    fn added(&self, other: &Self) -> Self;
+
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        self.scaled(1.0 - t).added(&other.scaled(t))
+    }
+
+    /// Weighted sum of the values at the three corners of a triangle.
+    fn interpolate(values: [&Self; 3], weights: &Vec3) -> Self {
+        values[0]
+            .scaled(weights.x)
+            .added(&values[1].scaled(weights.y))
+            .added(&values[2].scaled(weights.z))
+    }
+}
+
+macro_rules! impl_varying_for_vec {
+    ($($t:ty),+) => {
+        $(impl Varying for $t {
+            fn scaled(&self, factor: f32) -> Self {
+                *self * factor
+            }
+
+            fn added(&self, other: &Self) -> Self {
+                *self + *other
+            }
+        })+
+    };
+}
+
+impl_varying_for_vec!(f32, Vec2, Vec3, Vec4, LinearColor);
+
+impl Varying for () {
+    fn scaled(&self, _factor: f32) -> Self {}
+
+    fn added(&self, _other: &Self) -> Self {}
+}
+
+macro_rules! impl_varying_for_tuple {
+    ($($name:ident $index:tt),+) => {
+        impl<$($name: Varying),+> Varying for ($($name,)+) {
+            fn scaled(&self, factor: f32) -> Self {
+                ($(self.$index.scaled(factor),)+)
+            }
+
+            fn added(&self, other: &Self) -> Self {
+                ($(self.$index.added(&other.$index),)+)
+            }
+        }
+    };
+}
+
+impl_varying_for_tuple!(A 0);
+impl_varying_for_tuple!(A 0, B 1);
+impl_varying_for_tuple!(A 0, B 1, C 2);
+impl_varying_for_tuple!(A 0, B 1, C 2, D 3);
+
+/// Fixed function inputs of the fragment stage.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Fragment {
+    /// Window coordinates of the shaded point in output pixels, and its depth.
+    pub position: Vec3,
+    /// Always true for lines and points.
+    pub front_facing: bool,
+}
+
+/// Per-instance attributes passed to the vertex stage by
+/// [`Renderer::draw_indexed_instanced`](crate::renderer::Renderer::draw_indexed_instanced).
+/// Non-instanced draws use the default, an identity transform and white.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Instance {
+    pub transform: Mat4,
+    pub color: Color,
+}
+
+/// Programmable stages run by [`Renderer::draw_indexed`](crate::renderer::Renderer::draw_indexed).
+pub trait Shader {
+    type Vertex;
+    type Varying: Varying;
+
+    /// Returns the clip space position of `vertex` and the values to interpolate.
+    /// `instance_index` is the position of `instance` in the instance slice of the draw.
+    fn vertex(
+        &self,
+        vertex: &Self::Vertex,
+        instance_index: u32,
+        instance: &Instance,
+    ) -> (Vec4, Self::Varying);
+
+    /// Returns the sRGB encoded color of a fragment, or `None` to discard it.
+    fn fragment(&self, varying: &Self::Varying, fragment: &Fragment) -> Option<Color>;
+
+    /// Returns the linear color of a fragment, which may exceed 1 when the renderer has a high
+    /// dynamic range target. Only this method is called by the renderer, it defaults to
+    /// [`Shader::fragment`] decoded from sRGB. Shaders that light or blend colors should
+    /// override it and do their math here.
+    fn fragment_linear(&self, varying: &Self::Varying, fragment: &Fragment) -> Option<LinearColor> {
+        self.fragment(varying, fragment)
+            .map(|color| ColorSpace::Srgb.decode(color))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::{vec2, vec3};
+
+    #[test]
+    fn test_tuple_interpolation() {
+        let a = (1.0, vec2(0.0, 0.0));
+        let b = (3.0, vec2(1.0, 0.0));
+        let c = (5.0, vec2(0.0, 1.0));
+        let (value, uv) = Varying::interpolate([&a, &b, &c], &vec3(0.5, 0.25, 0.25));
+        assert_eq!(value, 2.5);
+        assert_eq!(uv, vec2(0.25, 0.25));
+        assert_eq!(a.lerp(&b, 0.5), (2.0, vec2(0.5, 0.0)));
+    }
+}
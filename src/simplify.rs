@@ -0,0 +1,543 @@
+use crate::math::{Mat4, Mat4x1, Vec3};
+use crate::model::{Model, Submesh, Vertex};
+use crate::viewport::Viewport;
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+
+/// Symmetric 4x4 matrix accumulating the squared distances to a set of planes, stored as its
+/// upper triangle followed by the total weight of the planes.
+#[derive(Clone, Copy, Debug, Default)]
+struct Quadric([f64; 11]);
+
+impl Quadric {
+    /// Plane through `p` with unit normal `n`, weighted by `weight`.
+    fn from_plane(n: Vec3, p: Vec3, weight: f64) -> Self {
+        let [a, b, c] = [n.x as f64, n.y as f64, n.z as f64];
+        let d = -(a * p.x as f64 + b * p.y as f64 + c * p.z as f64);
+        Quadric([
+            a * a,
+            a * b,
+            a * c,
+            a * d,
+            b * b,
+            b * c,
+            b * d,
+            c * c,
+            c * d,
+            d * d,
+            1.0,
+        ])
+        .scaled(weight)
+    }
+
+    fn scaled(&self, s: f64) -> Self {
+        Quadric(self.0.map(|q| q * s))
+    }
+
+    fn added(&self, other: &Self) -> Self {
+        let mut sum = self.0;
+        for (s, o) in sum.iter_mut().zip(other.0) {
+            *s += o;
+        }
+        Quadric(sum)
+    }
+
+    /// Weighted mean of the squared distances from `p` to the planes.
+    fn evaluate(&self, p: &Vec3) -> f64 {
+        let [a2, ab, ac, ad, b2, bc, bd, c2, cd, d2, weight] = self.0;
+        if weight <= 0.0 {
+            return 0.0;
+        }
+        let [x, y, z] = [p.x as f64, p.y as f64, p.z as f64];
+        let sum = a2 * x * x
+            + 2.0 * ab * x * y
+            + 2.0 * ac * x * z
+            + 2.0 * ad * x
+            + b2 * y * y
+            + 2.0 * bc * y * z
+            + 2.0 * bd * y
+            + c2 * z * z
+            + 2.0 * cd * z
+            + d2;
+        sum / weight
+    }
+}
+
+/// Collapse of the position `from` onto the position `to`, ordered by increasing cost.
+struct Collapse {
+    cost: f64,
+    from: u32,
+    to: u32,
+    from_version: u32,
+    to_version: u32,
+}
+
+impl PartialEq for Collapse {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Collapse {}
+
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Collapse {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // BinaryHeap is a max heap, the cheapest collapse must come out first
+        other.cost.total_cmp(&self.cost)
+    }
+}
+
+/// Reduces a triangle list to at most `target_index_count` indices by collapsing edges in order
+/// of their quadric error, stopping early when the next collapse would move the surface by more
+/// than `target_error` model units. Returns the new indices, which refer to the same vertices,
+/// and the largest error introduced, the area weighted RMS distance to the original planes.
+///
+/// Vertices on open borders and on seams, where one position has several texture coordinates
+/// or normals, never move, so the outline of the mesh, its texture mapping and its hard edges
+/// are preserved.
+#[profiling::function]
+pub fn simplify(
+    vertices: &[Vertex],
+    indices: &[u32],
+    target_index_count: usize,
+    target_error: f32,
+) -> (Vec<u32>, f32) {
+    // weld vertices that only differ in uv, collapses work on positions
+    let mut welded: HashMap<[u32; 3], u32> = HashMap::new();
+    let mut positions: Vec<Vec3> = vec![];
+    let mut variants: Vec<Vec<u32>> = vec![];
+    let position_ids: Vec<u32> = vertices
+        .iter()
+        .enumerate()
+        .map(|(v, vertex)| {
+            let p = vertex.position;
+            let key = [p.x.to_bits(), p.y.to_bits(), p.z.to_bits()];
+            let id = *welded.entry(key).or_insert_with(|| {
+                positions.push(p);
+                variants.push(vec![]);
+                positions.len() as u32 - 1
+            });
+            variants[id as usize].push(v as u32);
+            id
+        })
+        .collect();
+    let position_count = positions.len();
+
+    let mut triangles: Vec<[u32; 3]> = indices
+        .chunks_exact(3)
+        .map(|t| [t[0], t[1], t[2]])
+        .collect();
+    let ids = |triangle: &[u32; 3]| triangle.map(|v| position_ids[v as usize]);
+    let mut alive = vec![true; triangles.len()];
+    let mut live_count = triangles.len();
+
+    let mut adjacency: Vec<Vec<u32>> = vec![vec![]; position_count];
+    let mut quadrics = vec![Quadric::default(); position_count];
+    let mut edge_uses: HashMap<(u32, u32), u32> = HashMap::new();
+    for (t, triangle) in triangles.iter().enumerate() {
+        let corners = ids(triangle);
+        let [a, b, c] = corners.map(|id| positions[id as usize]);
+        let normal = (b - a).cross(&(c - a));
+        let area = normal.sqrt();
+        let quadric = if area > 0.0 {
+            Quadric::from_plane(normal / area, a, area as f64)
+        } else {
+            Quadric::default()
+        };
+        for i in 0..3 {
+            let id = corners[i] as usize;
+            adjacency[id].push(t as u32);
+            quadrics[id] = quadrics[id].added(&quadric);
+            let next = corners[(i + 1) % 3];
+            let edge = (corners[i].min(next), corners[i].max(next));
+            *edge_uses.entry(edge).or_default() += 1;
+        }
+    }
+
+    let mut locked: Vec<bool> = variants
+        .iter()
+        .map(|vs| {
+            let first = &vertices[vs[0] as usize];
+            vs.iter().any(|&v| {
+                let vertex = &vertices[v as usize];
+                vertex.uv != first.uv || vertex.normal != first.normal
+            })
+        })
+        .collect();
+    for (&(a, b), &uses) in &edge_uses {
+        if uses == 1 {
+            locked[a as usize] = true;
+            locked[b as usize] = true;
+        }
+    }
+
+    let mut versions = vec![0u32; position_count];
+    let mut heap = BinaryHeap::new();
+    let push_collapses = |heap: &mut BinaryHeap<Collapse>,
+                          id: u32,
+                          adjacency: &[Vec<u32>],
+                          alive: &[bool],
+                          triangles: &[[u32; 3]],
+                          quadrics: &[Quadric],
+                          versions: &[u32]| {
+        for &t in &adjacency[id as usize] {
+            if !alive[t as usize] {
+                continue;
+            }
+            for neighbor in ids(&triangles[t as usize]) {
+                if neighbor == id {
+                    continue;
+                }
+                for (from, to) in [(id, neighbor), (neighbor, id)] {
+                    if locked[from as usize] {
+                        continue;
+                    }
+                    let quadric = quadrics[from as usize].added(&quadrics[to as usize]);
+                    heap.push(Collapse {
+                        cost: quadric.evaluate(&positions[to as usize]).max(0.0),
+                        from,
+                        to,
+                        from_version: versions[from as usize],
+                        to_version: versions[to as usize],
+                    });
+                }
+            }
+        }
+    };
+    for id in 0..position_count as u32 {
+        push_collapses(
+            &mut heap, id, &adjacency, &alive, &triangles, &quadrics, &versions,
+        );
+    }
+
+    let max_cost = target_error as f64 * target_error as f64;
+    let mut error = 0.0f64;
+    let mut removed = vec![false; position_count];
+    while live_count * 3 > target_index_count {
+        let Some(collapse) = heap.pop() else {
+            break;
+        };
+        let (from, to) = (collapse.from as usize, collapse.to as usize);
+        if removed[from]
+            || removed[to]
+            || versions[from] != collapse.from_version
+            || versions[to] != collapse.to_version
+        {
+            continue;
+        }
+        if collapse.cost > max_cost {
+            break;
+        }
+        // moving `from` must not fold any of the remaining triangles over
+        let flips = adjacency[from].iter().any(|&t| {
+            let corners = ids(&triangles[t as usize]);
+            if !alive[t as usize] || corners.contains(&(to as u32)) {
+                return false;
+            }
+            let before = corners.map(|id| positions[id as usize]);
+            let after = corners.map(|id| {
+                if id as usize == from {
+                    positions[to]
+                } else {
+                    positions[id as usize]
+                }
+            });
+            let normal = |[a, b, c]: [Vec3; 3]| (b - a).cross(&(c - a));
+            normal(before).dot(&normal(after)) <= 0.0
+        });
+        if flips {
+            continue;
+        }
+
+        error = error.max(collapse.cost);
+        for t in std::mem::take(&mut adjacency[from]) {
+            if !alive[t as usize] {
+                continue;
+            }
+            let triangle = &mut triangles[t as usize];
+            if ids(triangle).contains(&(to as u32)) {
+                alive[t as usize] = false;
+                live_count -= 1;
+                continue;
+            }
+            for v in triangle.iter_mut() {
+                if position_ids[*v as usize] as usize == from {
+                    *v = nearest_variant(vertices, &variants[to], &vertices[*v as usize]);
+                }
+            }
+            adjacency[to].push(t);
+        }
+        quadrics[to] = quadrics[to].added(&quadrics[from]);
+        removed[from] = true;
+        versions[to] += 1;
+        push_collapses(
+            &mut heap, to as u32, &adjacency, &alive, &triangles, &quadrics, &versions,
+        );
+    }
+
+    let indices = triangles
+        .iter()
+        .zip(&alive)
+        .filter(|(_, alive)| **alive)
+        .flat_map(|(triangle, _)| *triangle)
+        .collect();
+    (indices, error.sqrt() as f32)
+}
+
+/// Picks the vertex among `variants`, which share a position, whose attributes are closest to
+/// those of `vertex`.
+fn nearest_variant(vertices: &[Vertex], variants: &[u32], vertex: &Vertex) -> u32 {
+    let distance = |v: u32| {
+        let other = &vertices[v as usize];
+        (other.uv - vertex.uv).sqrt() + (other.normal - vertex.normal).sqrt()
+    };
+    *variants
+        .iter()
+        .min_by(|&&a, &&b| distance(a).total_cmp(&distance(b)))
+        .unwrap()
+}
+
+/// One level of detail, an index buffer into the vertices of the full detail model.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LodLevel {
+    pub indices: Vec<u32>,
+    /// The submeshes of the model with their ranges in `indices`, in the same order.
+    pub submeshes: Vec<Submesh>,
+    /// Approximate distance in model units between this level and the full detail surface.
+    pub error: f32,
+}
+
+/// Levels of detail of a model, from full detail to coarsest.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LodChain {
+    pub levels: Vec<LodLevel>,
+}
+
+impl LodChain {
+    /// Builds up to `level_count` levels, each with about `ratio` times the triangles of the
+    /// previous one. Every submesh is simplified on its own, keeping its borders. Stops early
+    /// when simplification can't remove any more triangles.
+    #[profiling::function]
+    pub fn generate(model: &Model, level_count: usize, ratio: f32) -> Self {
+        let mut levels = vec![LodLevel {
+            indices: model.indices.clone(),
+            submeshes: model.submeshes.clone(),
+            error: 0.0,
+        }];
+        while levels.len() < level_count {
+            let previous = levels.last().unwrap();
+            let mut level = LodLevel::default();
+            for submesh in &previous.submeshes {
+                let source = &previous.indices[submesh.index_range()];
+                let target = ((source.len() as f32 * ratio) as usize / 3) * 3;
+                let (indices, error) = simplify(&model.vertices, source, target, f32::MAX);
+                level.submeshes.push(Submesh {
+                    first_index: level.indices.len() as u32,
+                    index_count: indices.len() as u32,
+                    ..*submesh
+                });
+                level.indices.extend(indices);
+                level.error = level.error.max(error);
+            }
+            if level.indices.len() >= previous.indices.len() {
+                break;
+            }
+            // errors are measured against the previous level, accumulate them
+            level.error += previous.error;
+            levels.push(level);
+        }
+        LodChain { levels }
+    }
+
+    /// Returns the index of the coarsest level whose error stays under `max_pixel_error` pixels
+    /// on screen, where `pixels_per_unit` is the projected size of one model unit as returned by
+    /// [`pixels_per_unit`]. Always the finest level when that size is infinite or undefined. See
+    /// [`Renderer::draw_model_lod`](crate::renderer::Renderer::draw_model_lod).
+    pub fn select(&self, pixels_per_unit: f32, max_pixel_error: f32) -> usize {
+        if !pixels_per_unit.is_finite() {
+            return 0;
+        }
+        self.levels
+            .iter()
+            .rposition(|level| level.error * pixels_per_unit <= max_pixel_error)
+            .unwrap_or(0)
+    }
+}
+
+/// Size in pixels of one model unit at `point` once projected by `model_view_projection` into
+/// `viewport`, the largest along the three model axes. Infinite at or behind the eye.
+pub fn pixels_per_unit(model_view_projection: &Mat4, point: &Vec3, viewport: &Viewport) -> f32 {
+    let m = model_view_projection;
+    let clip = m.mul_mat41(&Mat4x1::from(*point)).to_vec4();
+    if clip.w <= f32::EPSILON {
+        return f32::INFINITY;
+    }
+    (0..3)
+        .map(|axis| {
+            // derivative of the window position along the axis, by the quotient rule
+            let dw = m[(3, axis)] / clip.w;
+            let dx = (m[(0, axis)] - clip.x * dw) / clip.w * viewport.width / 2.0;
+            let dy = (m[(1, axis)] - clip.y * dw) / clip.w * viewport.height / 2.0;
+            (dx * dx + dy * dy).sqrt()
+        })
+        .fold(0.0, f32::max)
+}
+
+impl Model {
+    /// See [`LodChain::generate`].
+    pub fn generate_lods(&self, level_count: usize, ratio: f32) -> LodChain {
+        LodChain::generate(self, level_count, ratio)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::Camera;
+    use crate::math::vec3;
+    use crate::testing::grid;
+
+    fn referenced(indices: &[u32], vertex_count: usize) -> Vec<bool> {
+        let mut used = vec![false; vertex_count];
+        indices.iter().for_each(|&i| used[i as usize] = true);
+        used
+    }
+
+    #[test]
+    fn test_simplify_flat_grid_keeps_border() {
+        let n = 8;
+        let (vertices, indices) = grid(n, |_, _| 0.0);
+        let (simplified, error) = simplify(&vertices, &indices, 0, f32::MAX);
+        // a flat grid collapses to close to the 30 triangles spanning its 32 border vertices
+        assert!(simplified.len() <= 40 * 3, "{}", simplified.len());
+        assert!(error < 1e-3);
+        let used = referenced(&simplified, vertices.len());
+        for (v, vertex) in vertices.iter().enumerate() {
+            let p = vertex.position;
+            if p.x == 0.0 || p.y == 0.0 || p.x == 1.0 || p.y == 1.0 {
+                assert!(used[v], "border vertex {} was removed", v);
+            }
+        }
+    }
+
+    #[test]
+    fn test_simplify_error_bound_and_seams() {
+        let n = 8;
+        let (mut vertices, mut indices) = grid(n, |x, y| (x * 6.0).sin() * (y * 6.0).cos() * 0.2);
+        // split the middle column into a seam, the right half gets its own copies
+        let column = n / 2;
+        let mut seam = vec![];
+        for y in 0..=n {
+            let v = (y * (n + 1) + column) as usize;
+            let mut copy = vertices[v];
+            copy.uv.x += 0.5;
+            vertices.push(copy);
+            seam.push((v as u32, vertices.len() as u32 - 1));
+        }
+        for triangle in indices.chunks_exact_mut(3) {
+            let right = triangle
+                .iter()
+                .any(|&i| i % (n + 1) > column && (i as usize) < (n as usize + 1).pow(2));
+            if right {
+                for i in triangle.iter_mut() {
+                    if let Some((_, copy)) = seam.iter().find(|(v, _)| v == i) {
+                        *i = *copy;
+                    }
+                }
+            }
+        }
+
+        let (coarse, coarse_error) = simplify(&vertices, &indices, 0, 0.01);
+        assert!(coarse_error <= 0.01);
+        assert!(coarse.len() < indices.len());
+        let used = referenced(&coarse, vertices.len());
+        for (v, copy) in &seam {
+            assert!(used[*v as usize] && used[*copy as usize]);
+        }
+
+        let (finer, finer_error) = simplify(&vertices, &indices, 0, 0.001);
+        assert!(finer.len() >= coarse.len());
+        assert!(finer_error <= coarse_error);
+    }
+
+    #[test]
+    fn test_lod_selection() {
+        let chain = LodChain {
+            levels: [(300, 0.0), (150, 0.01), (60, 0.1)]
+                .map(|(count, error)| LodLevel {
+                    indices: vec![0; count],
+                    submeshes: vec![],
+                    error,
+                })
+                .to_vec(),
+        };
+        let mut camera = Camera::new(std::f32::consts::FRAC_PI_2, 1.0, 0.1, 100.0);
+        let viewport = Viewport::new(0.0, 0.0, 800.0, 800.0);
+        let near = pixels_per_unit(&camera.view_projection_matrix(), &Vec3::ZERO, &viewport);
+        camera.position = vec3(0.0, 0.0, 1.0);
+        let one = pixels_per_unit(&camera.view_projection_matrix(), &Vec3::ZERO, &viewport);
+        camera.position = vec3(0.0, 0.0, 10.0);
+        let ten = pixels_per_unit(&camera.view_projection_matrix(), &Vec3::ZERO, &viewport);
+        assert_eq!(near, f32::INFINITY);
+        assert!((one - 400.0).abs() < 1e-3);
+        assert!((ten - 40.0).abs() < 1e-3);
+        assert_eq!(chain.select(one, 1.0), 0);
+        assert_eq!(chain.select(ten, 1.0), 1);
+        assert_eq!(chain.select(1.0, 1.0), 2);
+        // at or behind the eye even a lossless level would be infinitely large
+        assert_eq!(chain.select(near, 1.0), 0);
+        assert_eq!(chain.select(near, f32::INFINITY), 0);
+        assert_eq!(chain.select(f32::NAN, 1.0), 0);
+    }
+
+    #[test]
+    fn test_lod_chain_submeshes() {
+        // two grids side by side as separate submeshes
+        let (mut vertices, mut indices) = grid(8, |x, y| (x * 6.0).sin() * (y * 6.0).cos() * 0.2);
+        let (right, right_indices) = grid(8, |_, _| 0.0);
+        let offset = vertices.len() as u32;
+        let split = indices.len() as u32;
+        vertices.extend(right.into_iter().map(|v| Vertex {
+            position: v.position + vec3(1.0, 0.0, 0.0),
+            ..v
+        }));
+        indices.extend(right_indices.iter().map(|i| i + offset));
+        let mut model = Model {
+            vertices,
+            submeshes: vec![
+                Submesh {
+                    first_index: 0,
+                    index_count: split,
+                    ..Default::default()
+                },
+                Submesh {
+                    first_index: split,
+                    index_count: indices.len() as u32 - split,
+                    ..Default::default()
+                },
+            ],
+            indices,
+            diffuse: Default::default(),
+            bounds: Default::default(),
+        };
+        model.compute_bounds();
+
+        let chain = model.generate_lods(3, 0.5);
+        assert_eq!(chain.levels.len(), 3);
+        for level in &chain.levels {
+            assert_eq!(level.submeshes.len(), 2);
+            let [left, right] = [0, 1].map(|s| &level.indices[level.submeshes[s].index_range()]);
+            assert_eq!(left.len() + right.len(), level.indices.len());
+            // every submesh keeps to its own vertices and bounds
+            assert!(left.iter().all(|&i| i < offset) && right.iter().all(|&i| i >= offset));
+            assert_eq!(level.submeshes[1].bounds, model.submeshes[1].bounds);
+        }
+        assert!(chain.levels[2].indices.len() < chain.levels[1].indices.len());
+    }
+}
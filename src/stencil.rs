@@ -0,0 +1,168 @@
+/// Comparison between a reference value and the value stored in a buffer.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CompareFunction {
+    Never,
+    Less,
+    Equal,
+    LessEqual,
+    Greater,
+    NotEqual,
+    GreaterEqual,
+    #[default]
+    Always,
+}
+
+impl CompareFunction {
+    /// Returns `reference OP stored`, e.g. `Less` passes when the reference is less than the
+    /// stored value.
+    pub fn test<T: PartialOrd>(&self, reference: T, stored: T) -> bool {
+        match self {
+            CompareFunction::Never => false,
+            CompareFunction::Less => reference < stored,
+            CompareFunction::Equal => reference == stored,
+            CompareFunction::LessEqual => reference <= stored,
+            CompareFunction::Greater => reference > stored,
+            CompareFunction::NotEqual => reference != stored,
+            CompareFunction::GreaterEqual => reference >= stored,
+            CompareFunction::Always => true,
+        }
+    }
+}
+
+/// Operation applied to the stencil buffer depending on the outcome of the stencil and depth tests.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StencilOp {
+    #[default]
+    Keep,
+    Zero,
+    Replace,
+    IncrementClamp,
+    DecrementClamp,
+    Invert,
+    IncrementWrap,
+    DecrementWrap,
+}
+
+impl StencilOp {
+    pub fn apply(&self, value: u8, reference: u8) -> u8 {
+        match self {
+            StencilOp::Keep => value,
+            StencilOp::Zero => 0,
+            StencilOp::Replace => reference,
+            StencilOp::IncrementClamp => value.saturating_add(1),
+            StencilOp::DecrementClamp => value.saturating_sub(1),
+            StencilOp::Invert => !value,
+            StencilOp::IncrementWrap => value.wrapping_add(1),
+            StencilOp::DecrementWrap => value.wrapping_sub(1),
+        }
+    }
+}
+
+/// Stencil test and operations for one triangle facing.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StencilFaceState {
+    pub compare: CompareFunction,
+    /// Applied when the stencil test fails.
+    pub fail_op: StencilOp,
+    /// Applied when the stencil test passes but the depth test fails.
+    pub depth_fail_op: StencilOp,
+    /// Applied when both tests pass.
+    pub pass_op: StencilOp,
+}
+
+impl StencilFaceState {
+    pub const IGNORE: StencilFaceState = StencilFaceState {
+        compare: CompareFunction::Always,
+        fail_op: StencilOp::Keep,
+        depth_fail_op: StencilOp::Keep,
+        pass_op: StencilOp::Keep,
+    };
+}
+
+/// Configuration of the 8-bit stencil attachment. The default state never rejects a fragment
+/// and never writes, which is the same as having no stencil at all.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StencilState {
+    pub front: StencilFaceState,
+    pub back: StencilFaceState,
+    /// Applied to both the reference and the stored value before comparing.
+    pub read_mask: u8,
+    /// Bits of the stored value that the stencil ops may change.
+    pub write_mask: u8,
+    pub reference: u8,
+}
+
+impl StencilState {
+    pub fn is_enabled(&self) -> bool {
+        self.front != StencilFaceState::IGNORE || self.back != StencilFaceState::IGNORE
+    }
+
+    pub fn face(&self, front_facing: bool) -> &StencilFaceState {
+        if front_facing {
+            &self.front
+        } else {
+            &self.back
+        }
+    }
+
+    pub fn test(&self, face: &StencilFaceState, stored: u8) -> bool {
+        face.compare
+            .test(self.reference & self.read_mask, stored & self.read_mask)
+    }
+
+    /// Returns the new stored value after applying `op` through the write mask.
+    pub fn update(&self, op: StencilOp, stored: u8) -> u8 {
+        let value = op.apply(stored, self.reference);
+        (stored & !self.write_mask) | (value & self.write_mask)
+    }
+}
+
+impl Default for StencilState {
+    fn default() -> Self {
+        StencilState {
+            front: StencilFaceState::IGNORE,
+            back: StencilFaceState::IGNORE,
+            read_mask: 0xff,
+            write_mask: 0xff,
+            reference: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stencil_ops() {
+        assert_eq!(StencilOp::Keep.apply(7, 3), 7);
+        assert_eq!(StencilOp::Zero.apply(7, 3), 0);
+        assert_eq!(StencilOp::Replace.apply(7, 3), 3);
+        assert_eq!(StencilOp::IncrementClamp.apply(255, 0), 255);
+        assert_eq!(StencilOp::DecrementClamp.apply(0, 0), 0);
+        assert_eq!(StencilOp::IncrementWrap.apply(255, 0), 0);
+        assert_eq!(StencilOp::DecrementWrap.apply(0, 0), 255);
+        assert_eq!(StencilOp::Invert.apply(0b1010_0000, 0), 0b0101_1111);
+    }
+
+    #[test]
+    fn test_stencil_masks() {
+        let state = StencilState {
+            front: StencilFaceState {
+                compare: CompareFunction::Equal,
+                ..StencilFaceState::IGNORE
+            },
+            read_mask: 0x0f,
+            write_mask: 0xf0,
+            reference: 0x12,
+            ..Default::default()
+        };
+        assert!(state.is_enabled());
+        // only the low nibble is compared
+        assert!(state.test(&state.front, 0xf2));
+        assert!(!state.test(&state.front, 0x13));
+        // only the high nibble is written
+        assert_eq!(state.update(StencilOp::Replace, 0xab), 0x1b);
+        assert!(!StencilState::default().is_enabled());
+    }
+}
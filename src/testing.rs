@@ -0,0 +1,113 @@
+//! Fixtures shared by the tests of several modules.
+
+use crate::color::{Color, LinearColor};
+use crate::math::{vec2, vec3, Mat4, Mat4x1, Vec3, Vec4};
+use crate::model::Vertex;
+use crate::shader::{Fragment, Instance, Shader};
+use std::cell::Cell;
+use std::marker::PhantomData;
+
+/// Vertices [`TestShader`] can draw.
+pub(crate) trait TestVertex {
+    fn position(&self) -> Vec4;
+
+    fn color(&self) -> LinearColor {
+        LinearColor::WHITE
+    }
+}
+
+impl TestVertex for Vec3 {
+    fn position(&self) -> Vec4 {
+        Vec4::new(self.x, self.y, self.z, 1.0)
+    }
+}
+
+impl TestVertex for Vertex {
+    fn position(&self) -> Vec4 {
+        self.position.position()
+    }
+}
+
+/// A clip space position and a gray level.
+impl TestVertex for (Vec4, f32) {
+    fn position(&self) -> Vec4 {
+        self.0
+    }
+
+    fn color(&self) -> LinearColor {
+        LinearColor::rgb(self.1, self.1, self.1)
+    }
+}
+
+/// Pass-through shader: positions are transformed by the instance and `transform`, fragments
+/// get `color` times the vertex and instance colors. Counts the vertex stage invocations.
+pub(crate) struct TestShader<V> {
+    pub transform: Mat4,
+    pub color: LinearColor,
+    pub invocations: Cell<u32>,
+    vertex: PhantomData<fn(&V)>,
+}
+
+impl<V> TestShader<V> {
+    pub fn new(transform: Mat4) -> Self {
+        TestShader {
+            transform,
+            color: LinearColor::WHITE,
+            invocations: Cell::new(0),
+            vertex: PhantomData,
+        }
+    }
+
+    pub fn with_color(self, color: LinearColor) -> Self {
+        TestShader { color, ..self }
+    }
+}
+
+impl<V: TestVertex> Shader for TestShader<V> {
+    type Vertex = V;
+    type Varying = LinearColor;
+
+    fn vertex(&self, vertex: &V, _instance_index: u32, instance: &Instance) -> (Vec4, LinearColor) {
+        self.invocations.set(self.invocations.get() + 1);
+        let p = vertex.position();
+        let position = instance
+            .transform
+            .mul(&self.transform)
+            .mul_mat41(&Mat4x1::new([p.x, p.y, p.z, p.w]))
+            .to_vec4();
+        let color = self.color * vertex.color() * LinearColor::from(instance.color);
+        (position, color)
+    }
+
+    fn fragment(&self, color: &LinearColor, fragment: &Fragment) -> Option<Color> {
+        self.fragment_linear(color, fragment).map(Color::from)
+    }
+
+    fn fragment_linear(&self, color: &LinearColor, _fragment: &Fragment) -> Option<LinearColor> {
+        Some(*color)
+    }
+}
+
+/// `n` x `n` quads over 0..1 in x and y on the surface z = `height(x, y)`, two triangles per
+/// quad. Uvs follow x and y.
+pub(crate) fn grid(n: u32, height: impl Fn(f32, f32) -> f32) -> (Vec<Vertex>, Vec<u32>) {
+    let mut vertices = vec![];
+    for y in 0..=n {
+        for x in 0..=n {
+            let (u, v) = (x as f32 / n as f32, y as f32 / n as f32);
+            vertices.push(Vertex {
+                position: vec3(u, v, height(u, v)),
+                uv: vec2(u, v),
+                normal: vec3(0.0, 0.0, 1.0),
+            });
+        }
+    }
+    let mut indices = vec![];
+    for y in 0..n {
+        for x in 0..n {
+            let i = y * (n + 1) + x;
+            indices.extend([i, i + 1, i + n + 2, i, i + n + 2, i + n + 1]);
+        }
+    }
+    (vertices, indices)
+}
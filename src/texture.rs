@@ -1,5 +1,6 @@
-use crate::color::Color;
+use crate::color::{Color, ColorSpace, LinearColor};
 use crate::math::Vec2f;
+use crate::resample::{resample_linear, ResampleFilter};
 use anyhow::Result;
 use image::io::Reader as ImageReader;
 use std::path::Path;
@@ -8,16 +9,68 @@ pub struct Texture {
     pub pixels: Vec<Color>,
     pub width: u32,
     pub height: u32,
+    /// Encoding of `pixels`, [`Texture::sample`] decodes it.
+    pub color_space: ColorSpace,
+}
+
+impl Default for Texture {
+    fn default() -> Self {
+        Texture::solid(Color::WHITE)
+    }
 }
 
 impl Texture {
+    /// A single sRGB texel of `color`, for models without a texture.
+    pub fn solid(color: Color) -> Self {
+        Texture {
+            pixels: vec![color],
+            width: 1,
+            height: 1,
+            color_space: ColorSpace::Srgb,
+        }
+    }
+
+    pub fn with_color_space(self, color_space: ColorSpace) -> Self {
+        Texture {
+            color_space,
+            ..self
+        }
+    }
+
+    /// Nearest texel at `uv`, coordinates outside of [0, 1) wrap around.
     pub fn get_color(&self, uv: &Vec2f) -> Color {
-        let x = (uv.x * self.width as f32) as u32;
-        let y = (uv.y * self.height as f32) as u32;
+        let x = ((uv.x.rem_euclid(1.0) * self.width as f32) as u32).min(self.width - 1);
+        let y = ((uv.y.rem_euclid(1.0) * self.height as f32) as u32).min(self.height - 1);
         let index = (y * self.width + x) as usize;
         self.pixels[index]
     }
 
+    /// Nearest texel at `uv` as linear values, see [`Texture::get_color`].
+    pub fn sample(&self, uv: &Vec2f) -> LinearColor {
+        self.color_space.decode(self.get_color(uv))
+    }
+
+    /// Returns a copy of the texture resampled to `width`x`height`, filtered in linear light.
+    pub fn resized(&self, width: u32, height: u32, filter: ResampleFilter) -> Self {
+        let linear: Vec<LinearColor> = self
+            .pixels
+            .iter()
+            .map(|c| self.color_space.decode(*c))
+            .collect();
+        let resampled = resample_linear(&linear, self.width, self.height, width, height, filter);
+        Texture {
+            pixels: resampled
+                .iter()
+                .map(|c| self.color_space.encode(c))
+                .collect(),
+            width,
+            height,
+            color_space: self.color_space,
+        }
+    }
+
+    /// Loads an image file, tagged as sRGB like the color textures of most models. Use
+    /// [`Texture::with_color_space`] for data textures.
     #[profiling::function]
     pub fn load_tga_texture<P: AsRef<Path>>(path: P) -> Result<Self> {
         let path = path.as_ref();
@@ -38,6 +91,7 @@ impl Texture {
             pixels,
             width,
             height,
+            color_space: ColorSpace::Srgb,
         })
     }
 }
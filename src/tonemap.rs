@@ -0,0 +1,161 @@
+use crate::math::Vec3;
+
+/// Curve compressing linear high dynamic range colors into 0..1.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ToneMapper {
+    /// Clips every channel at 1, like an 8-bit target.
+    Clamp,
+    /// `c / (1 + c)` per channel, never reaches white.
+    Reinhard,
+    /// Stephen Hill's fit of the ACES reference rendering and output transforms.
+    #[default]
+    Aces,
+    /// John Hable's filmic curve from Uncharted 2, with a white point of 11.2.
+    Uncharted2,
+    /// Troy Sobotka's AgX with the default look, desaturates very bright colors towards white
+    /// instead of skewing their hue.
+    AgX,
+}
+
+/// How [`Renderer::resolve`](crate::renderer::Renderer::resolve) turns the high dynamic range
+/// color buffer into displayable pixels.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ToneMapping {
+    /// In stops, every step up doubles the brightness before tone mapping.
+    pub exposure: f32,
+    pub tone_mapper: ToneMapper,
+}
+
+impl ToneMapping {
+    pub fn new(exposure: f32, tone_mapper: ToneMapper) -> Self {
+        ToneMapping {
+            exposure,
+            tone_mapper,
+        }
+    }
+
+    /// Exposes and tone maps a linear color, the result is linear and in 0..1.
+    pub fn apply(&self, color: &Vec3) -> Vec3 {
+        let c = *color * self.exposure.exp2();
+        let mapped = match self.tone_mapper {
+            ToneMapper::Clamp => c,
+            ToneMapper::Reinhard => per_channel(&c, |x| x / (1.0 + x)),
+            ToneMapper::Aces => aces(&c),
+            ToneMapper::Uncharted2 => {
+                const WHITE: f32 = 11.2;
+                let scale = 1.0 / hable(WHITE);
+                per_channel(&c, |x| hable(x * 2.0) * scale)
+            }
+            ToneMapper::AgX => agx(&c),
+        };
+        per_channel(&mapped, |x| x.clamp(0.0, 1.0))
+    }
+}
+
+fn per_channel(c: &Vec3, f: impl Fn(f32) -> f32) -> Vec3 {
+    Vec3::new(f(c.x), f(c.y), f(c.z))
+}
+
+/// Multiplies `v` by the matrix with the given rows.
+fn mul_rows(rows: &[[f32; 3]; 3], v: &Vec3) -> Vec3 {
+    let row = |r: &[f32; 3]| r[0] * v.x + r[1] * v.y + r[2] * v.z;
+    Vec3::new(row(&rows[0]), row(&rows[1]), row(&rows[2]))
+}
+
+fn aces(c: &Vec3) -> Vec3 {
+    // sRGB to the ACES rendering space, with the RRT's saturation adjustment folded in
+    const INPUT: [[f32; 3]; 3] = [
+        [0.59719, 0.35458, 0.04823],
+        [0.07600, 0.90834, 0.01566],
+        [0.02840, 0.13383, 0.83777],
+    ];
+    const OUTPUT: [[f32; 3]; 3] = [
+        [1.60475, -0.53108, -0.07367],
+        [-0.10208, 1.10813, -0.00605],
+        [-0.00327, -0.07276, 1.07602],
+    ];
+    let v = mul_rows(&INPUT, c);
+    let v = per_channel(&v, |x| {
+        (x * (x + 0.0245786) - 0.000090537) / (x * (0.983729 * x + 0.432951) + 0.238081)
+    });
+    mul_rows(&OUTPUT, &v)
+}
+
+fn hable(x: f32) -> f32 {
+    const A: f32 = 0.15;
+    const B: f32 = 0.50;
+    const C: f32 = 0.10;
+    const D: f32 = 0.20;
+    const E: f32 = 0.02;
+    const F: f32 = 0.30;
+    (x * (A * x + C * B) + D * E) / (x * (A * x + B) + D * F) - E / F
+}
+
+fn agx(c: &Vec3) -> Vec3 {
+    // inset into the AgX working space, log encode, apply the sigmoid and outset again
+    const INSET: [[f32; 3]; 3] = [
+        [0.84247906, 0.0784336, 0.07922375],
+        [0.04232824, 0.87846864, 0.07916613],
+        [0.04237565, 0.0784336, 0.879143],
+    ];
+    const OUTSET: [[f32; 3]; 3] = [
+        [1.196879, -0.09802088, -0.09902974],
+        [-0.05289685, 1.1519031, -0.09896118],
+        [-0.05297164, -0.09804345, 1.1510737],
+    ];
+    const MIN_EV: f32 = -12.47393;
+    const MAX_EV: f32 = 4.026069;
+    let v = mul_rows(&INSET, c);
+    let v = per_channel(&v, |x| {
+        let x = (x.max(1e-10).log2().clamp(MIN_EV, MAX_EV) - MIN_EV) / (MAX_EV - MIN_EV);
+        // polynomial fit of the default contrast curve
+        let x2 = x * x;
+        let x4 = x2 * x2;
+        15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x
+            - 0.00232
+    });
+    // the curve's output is display encoded with a 2.2 gamma
+    per_channel(&mul_rows(&OUTSET, &v), |x| x.max(0.0).powf(2.2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAPPERS: [ToneMapper; 5] = [
+        ToneMapper::Clamp,
+        ToneMapper::Reinhard,
+        ToneMapper::Aces,
+        ToneMapper::Uncharted2,
+        ToneMapper::AgX,
+    ];
+
+    #[test]
+    fn test_tone_mappers() {
+        for tone_mapper in MAPPERS {
+            let mapping = ToneMapping::new(0.0, tone_mapper);
+            let black = mapping.apply(&Vec3::ZERO);
+            assert!(black.x < 0.01, "{:?} {:?}", tone_mapper, black);
+            // gray levels stay ordered and within 0..1
+            let mut previous = -1.0;
+            for i in 0..64 {
+                let x = (i as f32 * 0.25 - 8.0).exp2();
+                let y = mapping.apply(&Vec3::splat(x));
+                assert!((0.0..=1.0).contains(&y.x), "{:?} {}", tone_mapper, y.x);
+                assert!(y.x >= previous - 1e-6, "{:?} at {}", tone_mapper, x);
+                previous = y.x;
+            }
+            if tone_mapper != ToneMapper::Reinhard {
+                assert!(previous > 0.9, "{:?} {}", tone_mapper, previous);
+            }
+        }
+
+        let reinhard = ToneMapping::new(0.0, ToneMapper::Reinhard);
+        assert_eq!(reinhard.apply(&Vec3::ONE), Vec3::splat(0.5));
+        let exposed = ToneMapping::new(1.0, ToneMapper::Reinhard);
+        assert_eq!(exposed.apply(&Vec3::splat(0.5)), Vec3::splat(0.5));
+        // the filmic curves keep highlights apart that clamping merges
+        let aces = ToneMapping::default();
+        assert!(aces.apply(&Vec3::splat(4.0)).x > aces.apply(&Vec3::splat(2.0)).x + 0.02);
+    }
+}
@@ -0,0 +1,141 @@
+use crate::math::{Mat4, Vec3};
+
+/// Region of the target that normalized device coordinates are mapped to. Pixels outside of it
+/// are never rasterized.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Viewport {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    /// Window depth of the far plane, fragments are clipped at it.
+    pub min_depth: f32,
+    /// Window depth of the near plane, fragments are clipped at it.
+    pub max_depth: f32,
+}
+
+impl Viewport {
+    pub const fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Viewport {
+            x,
+            y,
+            width,
+            height,
+            min_depth: 0.0,
+            max_depth: 1.0,
+        }
+    }
+
+    /// Maps x, y and z from [-1, 1] to window coordinates and the depth range.
+    pub fn matrix(&self) -> Mat4 {
+        let mut m = Mat4::identity();
+        m[(0, 3)] = self.x + self.width / 2.0;
+        m[(1, 3)] = self.y + self.height / 2.0;
+        m[(2, 3)] = (self.max_depth + self.min_depth) / 2.0;
+        m[(0, 0)] = self.width / 2.0;
+        m[(1, 1)] = self.height / 2.0;
+        m[(2, 2)] = (self.max_depth - self.min_depth) / 2.0;
+        m
+    }
+
+    pub fn to_window(&self, ndc: &Vec3) -> Vec3 {
+        Vec3 {
+            x: self.x + (ndc.x + 1.0) * self.width / 2.0,
+            y: self.y + (ndc.y + 1.0) * self.height / 2.0,
+            z: self.min_depth + (ndc.z + 1.0) * (self.max_depth - self.min_depth) / 2.0,
+        }
+    }
+
+    /// Inverse of [`Viewport::to_window`].
+    pub fn to_ndc(&self, window: &Vec3) -> Vec3 {
+        Vec3 {
+            x: (window.x - self.x) * 2.0 / self.width - 1.0,
+            y: (window.y - self.y) * 2.0 / self.height - 1.0,
+            z: (window.z - self.min_depth) * 2.0 / (self.max_depth - self.min_depth) - 1.0,
+        }
+    }
+
+    /// Pixels whose centers lie inside the viewport, as a rectangle clamped to the target.
+    pub fn pixel_rect(&self, target_width: u32, target_height: u32) -> ScissorRect {
+        let x0 = self.x.round().clamp(0.0, target_width as f32) as u32;
+        let y0 = self.y.round().clamp(0.0, target_height as f32) as u32;
+        let x1 = (self.x + self.width)
+            .round()
+            .clamp(x0 as f32, target_width as f32) as u32;
+        let y1 = (self.y + self.height)
+            .round()
+            .clamp(y0 as f32, target_height as f32) as u32;
+        ScissorRect::new(x0, y0, x1 - x0, y1 - y0)
+    }
+}
+
+/// Rectangle in pixels outside of which nothing is written.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ScissorRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl ScissorRect {
+    pub const fn new(x: u32, y: u32, width: u32, height: u32) -> Self {
+        ScissorRect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    pub fn contains(&self, x: u32, y: u32) -> bool {
+        x >= self.x && y >= self.y && x < self.x + self.width && y < self.y + self.height
+    }
+
+    pub fn intersect(&self, other: &ScissorRect) -> ScissorRect {
+        let x0 = self.x.max(other.x);
+        let y0 = self.y.max(other.y);
+        let x1 = (self.x + self.width).min(other.x + other.width).max(x0);
+        let y1 = (self.y + self.height).min(other.y + other.height).max(y0);
+        ScissorRect::new(x0, y0, x1 - x0, y1 - y0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::{vec3, Mat4x1};
+
+    #[test]
+    fn test_viewport_mapping() {
+        let viewport = Viewport {
+            min_depth: 0.0,
+            max_depth: 255.0,
+            ..Viewport::new(100.0, 100.0, 600.0, 400.0)
+        };
+        let ndc = vec3(-1.0, 1.0, 0.0);
+        let window = viewport.to_window(&ndc);
+        assert_eq!(window, vec3(100.0, 500.0, 127.5));
+        assert_eq!(
+            viewport.matrix().mul_mat41(&Mat4x1::from(ndc)).to_vec3(),
+            window
+        );
+    }
+
+    #[test]
+    fn test_rect_clamping() {
+        let viewport = Viewport::new(-10.0, 4.4, 100.0, 100.0);
+        assert_eq!(viewport.pixel_rect(50, 50), ScissorRect::new(0, 4, 50, 46));
+
+        let a = ScissorRect::new(0, 0, 10, 10);
+        let b = ScissorRect::new(5, 8, 10, 10);
+        assert_eq!(a.intersect(&b), ScissorRect::new(5, 8, 5, 2));
+        assert!(a.intersect(&ScissorRect::new(20, 20, 1, 1)).is_empty());
+        assert!(b.contains(5, 8));
+        assert!(!b.contains(15, 8));
+    }
+}